@@ -0,0 +1,219 @@
+use std::collections::{BTreeMap, VecDeque};
+use std::panic::{self, AssertUnwindSafe};
+use std::sync::{Condvar, Mutex, MutexGuard, OnceLock, PoisonError};
+use std::thread;
+use std::time::{Duration, Instant};
+use futures::channel::oneshot;
+
+/// The most threads running blocking jobs at once. Jobs submitted while every thread is busy wait
+/// for one of them to be free.
+const MAX_THREADS: usize = 32;
+
+type Job = Box<dyn FnOnce() + Send>;
+
+/// Runs `job` on a shared pool of threads, so that awaiting it doesn't block the thread polling
+/// the returned future. Returns `None` if the job panicked.
+///
+/// The job is skipped if the returned future is dropped before a thread picks it up. A job that
+/// already started runs to completion, as there's no way to interrupt it.
+pub(crate) async fn spawn<T: Send + 'static>(job: impl FnOnce() -> T + Send + 'static) -> Option<T> {
+    let (sender, receiver) = oneshot::channel();
+    pool().submit(Box::new(move || {
+        if !sender.is_canceled() {
+            // A panic drops the sender, which the receiver sees as a cancellation.
+            let _ = sender.send(job());
+        }
+    }));
+    receiver.await.ok()
+}
+
+/// Completes once `duration` has elapsed, without blocking the thread polling the returned future.
+///
+/// Every sleep is tracked by a single timer thread.
+pub(crate) async fn sleep(duration: Duration) {
+    #[cfg(test)]
+    if let Some(elapsed) = PAUSED.get() {
+        PAUSED.set(Some(elapsed + duration));
+        return;
+    }
+    if duration.is_zero() {
+        return;
+    }
+    let (sender, receiver) = oneshot::channel();
+    timer().schedule(Instant::now() + duration, sender);
+    let _ = receiver.await;
+}
+
+#[cfg(test)]
+thread_local! {
+    /// The time slept on this thread while it's paused, see [paused].
+    static PAUSED: std::cell::Cell<Option<Duration>> = const { std::cell::Cell::new(None) };
+}
+
+/// Runs `f` with the sleeps polled by the current thread completing at once, and returns the time
+/// they would have taken. Concurrent sleeps are added up, so this only measures sequential ones,
+/// such as the delays between retries.
+#[cfg(test)]
+pub(crate) fn paused<T>(f: impl FnOnce() -> T) -> (T, Duration) {
+    PAUSED.set(Some(Duration::ZERO));
+    let output = f();
+    (output, PAUSED.take().unwrap_or_default())
+}
+
+fn lock<T>(mutex: &Mutex<T>) -> MutexGuard<'_, T> {
+    mutex.lock().unwrap_or_else(PoisonError::into_inner)
+}
+
+#[derive(Default)]
+struct Pool {
+    state: Mutex<PoolState>,
+    submitted: Condvar,
+}
+
+#[derive(Default)]
+struct PoolState {
+    jobs: VecDeque<Job>,
+    threads: usize,
+    /// The threads waiting for a job.
+    idle: usize,
+}
+
+fn pool() -> &'static Pool {
+    static POOL: OnceLock<Pool> = OnceLock::new();
+    POOL.get_or_init(Pool::default)
+}
+
+impl Pool {
+    fn submit(&'static self, job: Job) {
+        let mut state = lock(&self.state);
+        state.jobs.push_back(job);
+        // Each idle thread takes one of the pending jobs, a new thread is needed for the others.
+        if state.jobs.len() > state.idle && state.threads < MAX_THREADS {
+            state.threads += 1;
+            thread::spawn(move || self.work());
+        } else {
+            self.submitted.notify_one();
+        }
+    }
+
+    fn work(&self) {
+        let mut state = lock(&self.state);
+        loop {
+            match state.jobs.pop_front() {
+                Some(job) => {
+                    drop(state);
+                    // The panic is reported by `spawn`, the thread keeps serving jobs.
+                    let _ = panic::catch_unwind(AssertUnwindSafe(job));
+                    state = lock(&self.state);
+                }
+                None => {
+                    state.idle += 1;
+                    state = self.submitted.wait(state).unwrap_or_else(PoisonError::into_inner);
+                    state.idle -= 1;
+                }
+            }
+        }
+    }
+}
+
+#[derive(Default)]
+struct Timer {
+    sleeps: Mutex<Sleeps>,
+    scheduled: Condvar,
+}
+
+#[derive(Default)]
+struct Sleeps {
+    /// The pending sleeps, by deadline and then by order of scheduling.
+    pending: BTreeMap<(Instant, u64), oneshot::Sender<()>>,
+    scheduled: u64,
+}
+
+fn timer() -> &'static Timer {
+    static TIMER: OnceLock<Timer> = OnceLock::new();
+    TIMER.get_or_init(|| {
+        thread::spawn(|| timer().run());
+        Timer::default()
+    })
+}
+
+impl Timer {
+    fn schedule(&self, deadline: Instant, sender: oneshot::Sender<()>) {
+        let mut sleeps = lock(&self.sleeps);
+        // The sleeps that were dropped would otherwise be kept until their deadline.
+        sleeps.pending.retain(|_, sender| !sender.is_canceled());
+        sleeps.scheduled += 1;
+        let key = (deadline, sleeps.scheduled);
+        sleeps.pending.insert(key, sender);
+        self.scheduled.notify_one();
+    }
+
+    fn run(&self) {
+        let mut sleeps = lock(&self.sleeps);
+        loop {
+            let now = Instant::now();
+            while let Some(entry) = sleeps.pending.first_entry().filter(|entry| entry.key().0 <= now) {
+                // The receiver is gone if the sleep was dropped.
+                let _ = entry.remove().send(());
+            }
+            sleeps = match sleeps.pending.first_key_value() {
+                Some(((deadline, _), _)) => {
+                    let timeout = deadline.saturating_duration_since(now);
+                    self.scheduled.wait_timeout(sleeps, timeout).unwrap_or_else(PoisonError::into_inner).0
+                }
+                None => self.scheduled.wait(sleeps).unwrap_or_else(PoisonError::into_inner),
+            };
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::mpsc;
+    use futures::executor::block_on;
+    use futures::future::{select, Either};
+    use futures::join;
+    use super::*;
+    use rstest::*;
+
+    #[rstest]
+    fn spawn_returns_result() {
+        assert_eq!(block_on(spawn(|| 1 + 1)), Some(2));
+    }
+
+    #[rstest]
+    fn spawn_reports_panic() {
+        assert_eq!(block_on(spawn(|| -> u32 { panic!("Something happened") })), None);
+    }
+
+    #[rstest]
+    fn spawn_runs_jobs_concurrently() {
+        // Each job only returns once it received from the other, so they must overlap. The timeout
+        // fails the test instead of hanging it when they don't.
+        let timeout = Duration::from_secs(30);
+        let (first_sender, first_receiver) = mpsc::channel();
+        let (second_sender, second_receiver) = mpsc::channel();
+        let first = spawn(move || second_sender.send(()).is_ok() && first_receiver.recv_timeout(timeout).is_ok());
+        let second = spawn(move || first_sender.send(()).is_ok() && second_receiver.recv_timeout(timeout).is_ok());
+
+        assert_eq!(block_on(async { join!(first, second) }), (Some(true), Some(true)));
+    }
+
+    #[rstest]
+    fn sleep_waits_in_order() {
+        let short = Box::pin(sleep(Duration::from_millis(1)));
+        let long = Box::pin(sleep(Duration::from_secs(86400)));
+
+        assert!(matches!(block_on(select(long, short)), Either::Right(_)));
+    }
+
+    #[rstest]
+    fn paused_sleeps_complete_at_once() {
+        let ((), elapsed) = paused(|| block_on(async {
+            sleep(Duration::from_secs(86400)).await;
+            sleep(Duration::from_secs(1)).await;
+        }));
+
+        assert_eq!(elapsed, Duration::from_secs(86401));
+    }
+}
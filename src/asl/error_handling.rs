@@ -1,74 +1,128 @@
+use std::fmt::{Display, Formatter};
+use std::time::Duration;
+use rand::Rng;
 use serde_json::Number;
 use serde::Deserialize;
-use crate::asl::types::MyJsonPath;
+use crate::asl::execution::ExecutionError;
+use crate::asl::json_path::ReferencePath;
+use crate::asl::state_machine::reference_path_default;
 
-// TODO: Maybe this could be a parameter. It could be a string or a parameter type of the StateMachine...
-#[derive(Deserialize, Debug, PartialEq, Eq)]
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "UPPERCASE")]
 enum JitterStrategy {
-    // TODO: Check which values we want to implement here
-    FULL,
+    /// Each delay is picked at random between 0 and the computed backoff.
+    Full,
+    /// The computed backoff is used as is.
+    None,
 }
 
 /// See https://states-language.net/spec.html#appendix-a
-#[derive(Deserialize, Debug, PartialEq, Eq)]
-#[serde(untagged)]
-enum ErrorName {
+#[derive(Deserialize, Debug, Clone, PartialEq, Eq)]
+#[serde(from = "String")]
+pub enum ErrorName {
     /// A wildcard which matches any Error Name.
-    #[serde(rename = "States.ALL")]
     StatesALL,
 
     /// A Task State failed to heartbeat for a time longer than the "HeartbeatSeconds" value.
-    #[serde(rename = "States.HeartbeatTimeout")]
     StatesHeartbeatTimeout,
 
     /// A Task State either ran longer than the "TimeoutSeconds" value, or failed to heartbeat for a
     /// time longer than the "HeartbeatSeconds" value.
-    #[serde(rename = "States.Timeout")]
     StatesTimeout,
 
     /// A Task State failed during the execution.
-    #[serde(rename = "States.TaskFailed")]
     StatesTaskFailed,
 
     /// A Task State failed because it had insufficient privileges to execute the specified code.
-    #[serde(rename = "States.Permissions")]
     StatesPermissions,
 
     /// A state’s "ResultPath" field cannot be applied to the input the state received.
-    #[serde(rename = "States.ResultPathMatchFailure")]
     StatesResultPathMatchFailure,
 
     /// Within a state’s "Parameters" field, the attempt to replace a field whose name ends in ".$" using a Path failed.
-    #[serde(rename = "States.ParameterPathFailure")]
     StatesParameterPathFailure,
 
     /// A branch of a Parallel State failed.
-    #[serde(rename = "States.BranchFailed")]
     StatesBranchFailed,
 
     /// A Choice State failed to find a match for the condition field extracted from its input.
-    #[serde(rename = "States.NoChoiceMatched")]
     StatesNoChoiceMatched,
 
     /// Within a Payload Template, the attempt to invoke an Intrinsic Function failed.
-    #[serde(rename = "States.IntrinsicFailure")]
     StatesIntrinsicFailure,
 
     /// A Map state failed because the number of failed items exceeded the configured tolerated failure threshold.
-    #[serde(rename = "States.ExceedToleratedFailureThreshold")]
     StatesExceedToleratedFailureThreshold,
 
     /// A Map state failed to read all items as specified by the "ItemReader" field.
-    #[serde(rename = "States.ItemReaderFailed")]
     StatesItemReaderFailed,
 
     /// A Map state failed to write all results as specified by the "ResultWriter" field.
-    #[serde(rename = "States.ResultWriterFailed")]
     StatesResultWriterFailed,
 
+    /// Not part of the spec, but used by AWS: the execution failed due to an exception the
+    /// interpreter couldn't process, for example a Task whose "Resource" has no handler.
+    StatesRuntime,
+
     Custom(String),
 }
 
+impl ErrorName {
+    pub fn as_str(&self) -> &str {
+        match self {
+            ErrorName::StatesALL => "States.ALL",
+            ErrorName::StatesHeartbeatTimeout => "States.HeartbeatTimeout",
+            ErrorName::StatesTimeout => "States.Timeout",
+            ErrorName::StatesTaskFailed => "States.TaskFailed",
+            ErrorName::StatesPermissions => "States.Permissions",
+            ErrorName::StatesResultPathMatchFailure => "States.ResultPathMatchFailure",
+            ErrorName::StatesParameterPathFailure => "States.ParameterPathFailure",
+            ErrorName::StatesBranchFailed => "States.BranchFailed",
+            ErrorName::StatesNoChoiceMatched => "States.NoChoiceMatched",
+            ErrorName::StatesIntrinsicFailure => "States.IntrinsicFailure",
+            ErrorName::StatesExceedToleratedFailureThreshold => "States.ExceedToleratedFailureThreshold",
+            ErrorName::StatesItemReaderFailed => "States.ItemReaderFailed",
+            ErrorName::StatesResultWriterFailed => "States.ResultWriterFailed",
+            ErrorName::StatesRuntime => "States.Runtime",
+            ErrorName::Custom(name) => name,
+        }
+    }
+}
+
+impl From<&str> for ErrorName {
+    fn from(name: &str) -> Self {
+        match name {
+            "States.ALL" => ErrorName::StatesALL,
+            "States.HeartbeatTimeout" => ErrorName::StatesHeartbeatTimeout,
+            "States.Timeout" => ErrorName::StatesTimeout,
+            "States.TaskFailed" => ErrorName::StatesTaskFailed,
+            "States.Permissions" => ErrorName::StatesPermissions,
+            "States.ResultPathMatchFailure" => ErrorName::StatesResultPathMatchFailure,
+            "States.ParameterPathFailure" => ErrorName::StatesParameterPathFailure,
+            "States.BranchFailed" => ErrorName::StatesBranchFailed,
+            "States.NoChoiceMatched" => ErrorName::StatesNoChoiceMatched,
+            "States.IntrinsicFailure" => ErrorName::StatesIntrinsicFailure,
+            "States.ExceedToleratedFailureThreshold" => ErrorName::StatesExceedToleratedFailureThreshold,
+            "States.ItemReaderFailed" => ErrorName::StatesItemReaderFailed,
+            "States.ResultWriterFailed" => ErrorName::StatesResultWriterFailed,
+            "States.Runtime" => ErrorName::StatesRuntime,
+            custom => ErrorName::Custom(custom.to_string()),
+        }
+    }
+}
+
+impl From<String> for ErrorName {
+    fn from(name: String) -> Self {
+        ErrorName::from(name.as_str())
+    }
+}
+
+impl Display for ErrorName {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
 #[derive(Deserialize, Debug, PartialEq, Eq)]
 #[serde(rename_all = "PascalCase")]
 pub struct Retrier {
@@ -84,6 +138,49 @@ pub struct Retrier {
     jitter_strategy: Option<JitterStrategy>,
 }
 
+impl Retrier {
+    pub fn error_equals(&self) -> &[ErrorName] {
+        &self.error_equals
+    }
+
+    /// The maximum number of retries, 0 meaning the error is never retried.
+    pub fn max_attempts(&self) -> u32 {
+        self.max_attempts
+    }
+
+    /// Whether the retrier applies to the given error.
+    pub(crate) fn matches(&self, error: &ExecutionError) -> bool {
+        error_matches(&self.error_equals, error)
+    }
+
+    /// How long to wait before the retry following `attempt` previous retries by this retrier.
+    ///
+    /// The delay grows exponentially from "IntervalSeconds" by "BackoffRate", is capped by
+    /// "MaxDelaySeconds", then randomized according to "JitterStrategy".
+    pub(crate) fn delay(&self, attempt: u32) -> Duration {
+        let interval = self.interval_seconds.as_f64().unwrap_or_default();
+        let backoff_rate = self.backoff_rate.as_f64().unwrap_or_default();
+        let mut seconds = interval * backoff_rate.powi(i32::try_from(attempt).unwrap_or(i32::MAX));
+        if let Some(max_delay) = self.max_delay_seconds.as_ref().and_then(Number::as_f64) {
+            seconds = seconds.min(max_delay);
+        }
+        if self.jitter_strategy == Some(JitterStrategy::Full) {
+            seconds = rand::thread_rng().gen_range(0.0..=seconds.max(0.0));
+        }
+        Duration::try_from_secs_f64(seconds).unwrap_or(Duration::MAX)
+    }
+}
+
+/// Whether an error matches one of the names of an "ErrorEquals" field.
+///
+/// `States.ALL` matches any error, except `States.Runtime` which can't be retried nor caught.
+fn error_matches(error_equals: &[ErrorName], error: &ExecutionError) -> bool {
+    if error.error == Some(ErrorName::StatesRuntime) {
+        return false;
+    }
+    error_equals.iter().any(|name| *name == ErrorName::StatesALL || Some(name) == error.error.as_ref())
+}
+
 fn max_attempts_default() -> u32 {
     3
 }
@@ -102,5 +199,75 @@ fn backoff_rate_default() -> Number {
 pub struct Catcher {
     error_equals: Vec<ErrorName>,
     next: String,
-    result_path: Option<MyJsonPath>
+    #[serde(default = "reference_path_default")]
+    result_path: Option<ReferencePath>
+}
+
+impl Catcher {
+    pub fn error_equals(&self) -> &[ErrorName] {
+        &self.error_equals
+    }
+
+    /// The state to transition to when the catcher matches.
+    pub fn next(&self) -> &str {
+        &self.next
+    }
+
+    /// Where the error output is placed in the state input, `None` meaning it's discarded.
+    pub fn result_path(&self) -> &Option<ReferencePath> {
+        &self.result_path
+    }
+
+    /// Whether the catcher applies to the given error.
+    pub(crate) fn matches(&self, error: &ExecutionError) -> bool {
+        error_matches(&self.error_equals, error)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rstest::*;
+    use serde_json::json;
+
+    fn retrier(retrier: serde_json::Value) -> Retrier {
+        serde_json::from_value(retrier).unwrap()
+    }
+
+    #[rstest]
+    #[case::defaults(json!({ "ErrorEquals": ["States.ALL"] }), vec![1.0, 2.0, 4.0, 8.0])]
+    #[case::backoff_rate(json!({ "ErrorEquals": ["States.ALL"], "IntervalSeconds": 2, "BackoffRate": 1.5 }), vec![2.0, 3.0, 4.5])]
+    #[case::max_delay(json!({ "ErrorEquals": ["States.ALL"], "IntervalSeconds": 3, "MaxDelaySeconds": 10 }), vec![3.0, 6.0, 10.0, 10.0])]
+    #[case::no_jitter(json!({ "ErrorEquals": ["States.ALL"], "JitterStrategy": "NONE" }), vec![1.0, 2.0])]
+    fn delay(#[case] retrier_definition: serde_json::Value, #[case] expected: Vec<f64>) {
+        let retrier = retrier(retrier_definition);
+
+        let delays: Vec<f64> = (0..expected.len() as u32).map(|attempt| retrier.delay(attempt).as_secs_f64()).collect();
+
+        assert_eq!(delays, expected);
+    }
+
+    #[rstest]
+    fn full_jitter_stays_within_backoff() {
+        let retrier = retrier(json!({ "ErrorEquals": ["States.ALL"], "IntervalSeconds": 2, "MaxDelaySeconds": 5, "JitterStrategy": "FULL" }));
+
+        for attempt in 0..10 {
+            let expected_max = Duration::from_secs(5).min(Duration::from_secs(2 << attempt));
+            assert!(retrier.delay(attempt) <= expected_max);
+        }
+    }
+
+    #[rstest]
+    #[case::named(json!(["Custom", "States.Timeout"]), Some(ErrorName::StatesTimeout), true)]
+    #[case::custom(json!(["Custom"]), Some(ErrorName::Custom("Custom".to_string())), true)]
+    #[case::other(json!(["Custom"]), Some(ErrorName::StatesTaskFailed), false)]
+    #[case::all(json!(["States.ALL"]), Some(ErrorName::StatesTaskFailed), true)]
+    #[case::all_unnamed(json!(["States.ALL"]), None, true)]
+    #[case::unnamed(json!(["Custom"]), None, false)]
+    #[case::runtime(json!(["States.ALL", "States.Runtime"]), Some(ErrorName::StatesRuntime), false)]
+    fn matches(#[case] error_equals: serde_json::Value, #[case] error: Option<ErrorName>, #[case] expected: bool) {
+        let retrier = retrier(json!({ "ErrorEquals": error_equals }));
+
+        assert_eq!(retrier.matches(&ExecutionError { error, cause: None }), expected);
+    }
 }
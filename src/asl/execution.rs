@@ -0,0 +1,2023 @@
+use std::collections::HashMap;
+use std::fmt::{Display, Formatter};
+use std::sync::{Arc, Mutex, PoisonError};
+use std::time::Duration;
+use chrono::{DateTime, Utc};
+use futures::executor::block_on;
+use futures::future::{try_join_all, BoxFuture};
+use futures::{stream, FutureExt, StreamExt, TryFutureExt};
+use serde_json::{json, Number, Value};
+use uuid::Uuid;
+use crate::asl::error_handling::{ErrorName, Retrier};
+use crate::asl::intrinsic_functions::IntrinsicFunction;
+use crate::asl::json_path::{JsonPath, ReferencePath};
+use crate::asl::payload_template::PayloadTemplate;
+use crate::asl::blocking;
+use crate::asl::resources::{Handler, Resources};
+use crate::asl::state_machine::{EndOrNext, State, StateMachine};
+use crate::asl::states::choice;
+use crate::asl::states::fail::{FailStateCauseField, FailStateErrorField};
+use crate::asl::result_writer::MapRunResults;
+use crate::asl::states::map::{ExecutionType, ItemReaderConfiguration, MapStateIterator, ProcessorMode, ResultWriterConfiguration};
+use crate::asl::states::wait::WaitDuration;
+
+/// The error raised by a state, as defined in https://states-language.net/spec.html#errors
+///
+/// Both fields are optional because a Fail state is allowed to omit its "Error" and "Cause".
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ExecutionError {
+    pub error: Option<ErrorName>,
+    pub cause: Option<String>,
+}
+
+impl ExecutionError {
+    pub fn new(error: ErrorName, cause: impl Into<String>) -> Self {
+        ExecutionError {
+            error: Some(error),
+            cause: Some(cause.into()),
+        }
+    }
+
+    pub(crate) fn runtime(cause: impl Into<String>) -> Self {
+        ExecutionError::new(ErrorName::StatesRuntime, cause)
+    }
+}
+
+impl Display for ExecutionError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match (&self.error, &self.cause) {
+            (Some(error), Some(cause)) => write!(f, "{error}: {cause}"),
+            (Some(error), None) => write!(f, "{error}"),
+            (None, Some(cause)) => write!(f, "{cause}"),
+            (None, None) => write!(f, "Execution failed"),
+        }
+    }
+}
+
+impl std::error::Error for ExecutionError {}
+
+/// The status of an [Execution].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ExecutionStatus {
+    /// The execution hasn't reached a terminal state yet.
+    Running,
+    /// The execution reached a terminal state, with the given output.
+    Succeeded(Value),
+    /// A state failed and the error wasn't handled.
+    Failed(ExecutionError),
+}
+
+/// A single state transition of an [Execution], as returned by [Execution::next].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct StateExecution<'a> {
+    /// The name of the state that ran.
+    pub name: &'a str,
+    /// The input the state received.
+    pub input: Value,
+    /// The output of the state, or the error it raised.
+    pub output: Result<Value, ExecutionError>,
+    /// The state the execution transitions to, or `None` if the state ended the execution.
+    pub next: Option<&'a str>,
+}
+
+/// A child execution started by a distributed Map state, see [Execution::child_executions].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ChildExecution<'a> {
+    /// The unique id of the child execution, also available as `$$.Execution.Id` within it.
+    pub id: String,
+    /// The id of the execution running the Map state.
+    pub parent_id: String,
+    /// The name of the Map state that started the child execution.
+    pub map_state: &'a str,
+    pub execution_type: Option<ExecutionType>,
+    /// The input of the child execution, i.e. its item or batch of items.
+    pub input: Value,
+    pub status: ExecutionStatus,
+    /// The state transitions of the child execution, in order.
+    pub history: Vec<StateExecution<'a>>,
+}
+
+/// How an execution waits, either in Wait states or between retries, and runs blocking handlers.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Runtime {
+    /// Blocks the current thread until the execution is done. Waits are tracked by a timer thread
+    /// and blocking handlers run on a bounded pool of threads, so that the branches of a Parallel
+    /// state and the iterations of a Map state polled by the current thread still run concurrently.
+    Blocking,
+    /// Sleeps on the tokio timer and runs blocking handlers on the tokio blocking thread pool,
+    /// without blocking the thread.
+    #[cfg(feature = "tokio")]
+    Tokio,
+}
+
+impl Runtime {
+    async fn sleep(self, duration: Duration) {
+        match self {
+            Runtime::Blocking => blocking::sleep(duration).await,
+            #[cfg(feature = "tokio")]
+            Runtime::Tokio => tokio::time::sleep(duration).await,
+        }
+    }
+
+    /// Runs a blocking `job` without blocking the thread polling the returned future. Returns
+    /// `None` if the job panicked.
+    async fn spawn_blocking<T: Send + 'static>(self, job: impl FnOnce() -> T + Send + 'static) -> Option<T> {
+        match self {
+            Runtime::Blocking => blocking::spawn(job).await,
+            #[cfg(feature = "tokio")]
+            Runtime::Tokio => tokio::task::spawn_blocking(job).await.ok(),
+        }
+    }
+}
+
+/// A single run of a [StateMachine], created by [StateMachine::start].
+///
+/// An execution is driven one state at a time, as an [Iterator]: each call to `next()` runs the
+/// current state and transitions to the next one. Use [Execution::run] to drive it to completion.
+///
+/// The branches of a Parallel state and the iterations of a Map state run concurrently: blocking
+/// resource handlers run on a bounded pool of threads and Wait states on a timer thread while the
+/// current thread waits for them. When a branch or iteration fails, the others are dropped and
+/// their results ignored, although a blocking handler that already started runs to completion on
+/// its thread.
+///
+/// With the `tokio` feature, the execution can also be driven asynchronously with
+/// `Execution::next_async` and `Execution::run_async`. In that mode async resource handlers are
+/// awaited, Wait states don't block the thread and blocking resource handlers run on the tokio
+/// blocking thread pool.
+pub struct Execution<'a> {
+    states: &'a HashMap<String, State>,
+    resources: &'a Resources,
+    id: String,
+    current_state: Option<&'a str>,
+    input: Value,
+    /// The Context Object, see https://states-language.net/spec.html#contextobject
+    context: Value,
+    status: ExecutionStatus,
+    /// The child executions of distributed Map states, shared by the whole tree of executions.
+    child_executions: Arc<Mutex<Vec<ChildExecution<'a>>>>,
+}
+
+impl<'a> Execution<'a> {
+    pub(crate) fn new(state_machine: &'a StateMachine, input: Value) -> Self {
+        let id = Uuid::new_v4().to_string();
+        let context = json!({
+            "Execution": {
+                "Id": id,
+                "Input": input,
+                "StartTime": Utc::now().to_rfc3339(),
+            },
+        });
+        Execution {
+            states: &state_machine.definition.states,
+            resources: &state_machine.resources,
+            id,
+            current_state: Some(state_machine.definition.start_at.as_str()),
+            input,
+            context,
+            status: ExecutionStatus::Running,
+            child_executions: Arc::default(),
+        }
+    }
+
+    /// Creates the execution of a nested state machine, such as a branch of a Parallel state or an
+    /// iteration of a Map state. It shares the resources of its parent.
+    fn nested(parent: &Execution<'a>, start_at: &'a str, states: &'a HashMap<String, State>, input: Value, context: Value) -> Self {
+        Execution {
+            states,
+            resources: parent.resources,
+            id: parent.id.clone(),
+            current_state: Some(start_at),
+            input,
+            context,
+            status: ExecutionStatus::Running,
+            child_executions: parent.child_executions.clone(),
+        }
+    }
+
+    /// Creates a child execution for an iteration of a distributed Map state. Unlike nested
+    /// executions, it has its own id and Context Object.
+    fn child(parent: &Execution<'a>, start_at: &'a str, states: &'a HashMap<String, State>, input: Value) -> Self {
+        let id = Uuid::new_v4().to_string();
+        let context = json!({
+            "Execution": {
+                "Id": id,
+                "Input": input,
+                "StartTime": Utc::now().to_rfc3339(),
+            },
+        });
+        Execution {
+            states,
+            resources: parent.resources,
+            id,
+            current_state: Some(start_at),
+            input,
+            context,
+            status: ExecutionStatus::Running,
+            child_executions: parent.child_executions.clone(),
+        }
+    }
+
+    /// The unique id of the execution, also available as `$$.Execution.Id`.
+    pub fn id(&self) -> &str {
+        &self.id
+    }
+
+    /// The name of the state that runs on the next call to `next()`, or `None` if the execution
+    /// is finished.
+    pub fn current_state(&self) -> Option<&'a str> {
+        self.current_state
+    }
+
+    /// The input of the current state.
+    pub fn input(&self) -> &Value {
+        &self.input
+    }
+
+    pub fn status(&self) -> &ExecutionStatus {
+        &self.status
+    }
+
+    pub fn is_finished(&self) -> bool {
+        self.status != ExecutionStatus::Running
+    }
+
+    /// The child executions started so far by the distributed Map states of this execution,
+    /// including those nested in Parallel branches, Map iterations and other child executions.
+    ///
+    /// Child executions are listed in the order they finished. Those still running when their Map
+    /// state failed are cancelled, and aren't listed.
+    pub fn child_executions(&self) -> Vec<ChildExecution<'a>> {
+        self.child_executions.lock().unwrap_or_else(PoisonError::into_inner).clone()
+    }
+
+    /// Runs the state machine until a terminal state is reached.
+    ///
+    /// Returns the output of the last state, or the error that made the execution fail.
+    pub fn run(mut self) -> Result<Value, ExecutionError> {
+        self.by_ref().for_each(drop);
+        self.into_result()
+    }
+
+    /// Runs the current state and transitions to the next one, without blocking the thread.
+    ///
+    /// This is the async counterpart of `next()`. Returns `None` once the execution is finished.
+    #[cfg(feature = "tokio")]
+    pub async fn next_async(&mut self) -> Option<StateExecution<'a>> {
+        self.step(Runtime::Tokio).await
+    }
+
+    /// Runs the state machine until a terminal state is reached, without blocking the thread.
+    ///
+    /// This is the async counterpart of [Execution::run].
+    #[cfg(feature = "tokio")]
+    pub async fn run_async(mut self) -> Result<Value, ExecutionError> {
+        while self.next_async().await.is_some() {}
+        self.into_result()
+    }
+
+    fn into_result(self) -> Result<Value, ExecutionError> {
+        match self.status {
+            ExecutionStatus::Succeeded(output) => Ok(output),
+            ExecutionStatus::Failed(error) => Err(error),
+            ExecutionStatus::Running => unreachable!("The execution stops stepping only once it's finished"),
+        }
+    }
+
+    async fn step(&mut self, runtime: Runtime) -> Option<StateExecution<'a>> {
+        let name = self.current_state?;
+        let input = std::mem::take(&mut self.input);
+        self.context["State"] = json!({
+            "Name": name,
+            "EnteredTime": Utc::now().to_rfc3339(),
+            "RetryCount": 0,
+        });
+        let result = match self.states.get(name) {
+            Some(state) => match self.execute_with_retries(state, &input, runtime).await {
+                Err(error) => catch(state, error, &input),
+                result => result,
+            },
+            None => Err(ExecutionError::runtime(format!("State '{name}' is not defined"))),
+        };
+        let (output, next) = match result {
+            Ok((output, next)) => {
+                self.current_state = next;
+                match next {
+                    Some(_) => self.input = output.clone(),
+                    None => self.status = ExecutionStatus::Succeeded(output.clone()),
+                }
+                (Ok(output), next)
+            }
+            Err(error) => {
+                self.current_state = None;
+                self.status = ExecutionStatus::Failed(error.clone());
+                (Err(error), None)
+            }
+        };
+        Some(StateExecution { name, input, output, next })
+    }
+
+    /// Runs a state, retrying it according to the first of its retriers matching each error.
+    ///
+    /// Each retrier counts its own retries, and `$$.State.RetryCount` counts them all.
+    async fn execute_with_retries(&mut self, state: &'a State, input: &Value, runtime: Runtime) -> Result<(Value, Option<&'a str>), ExecutionError> {
+        let retriers = retriers(state);
+        let mut attempts = vec![0; retriers.len()];
+        let mut retry_count = 0;
+        loop {
+            let error = match self.execute_state(state, input.clone(), runtime).await {
+                Err(error) => error,
+                result => return result,
+            };
+            let Some(index) = retriers.iter().position(|retrier| retrier.matches(&error)) else {
+                return Err(error);
+            };
+            if attempts[index] >= retriers[index].max_attempts() {
+                return Err(error);
+            }
+            runtime.sleep(retriers[index].delay(attempts[index])).await;
+            attempts[index] += 1;
+            retry_count += 1;
+            self.context["State"]["RetryCount"] = json!(retry_count);
+        }
+    }
+
+    /// Executes a single state, returning its output and the name of the next state to run, or
+    /// `None` if the state is terminal.
+    ///
+    /// The input goes through the state's "InputPath", "Parameters", "ResultSelector", "ResultPath"
+    /// and "OutputPath", see https://states-language.net/spec.html#filters
+    async fn execute_state(&self, state: &'a State, input: Value, runtime: Runtime) -> Result<(Value, Option<&'a str>), ExecutionError> {
+        let context = &self.context;
+        match state {
+            State::Task { resource, input_path, parameters, result_selector, result_path, output_path, end_or_next, .. } => {
+                let effective_input = apply_input_path(input_path, &input, context)?;
+                let effective_input = render(parameters, effective_input, context)?;
+                let handler = self.resources.get(resource)
+                    .ok_or_else(|| ExecutionError::runtime(format!("No handler registered for resource '{resource}'")))?;
+                let result = match handler {
+                    Handler::Blocking(handler) => {
+                        let (handler, task_resource) = (handler.clone(), resource.clone());
+                        runtime.spawn_blocking(move || handler.invoke(&task_resource, &effective_input)).await
+                            .unwrap_or_else(|| Err(ExecutionError::runtime(format!("The handler of the resource '{resource}' panicked"))))?
+                    }
+                    Handler::Async(handler) => handler.invoke(resource, &effective_input).await?,
+                };
+                let result = render(result_selector, result, context)?;
+                let output = apply_result_path(result_path, input, result)?;
+                Ok((apply_output_path(output_path, &output, context)?, next_state(end_or_next)))
+            }
+            State::Pass { result, input_path, parameters, result_path, output_path, end_or_next, .. } => {
+                let effective_input = apply_input_path(input_path, &input, context)?;
+                let effective_input = render(parameters, effective_input, context)?;
+                let result = result.clone().unwrap_or(effective_input);
+                let output = apply_result_path(result_path, input, result)?;
+                Ok((apply_output_path(output_path, &output, context)?, next_state(end_or_next)))
+            }
+            State::Wait { duration, input_path, output_path, end_or_next, .. } => {
+                let effective_input = apply_input_path(input_path, &input, context)?;
+                runtime.sleep(wait_time(duration, &effective_input, context)?).await;
+                Ok((apply_output_path(output_path, &effective_input, context)?, next_state(end_or_next)))
+            }
+            State::Succeed { input_path, output_path, .. } => {
+                let effective_input = apply_input_path(input_path, &input, context)?;
+                Ok((apply_output_path(output_path, &effective_input, context)?, None))
+            }
+            State::Fail { error, cause, .. } => Err(fail_error(error, cause, &input, context)?),
+            State::Choice { choices, default, input_path, output_path, .. } => {
+                let effective_input = apply_input_path(input_path, &input, context)?;
+                let next = match choice::first_match(choices, &effective_input, context)? {
+                    Some(rule) => rule.next(),
+                    None => default.as_deref().ok_or_else(|| ExecutionError::new(
+                        ErrorName::StatesNoChoiceMatched,
+                        "No Choice Rule matched the input and the Choice state has no \"Default\"",
+                    ))?,
+                };
+                Ok((apply_output_path(output_path, &effective_input, context)?, Some(next)))
+            }
+            State::Parallel { branches, input_path, parameters, result_selector, result_path, output_path, end_or_next, .. } => {
+                let effective_input = apply_input_path(input_path, &input, context)?;
+                let effective_input = render(parameters, effective_input, context)?;
+                // The first failure drops the other branches, which cancels them.
+                let outputs = try_join_all(branches.iter().map(|branch| {
+                    self.run_nested(branch.start_at(), branch.states(), effective_input.clone(), context.clone(), runtime)
+                        .map_err(branch_error)
+                })).await?;
+                let result = render(result_selector, Value::Array(outputs), context)?;
+                let output = apply_result_path(result_path, input, result)?;
+                Ok((apply_output_path(output_path, &output, context)?, next_state(end_or_next)))
+            }
+            // "Parameters" is the deprecated name of "ItemSelector".
+            #[allow(deprecated)]
+            State::Map { item_processor, items_path, item_reader, item_selector, result_writer, parameters, item_batcher, max_concurrency, tolerated_failures, input_path, result_selector, result_path, output_path, end_or_next, .. } => {
+                let effective_input = apply_input_path(input_path, &input, context)?;
+                let items = match item_reader {
+                    Some(item_reader) => self.read_items(item_reader, &effective_input)?,
+                    None => map_items(items_path, &effective_input, context)?,
+                };
+                let tolerated_count = tolerated_failures.count(&effective_input, context)?;
+                let tolerated_percentage = tolerated_failures.percentage(&effective_input, context)?;
+                let item_selector = item_selector.as_ref().or(parameters.as_ref());
+                let map_context = |index: usize, value: &Value| {
+                    let mut item_context = context.clone();
+                    item_context["Map"] = json!({ "Item": { "Index": index, "Value": value } });
+                    item_context
+                };
+                // Each iteration gets its input and its Context Object.
+                let mut inputs = items.into_iter().enumerate()
+                    .map(|(index, item)| {
+                        let item_context = map_context(index, &item);
+                        match item_selector {
+                            Some(item_selector) => Ok((item_selector.render(&effective_input, &item_context)?, item_context)),
+                            None => Ok((item, item_context)),
+                        }
+                    })
+                    .collect::<Result<Vec<_>, ExecutionError>>()?;
+                if let Some(item_batcher) = item_batcher {
+                    let items = inputs.into_iter().map(|(item, _)| item).collect();
+                    inputs = item_batcher.batch(items, &effective_input, context)?.into_iter().enumerate()
+                        .map(|(index, batch)| {
+                            let batch_context = map_context(index, &batch);
+                            (batch, batch_context)
+                        })
+                        .collect();
+                }
+                let total = inputs.len();
+                // The results written by a "ResultWriter" include the input of each iteration.
+                let item_inputs: Vec<Value> = match result_writer {
+                    Some(_) => inputs.iter().map(|(item_input, _)| item_input.clone()).collect(),
+                    None => vec![],
+                };
+                // A "MaxConcurrency" of 0 means no limit.
+                let limit = match max_concurrency {
+                    Some(limit) if *limit > 0 => *limit as usize,
+                    _ => total.max(1),
+                };
+                let distributed = item_processor.processor_config().mode() == ProcessorMode::Distributed;
+                let iterations = inputs.into_iter().enumerate().map(|(index, (item_input, item_context))| async move {
+                    let output = if distributed {
+                        self.run_child(item_processor, item_input, runtime).await
+                    } else {
+                        self.run_nested(item_processor.start_at(), item_processor.states(), item_input, item_context, runtime).await
+                    };
+                    (index, output.map_err(branch_error))
+                });
+                let mut iterations = stream::iter(iterations).buffer_unordered(limit);
+                // The iterations that are still running when the Map state fails stay `None`.
+                let mut outputs: Vec<Option<Result<Value, ExecutionError>>> = vec![None; total];
+                let mut failures = 0;
+                let mut failure = None;
+                while let Some((index, output)) = iterations.next().await {
+                    if let Err(error) = &output {
+                        if tolerated_count.is_none() && tolerated_percentage.is_none() {
+                            failure = Some(error.clone());
+                        } else {
+                            failures += 1;
+                            let exceeds_count = tolerated_count.is_some_and(|count| failures > count);
+                            let exceeds_percentage = tolerated_percentage.is_some_and(|percentage| failures as f64 * 100.0 / total as f64 > percentage);
+                            if exceeds_count || exceeds_percentage {
+                                failure = Some(ExecutionError::new(
+                                    ErrorName::StatesExceedToleratedFailureThreshold,
+                                    format!("{failures} of the {total} iterations failed, which exceeds the tolerated failure threshold"),
+                                ));
+                            }
+                        }
+                    }
+                    outputs[index] = Some(output);
+                    if failure.is_some() {
+                        break;
+                    }
+                }
+                // Dropping the iterations still running cancels them.
+                drop(iterations);
+                let result = match result_writer {
+                    Some(result_writer) => {
+                        let written = self.write_results(result_writer, &effective_input, item_inputs, outputs);
+                        // The failure of the Map state takes precedence over the failure to write its results.
+                        if let Some(failure) = failure {
+                            return Err(failure);
+                        }
+                        written?
+                    }
+                    None => {
+                        if let Some(failure) = failure {
+                            return Err(failure);
+                        }
+                        let results = outputs.into_iter()
+                            .map(|output| match output {
+                                Some(Ok(output)) => output,
+                                Some(Err(error)) => error_output(error),
+                                None => Value::Null,
+                            })
+                            .collect();
+                        Value::Array(results)
+                    }
+                };
+                let result = render(result_selector, result, context)?;
+                let output = apply_result_path(result_path, input, result)?;
+                Ok((apply_output_path(output_path, &output, context)?, next_state(end_or_next)))
+            }
+        }
+    }
+}
+
+impl<'a> Execution<'a> {
+    /// Reads the items of a Map state with the reader registered for its "ItemReader" resource.
+    fn read_items(&self, item_reader: &ItemReaderConfiguration, input: &Value) -> Result<Vec<Value>, ExecutionError> {
+        let resource = item_reader.resource();
+        let reader = self.resources.item_reader(resource)
+            .ok_or_else(|| ExecutionError::new(ErrorName::StatesItemReaderFailed, format!("No item reader registered for resource '{resource}'")))?;
+        let parameters = match item_reader.parameters() {
+            Some(parameters) => parameters.render(input, &self.context)?,
+            None => json!({}),
+        };
+        let max_items = item_reader.reader_config().max_items(input, &self.context)?;
+        let mut items = reader.read(resource, &parameters, item_reader.reader_config())?;
+        if let Some(max_items) = max_items {
+            items.truncate(usize::try_from(max_items).unwrap_or(usize::MAX));
+        }
+        Ok(items)
+    }
+
+    /// Writes the results of the iterations of a Map state with the writer registered for its
+    /// "ResultWriter" resource, returning the output of the state.
+    fn write_results(&self, result_writer: &ResultWriterConfiguration, input: &Value, item_inputs: Vec<Value>, outputs: Vec<Option<Result<Value, ExecutionError>>>) -> Result<Value, ExecutionError> {
+        let resource = result_writer.resource();
+        let writer = self.resources.result_writer(resource)
+            .ok_or_else(|| ExecutionError::runtime(format!("No result writer registered for resource '{resource}'")))?;
+        let parameters = match result_writer.parameters() {
+            Some(parameters) => parameters.render(input, &self.context)?,
+            None => json!({}),
+        };
+        let mut results = MapRunResults {
+            map_run_id: Uuid::new_v4().to_string(),
+            ..MapRunResults::default()
+        };
+        for (item_input, output) in item_inputs.into_iter().zip(outputs) {
+            let item_input = item_input.to_string();
+            match output {
+                Some(Ok(output)) => results.succeeded.push(json!({ "Input": item_input, "Output": output.to_string(), "Status": "SUCCEEDED" })),
+                Some(Err(error)) => results.failed.push(json!({
+                    "Input": item_input,
+                    "Error": error.error.map(|error| error.to_string()),
+                    "Cause": error.cause,
+                    "Status": "FAILED",
+                })),
+                None => results.pending.push(json!({ "Input": item_input, "Status": "PENDING" })),
+            }
+        }
+        let details = writer.write(resource, &parameters, &results)?;
+        Ok(json!({ "MapRunArn": results.map_run_id, "ResultWriterDetails": details }))
+    }
+
+    /// Runs an iteration of a distributed Map state as a child execution, returning its output.
+    ///
+    /// The child execution is tracked once it finishes.
+    fn run_child(&self, item_processor: &'a MapStateIterator, input: Value, runtime: Runtime) -> BoxFuture<'a, Result<Value, ExecutionError>> {
+        let mut execution = Execution::child(self, item_processor.start_at(), item_processor.states(), input.clone());
+        let parent_id = self.id.clone();
+        let map_state = self.current_state.unwrap_or_default();
+        async move {
+            let mut history = vec![];
+            while let Some(step) = execution.step(runtime).await {
+                history.push(step);
+            }
+            let child = ChildExecution {
+                id: execution.id.clone(),
+                parent_id,
+                map_state,
+                execution_type: item_processor.processor_config().execution_type(),
+                input,
+                status: execution.status.clone(),
+                history,
+            };
+            execution.child_executions.lock().unwrap_or_else(PoisonError::into_inner).push(child);
+            execution.into_result()
+        }.boxed()
+    }
+
+    /// Runs a nested state machine to completion, returning its output.
+    fn run_nested(&self, start_at: &'a str, states: &'a HashMap<String, State>, input: Value, context: Value, runtime: Runtime) -> BoxFuture<'a, Result<Value, ExecutionError>> {
+        let mut execution = Execution::nested(self, start_at, states, input, context);
+        async move {
+            while execution.step(runtime).await.is_some() {}
+            execution.into_result()
+        }.boxed()
+    }
+}
+
+impl<'a> Iterator for Execution<'a> {
+    type Item = StateExecution<'a>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        block_on(self.step(Runtime::Blocking))
+    }
+}
+
+/// The retriers of the states that can be retried, in the order they're evaluated.
+fn retriers(state: &State) -> &[Retrier] {
+    match state {
+        State::Task { retry, .. } | State::Parallel { retry, .. } | State::Map { retry, .. } => retry.as_deref().unwrap_or_default(),
+        _ => &[],
+    }
+}
+
+/// Handles an error that retries didn't recover from with the first matching catcher of the state,
+/// which transitions to its "Next" state with the error output placed in the state input.
+fn catch<'a>(state: &'a State, error: ExecutionError, input: &Value) -> Result<(Value, Option<&'a str>), ExecutionError> {
+    let catchers = match state {
+        State::Task { catch, .. } | State::Parallel { catch, .. } | State::Map { catch, .. } => catch.as_deref().unwrap_or_default(),
+        _ => &[],
+    };
+    let Some(catcher) = catchers.iter().find(|catcher| catcher.matches(&error)) else {
+        return Err(error);
+    };
+    let output = apply_result_path(catcher.result_path(), input.clone(), error_output(error))?;
+    Ok((output, Some(catcher.next())))
+}
+
+/// The error output of a state, as passed to a catcher or reported for a tolerated Map failure.
+fn error_output(error: ExecutionError) -> Value {
+    json!({ "Error": error.error.map(|error| error.to_string()), "Cause": error.cause })
+}
+
+/// A failed branch or iteration fails the whole Parallel or Map state with its error, or with
+/// `States.BranchFailed` if it failed without an error name.
+fn branch_error(error: ExecutionError) -> ExecutionError {
+    match error.error {
+        Some(_) => error,
+        None => ExecutionError {
+            error: Some(ErrorName::StatesBranchFailed),
+            cause: error.cause,
+        },
+    }
+}
+
+fn next_state(end_or_next: &EndOrNext) -> Option<&str> {
+    match end_or_next {
+        EndOrNext::End(_) => None,
+        EndOrNext::Next(next) => Some(next.as_str()),
+    }
+}
+
+/// Selects the effective input of a state. A `null` "InputPath" discards the input.
+fn apply_input_path(path: &Option<JsonPath>, input: &Value, context: &Value) -> Result<Value, ExecutionError> {
+    match path {
+        Some(path) => path.evaluate(input, context)
+            .ok_or_else(|| ExecutionError::runtime(format!("The JSONPath '{path}' specified for the field 'InputPath' could not be found in the input"))),
+        None => Ok(json!({})),
+    }
+}
+
+/// Selects the output of a state. A `null` "OutputPath" discards the output.
+fn apply_output_path(path: &Option<JsonPath>, output: &Value, context: &Value) -> Result<Value, ExecutionError> {
+    match path {
+        Some(path) => path.evaluate(output, context)
+            .ok_or_else(|| ExecutionError::runtime(format!("The JSONPath '{path}' specified for the field 'OutputPath' could not be found in the output"))),
+        None => Ok(json!({})),
+    }
+}
+
+/// Places the result of a state into its raw input. A `null` "ResultPath" discards the result.
+fn apply_result_path(path: &Option<ReferencePath>, input: Value, result: Value) -> Result<Value, ExecutionError> {
+    let Some(path) = path else {
+        return Ok(input);
+    };
+    let mut output = input;
+    path.set(&mut output, result)
+        .map_err(|e| ExecutionError::new(ErrorName::StatesResultPathMatchFailure, e.to_string()))?;
+    Ok(output)
+}
+
+/// Selects the array of items a Map state iterates over from its effective input.
+fn map_items(path: &Option<ReferencePath>, input: &Value, context: &Value) -> Result<Vec<Value>, ExecutionError> {
+    let items = match path {
+        Some(path) => path.get(input, context)
+            .ok_or_else(|| ExecutionError::runtime(format!("The JSONPath '{path}' specified for the field 'ItemsPath' could not be found in the input")))?,
+        None => input,
+    };
+    match items {
+        Value::Array(items) => Ok(items.clone()),
+        items => Err(ExecutionError::runtime(format!("The field 'ItemsPath' must select an array, but it selects {items}"))),
+    }
+}
+
+fn render(template: &Option<PayloadTemplate>, input: Value, context: &Value) -> Result<Value, ExecutionError> {
+    match template {
+        Some(template) => template.render(&input, context),
+        None => Ok(input),
+    }
+}
+
+fn wait_time(duration: &WaitDuration, input: &Value, context: &Value) -> Result<Duration, ExecutionError> {
+    match duration {
+        WaitDuration::Seconds(seconds) => seconds_to_duration(seconds),
+        WaitDuration::Timestamp(timestamp) => timestamp_to_duration(timestamp),
+        WaitDuration::SecondsPath(path) => match path.get(input, context) {
+            Some(Value::Number(seconds)) if seconds.is_u64() => seconds_to_duration(seconds),
+            value => Err(ExecutionError::runtime(format!("The JSONPath '{path}' specified for the field 'SecondsPath' must select a non-negative integer, but it selects {}", describe(value)))),
+        },
+        WaitDuration::TimestampPath(path) => match path.get(input, context) {
+            Some(Value::String(timestamp)) => timestamp_to_duration(timestamp),
+            value => Err(ExecutionError::runtime(format!("The JSONPath '{path}' specified for the field 'TimestampPath' must select a timestamp, but it selects {}", describe(value)))),
+        },
+    }
+}
+
+fn describe(value: Option<&Value>) -> String {
+    value.map_or_else(|| "nothing".to_string(), Value::to_string)
+}
+
+fn seconds_to_duration(seconds: &Number) -> Result<Duration, ExecutionError> {
+    seconds.as_f64()
+        .and_then(|seconds| Duration::try_from_secs_f64(seconds).ok())
+        .ok_or_else(|| ExecutionError::runtime(format!("Invalid number of seconds: {seconds}")))
+}
+
+fn timestamp_to_duration(timestamp: &str) -> Result<Duration, ExecutionError> {
+    let timestamp = DateTime::parse_from_rfc3339(timestamp)
+        .map_err(|e| ExecutionError::runtime(format!("Invalid timestamp '{timestamp}': {e}")))?;
+    // A timestamp in the past means there's nothing to wait for.
+    Ok((timestamp.with_timezone(&Utc) - Utc::now()).to_std().unwrap_or(Duration::ZERO))
+}
+
+fn fail_error(error: &Option<FailStateErrorField>, cause: &Option<FailStateCauseField>, input: &Value, context: &Value) -> Result<ExecutionError, ExecutionError> {
+    let error = match error {
+        None => None,
+        Some(FailStateErrorField::Error(error)) => Some(ErrorName::from(error.as_str())),
+        Some(FailStateErrorField::ErrorPath(path)) => Some(ErrorName::from(resolve_string("ErrorPath", path, input, context)?)),
+    };
+    let cause = match cause {
+        None => None,
+        Some(FailStateCauseField::Cause(cause)) => Some(cause.clone()),
+        Some(FailStateCauseField::CausePath(path)) => Some(resolve_string("CausePath", path, input, context)?),
+    };
+    Ok(ExecutionError { error, cause })
+}
+
+/// Resolves the "ErrorPath" or "CausePath" of a Fail state, which is either a Reference Path or an
+/// Intrinsic Function, to a string.
+fn resolve_string(field: &str, value: &str, input: &Value, context: &Value) -> Result<String, ExecutionError> {
+    let resolved = if value.starts_with("States.") {
+        let function: IntrinsicFunction = value.parse()
+            .map_err(|e| ExecutionError::runtime(format!("Invalid value for the field '{field}': {e}")))?;
+        function.evaluate(input, context)?
+    } else {
+        let path: ReferencePath = value.parse()
+            .map_err(|e| ExecutionError::runtime(format!("Invalid value for the field '{field}': {e}")))?;
+        path.get(input, context).cloned()
+            .ok_or_else(|| ExecutionError::runtime(format!("The JSONPath '{path}' specified for the field '{field}' could not be found in the input")))?
+    };
+    match resolved {
+        Value::String(resolved) => Ok(resolved),
+        resolved => Err(ExecutionError::runtime(format!("The field '{field}' must resolve to a string, but it resolves to {resolved}"))),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Condvar;
+    use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+    use super::*;
+    use rstest::*;
+    use serde_json::json;
+    use anyhow::Result;
+    use crate::asl::item_reader::LocalDirectoryReader;
+    use crate::asl::result_writer::LocalDirectoryWriter;
+    use crate::asl::temp_dir::TempDir;
+    use crate::asl::resources::{ResourceHandler, ResourcePattern, Resources};
+
+    fn resources() -> Resources {
+        let mut resources = Resources::new();
+        resources
+            .register("return", |_: &str, input: &Value| Ok(input.to_owned()))
+            .register("increment", |_: &str, input: &Value| {
+                input.as_i64()
+                    .map(|n| json!(n + 1))
+                    .ok_or_else(|| ExecutionError::new(ErrorName::Custom("NotANumber".to_string()), format!("Can't increment {input}")))
+            });
+        resources
+    }
+
+    /// A handler that counts how many times it was invoked.
+    #[derive(Default)]
+    struct Counter {
+        count: AtomicU64,
+    }
+
+    impl ResourceHandler for Counter {
+        fn invoke(&self, _resource: &str, _input: &Value) -> Result<Value, ExecutionError> {
+            Ok(json!(self.count.fetch_add(1, Ordering::SeqCst) + 1))
+        }
+    }
+
+    /// A handler whose invocations wait for each other in groups of `size`, so that they only
+    /// return if that many of them run at once. It records how many of them overlapped at most.
+    ///
+    /// The timeout only fails the invocations instead of hanging the test when they don't overlap.
+    struct Rendezvous {
+        size: usize,
+        /// The invocations waiting for the current group to be complete, and the number of groups
+        /// completed so far.
+        arrived: Mutex<(usize, usize)>,
+        completed: Condvar,
+        running: AtomicUsize,
+        peak: AtomicUsize,
+    }
+
+    impl Rendezvous {
+        fn new(size: usize) -> Self {
+            Rendezvous {
+                size,
+                arrived: Mutex::default(),
+                completed: Condvar::new(),
+                running: AtomicUsize::default(),
+                peak: AtomicUsize::default(),
+            }
+        }
+
+        fn meet(&self) -> bool {
+            let mut arrived = self.arrived.lock().unwrap_or_else(PoisonError::into_inner);
+            let group = arrived.1;
+            arrived.0 += 1;
+            if arrived.0 == self.size {
+                *arrived = (0, group + 1);
+                self.completed.notify_all();
+                return true;
+            }
+            let timeout = Duration::from_secs(30);
+            let (_arrived, result) = self.completed.wait_timeout_while(arrived, timeout, |arrived| arrived.1 == group)
+                .unwrap_or_else(PoisonError::into_inner);
+            !result.timed_out()
+        }
+    }
+
+    impl ResourceHandler for Rendezvous {
+        fn invoke(&self, _resource: &str, input: &Value) -> Result<Value, ExecutionError> {
+            let running = self.running.fetch_add(1, Ordering::SeqCst) + 1;
+            self.peak.fetch_max(running, Ordering::SeqCst);
+            let met = self.meet();
+            self.running.fetch_sub(1, Ordering::SeqCst);
+            match met {
+                true => Ok(input.clone()),
+                false => Err(ExecutionError::runtime(format!("Fewer than {} invocations ran at once", self.size))),
+            }
+        }
+    }
+
+    fn rendezvous_resources(size: usize) -> (Arc<Rendezvous>, Resources) {
+        let rendezvous = Arc::new(Rendezvous::new(size));
+        let mut resources = Resources::new();
+        let handler = rendezvous.clone();
+        resources.register("meet", move |resource: &str, input: &Value| handler.invoke(resource, input));
+        (rendezvous, resources)
+    }
+
+    #[rstest]
+    fn run_hello_world() -> Result<()> {
+        let definition = include_str!("test-data/hello-world.json");
+        let state_machine = StateMachine::parse_with_resources(definition, resources())?;
+
+        let output = state_machine.start(&json!({ "arg": "Hello world" })).run()?;
+
+        assert_eq!(output, json!({ "arg": "Hello world" }));
+        Ok(())
+    }
+
+    #[rstest]
+    fn run_follows_next_transitions() -> Result<()> {
+        let definition = r#"{
+            "StartAt": "Start",
+            "States": {
+                "Start": { "Type": "Pass", "Result": 1, "Next": "Increment" },
+                "Increment": { "Type": "Task", "Resource": "increment", "Next": "Wait" },
+                "Wait": { "Type": "Wait", "Seconds": 0, "Next": "Done" },
+                "Done": { "Type": "Succeed" }
+            }
+        }"#;
+        let state_machine = StateMachine::parse_with_resources(definition, resources())?;
+
+        let output = state_machine.start(&Value::Null).run()?;
+
+        assert_eq!(output, json!(2));
+        Ok(())
+    }
+
+    #[rstest]
+    fn run_with_stateful_handler() -> Result<()> {
+        let definition = r#"{
+            "StartAt": "First",
+            "States": {
+                "First": { "Type": "Task", "Resource": "count", "Next": "Second" },
+                "Second": { "Type": "Task", "Resource": "count", "End": true }
+            }
+        }"#;
+        let mut resources = Resources::new();
+        resources.register("count", Counter::default());
+        let state_machine = StateMachine::parse_with_resources(definition, resources)?;
+
+        assert_eq!(state_machine.start(&Value::Null).run()?, json!(2));
+        assert_eq!(state_machine.start(&Value::Null).run()?, json!(4));
+        Ok(())
+    }
+
+    #[rstest]
+    fn run_async_handler_in_blocking_mode() -> Result<()> {
+        let definition = include_str!("test-data/hello-world.json");
+        let mut resources = Resources::new();
+        resources.register_async("return", |_: &str, input: &Value| {
+            let input = input.to_owned();
+            async move { Ok(input) }
+        });
+        let state_machine = StateMachine::parse_with_resources(definition, resources)?;
+
+        assert_eq!(state_machine.start(&json!("Hello world")).run()?, json!("Hello world"));
+        Ok(())
+    }
+
+    #[cfg(feature = "tokio")]
+    #[tokio::test]
+    async fn run_async_in_spawned_task() -> Result<()> {
+        let definition = include_str!("test-data/hello-world.json");
+        let mut resources = Resources::new();
+        resources.register_async("return", |_: &str, input: &Value| {
+            let input = input.to_owned();
+            async move {
+                tokio::task::yield_now().await;
+                Ok(input)
+            }
+        });
+        let state_machine = std::sync::Arc::new(StateMachine::parse_with_resources(definition, resources)?);
+
+        let output = tokio::spawn(async move { state_machine.start(&json!("Hello world")).run_async().await }).await?;
+
+        assert_eq!(output?, json!("Hello world"));
+        Ok(())
+    }
+
+    #[cfg(feature = "tokio")]
+    #[tokio::test(start_paused = true)]
+    async fn wait_states_do_not_block_in_async_mode() -> Result<()> {
+        let definition = r#"{
+            "StartAt": "Wait",
+            "States": {
+                "Wait": { "Type": "Wait", "Seconds": 10, "End": true }
+            }
+        }"#;
+        let state_machine = StateMachine::parse(definition)?;
+        let started = tokio::time::Instant::now();
+
+        let (first, second) = futures::join!(
+            state_machine.start(&json!(1)).run_async(),
+            state_machine.start(&json!(2)).run_async(),
+        );
+
+        assert_eq!((first?, second?), (json!(1), json!(2)));
+        assert_eq!(started.elapsed().as_secs(), 10);
+        Ok(())
+    }
+
+    /// Registers a handler for any Lambda function, raising the error named in the "error" field
+    /// of its input.
+    fn failing_lambda() -> Resources {
+        let mut resources = Resources::new();
+        resources.register_pattern(ResourcePattern::Prefix("arn:aws:lambda:".to_string()), |_: &str, input: &Value| {
+            Err(ExecutionError::new(ErrorName::from(input["error"].as_str().unwrap_or_default()), "Function failed"))
+        });
+        resources
+    }
+
+    #[rstest]
+    #[case::custom_error("CustomError", Ok(json!("This is a fallback from a custom lambda function exception")))]
+    #[case::reserved_error("States.TaskFailed", Ok(json!("This is a fallback from a reserved error code")))]
+    #[case::any_error("OtherError", Ok(json!("This is a fallback from a reserved error code")))]
+    #[case::runtime_error("States.Runtime", Err(ExecutionError::runtime("Function failed")))]
+    fn run_catch(#[case] error: &str, #[case] expected: Result<Value, ExecutionError>) -> Result<()> {
+        let definition = include_str!("test-data/asl-validator/valid-catch-failure.json");
+        let state_machine = StateMachine::parse_with_resources(definition, failing_lambda())?;
+
+        let output = state_machine.start(&json!({ "error": error })).run();
+
+        assert_eq!(output, expected);
+        Ok(())
+    }
+
+    #[rstest]
+    #[case::default_result_path("", json!({ "Error": "CustomError", "Cause": "Function failed" }))]
+    #[case::result_path(r#""ResultPath": "$.caught","#, json!({ "error": "CustomError", "caught": { "Error": "CustomError", "Cause": "Function failed" } }))]
+    #[case::null_result_path(r#""ResultPath": null,"#, json!({ "error": "CustomError" }))]
+    fn run_catch_result_path(#[case] result_path: &str, #[case] expected: Value) -> Result<()> {
+        let definition = format!(r#"{{
+            "StartAt": "Task",
+            "States": {{
+                "Task": {{
+                    "Type": "Task",
+                    "Resource": "arn:aws:lambda:us-east-1:123456789012:function:fail",
+                    "OutputPath": "$.ignored",
+                    "Catch": [{{ "ErrorEquals": ["CustomError"], {result_path} "Next": "Fallback" }}],
+                    "End": true
+                }},
+                "Fallback": {{ "Type": "Pass", "End": true }}
+            }}
+        }}"#);
+        let state_machine = StateMachine::parse_with_resources(&definition, failing_lambda())?;
+        let mut execution = state_machine.start(&json!({ "error": "CustomError" }));
+
+        let step = execution.next();
+        let output = execution.run()?;
+
+        assert_eq!(step.map(|step| step.next), Some(Some("Fallback")));
+        assert_eq!(output, expected);
+        Ok(())
+    }
+
+    #[rstest]
+    fn run_catch_parallel_failure() -> Result<()> {
+        let definition = r#"{
+            "StartAt": "Parallel",
+            "States": {
+                "Parallel": {
+                    "Type": "Parallel",
+                    "Branches": [{ "StartAt": "Fail", "States": { "Fail": { "Type": "Fail", "Error": "BranchError", "Cause": "Oops" } } }],
+                    "Catch": [{ "ErrorEquals": ["States.ALL"], "ResultPath": "$.error", "Next": "Fallback" }],
+                    "End": true
+                },
+                "Fallback": { "Type": "Pass", "End": true }
+            }
+        }"#;
+        let state_machine = StateMachine::parse(definition)?;
+
+        let output = state_machine.start(&json!({ "input": 1 })).run()?;
+
+        assert_eq!(output, json!({ "input": 1, "error": { "Error": "BranchError", "Cause": "Oops" } }));
+        Ok(())
+    }
+
+    #[rstest]
+    #[case::custom_error("CustomError", 3)]
+    #[case::reserved_error("States.TaskFailed", 90)]
+    #[case::any_error("OtherError", 155)]
+    fn run_retry_failure(#[case] error: &str, #[case] expected_seconds: u64) -> Result<()> {
+        let definition = include_str!("test-data/asl-validator/valid-retry-failure.json");
+        let state_machine = StateMachine::parse_with_resources(definition, failing_lambda())?;
+
+        let (output, elapsed) = blocking::paused(|| state_machine.start(&json!({ "error": error })).run());
+
+        assert_eq!(output, Err(ExecutionError::new(ErrorName::from(error), "Function failed")));
+        assert_eq!(elapsed.as_secs(), expected_seconds);
+        Ok(())
+    }
+
+    #[rstest]
+    fn catch_after_retries() -> Result<()> {
+        let definition = r#"{
+            "StartAt": "Task",
+            "States": {
+                "Task": {
+                    "Type": "Task",
+                    "Resource": "flaky",
+                    "Retry": [{ "ErrorEquals": ["Flaky"], "MaxAttempts": 2 }],
+                    "Catch": [{ "ErrorEquals": ["Flaky"], "Next": "Fallback" }],
+                    "End": true
+                },
+                "Fallback": { "Type": "Pass", "End": true }
+            }
+        }"#;
+        let mut resources = Resources::new();
+        resources.register("flaky", Flaky::new(vec!["Flaky"; 4]));
+        let state_machine = StateMachine::parse_with_resources(definition, resources)?;
+
+        let (output, elapsed) = blocking::paused(|| state_machine.start(&json!({})).run());
+
+        assert_eq!(output?, json!({ "Error": "Flaky", "Cause": "Attempt 2 failed" }));
+        assert_eq!(elapsed.as_secs(), 3);
+        Ok(())
+    }
+
+    /// A handler raising the given errors on its first invocations, then returning the number of
+    /// previous invocations.
+    struct Flaky {
+        errors: Vec<&'static str>,
+        calls: AtomicU64,
+    }
+
+    impl Flaky {
+        fn new(errors: Vec<&'static str>) -> Self {
+            Flaky { errors, calls: AtomicU64::new(0) }
+        }
+    }
+
+    impl ResourceHandler for Flaky {
+        fn invoke(&self, _resource: &str, _input: &Value) -> Result<Value, ExecutionError> {
+            let call = self.calls.fetch_add(1, Ordering::SeqCst);
+            match self.errors.get(call as usize) {
+                Some(error) => Err(ExecutionError::new(ErrorName::from(*error), format!("Attempt {call} failed"))),
+                None => Ok(json!(call)),
+            }
+        }
+    }
+
+    #[rstest]
+    #[case::retries_until_success(vec!["Flaky", "Flaky"], r#"[{ "ErrorEquals": ["Flaky"] }]"#, Ok(json!({ "Result": 2, "RetryCount": 2 })), 3)]
+    #[case::exhausts_attempts(vec!["Flaky"; 4], r#"[{ "ErrorEquals": ["Flaky"], "MaxAttempts": 2 }]"#, Err("Flaky: Attempt 2 failed"), 3)]
+    #[case::max_attempts_zero(vec!["Flaky"], r#"[{ "ErrorEquals": ["Flaky"], "MaxAttempts": 0 }]"#, Err("Flaky: Attempt 0 failed"), 0)]
+    #[case::first_match_wins(
+        vec!["Flaky"],
+        r#"[{ "ErrorEquals": ["Flaky"], "MaxAttempts": 0 }, { "ErrorEquals": ["States.ALL"] }]"#,
+        Err("Flaky: Attempt 0 failed"),
+        0,
+    )]
+    #[case::unmatched(vec!["Other"], r#"[{ "ErrorEquals": ["Flaky"] }]"#, Err("Other: Attempt 0 failed"), 0)]
+    #[case::max_delay(
+        vec!["Flaky"; 3],
+        r#"[{ "ErrorEquals": ["Flaky"], "BackoffRate": 10, "MaxDelaySeconds": 5 }]"#,
+        Ok(json!({ "Result": 3, "RetryCount": 3 })),
+        11,
+    )]
+    #[case::counters_per_retrier(
+        vec!["A", "B", "A", "B"],
+        r#"[{ "ErrorEquals": ["A"], "MaxAttempts": 1 }, { "ErrorEquals": ["B"], "MaxAttempts": 1 }]"#,
+        Err("A: Attempt 2 failed"),
+        2,
+    )]
+    fn retry_task(#[case] errors: Vec<&'static str>, #[case] retry: &str, #[case] expected: Result<Value, &str>, #[case] expected_seconds: u64) -> Result<()> {
+        let definition = format!(r#"{{
+            "StartAt": "Task",
+            "States": {{
+                "Task": {{
+                    "Type": "Task",
+                    "Resource": "flaky",
+                    "Retry": {retry},
+                    "ResultSelector": {{ "Result.$": "$", "RetryCount.$": "$$.State.RetryCount" }},
+                    "End": true
+                }}
+            }}
+        }}"#);
+        let mut resources = Resources::new();
+        resources.register("flaky", Flaky::new(errors));
+        let state_machine = StateMachine::parse_with_resources(&definition, resources)?;
+
+        let (output, elapsed) = blocking::paused(|| state_machine.start(&json!({})).run());
+
+        assert_eq!(output.map_err(|error| error.to_string()), expected.map_err(str::to_string));
+        assert_eq!(elapsed.as_secs(), expected_seconds);
+        Ok(())
+    }
+
+    #[rstest]
+    #[case::parallel(r#""Type": "Parallel", "Branches": [{ "StartAt": "Task", "States": { "Task": { "Type": "Task", "Resource": "flaky", "End": true } } }]"#, json!([1]))]
+    #[case::map(r#""Type": "Map", "ItemProcessor": { "StartAt": "Task", "States": { "Task": { "Type": "Task", "Resource": "flaky", "End": true } } }"#, json!([1]))]
+    fn retry_parallel_and_map(#[case] fields: &str, #[case] expected: Value) -> Result<()> {
+        let definition = format!(r#"{{
+            "StartAt": "State",
+            "States": {{
+                "State": {{ {fields}, "Retry": [{{ "ErrorEquals": ["Flaky"], "IntervalSeconds": 2 }}], "End": true }}
+            }}
+        }}"#);
+        let mut resources = Resources::new();
+        resources.register("flaky", Flaky::new(vec!["Flaky"]));
+        let state_machine = StateMachine::parse_with_resources(&definition, resources)?;
+
+        let (output, elapsed) = blocking::paused(|| state_machine.start(&json!([0])).run());
+
+        assert_eq!(output?, expected);
+        assert_eq!(elapsed.as_secs(), 2);
+        Ok(())
+    }
+
+    #[cfg(feature = "tokio")]
+    #[tokio::test(start_paused = true)]
+    async fn retry_in_async_mode() -> Result<()> {
+        let definition = r#"{
+            "StartAt": "Task",
+            "States": {
+                "Task": { "Type": "Task", "Resource": "flaky", "Retry": [{ "ErrorEquals": ["Flaky"] }], "End": true }
+            }
+        }"#;
+        let mut resources = Resources::new();
+        resources.register("flaky", Flaky::new(vec!["Flaky", "Flaky"]));
+        let state_machine = StateMachine::parse_with_resources(definition, resources)?;
+        let started = tokio::time::Instant::now();
+
+        let output = state_machine.start(&json!({})).run_async().await?;
+
+        assert_eq!(output, json!(2));
+        assert_eq!(started.elapsed().as_secs(), 3);
+        Ok(())
+    }
+
+    #[rstest]
+    #[case::all_fields(
+        r#"{
+            "Type": "Task",
+            "Resource": "return",
+            "InputPath": "$.payload",
+            "Parameters": { "value.$": "$.n", "state.$": "$$.State.Name" },
+            "ResultSelector": { "selected.$": "$.value", "state.$": "$.state" },
+            "ResultPath": "$.result",
+            "OutputPath": "$.result",
+            "End": true
+        }"#,
+        json!({ "payload": { "n": 1 } }),
+        json!({ "selected": 1, "state": "State" }),
+    )]
+    #[case::default_paths(
+        r#"{ "Type": "Task", "Resource": "return", "End": true }"#,
+        json!({ "a": 1 }),
+        json!({ "a": 1 }),
+    )]
+    #[case::result_path_into_input(
+        r#"{ "Type": "Task", "Resource": "return", "Parameters": { "b": 2 }, "ResultPath": "$.nested.result", "End": true }"#,
+        json!({ "a": 1 }),
+        json!({ "a": 1, "nested": { "result": { "b": 2 } } }),
+    )]
+    #[case::null_output_path(
+        r#"{ "Type": "Task", "Resource": "return", "OutputPath": null, "End": true }"#,
+        json!({ "a": 1 }),
+        json!({}),
+    )]
+    #[case::pass_parameters(
+        r#"{ "Type": "Pass", "Parameters": { "b.$": "$.a" }, "ResultPath": "$.passed", "End": true }"#,
+        json!({ "a": 1 }),
+        json!({ "a": 1, "passed": { "b": 1 } }),
+    )]
+    #[case::pass_result(
+        r#"{ "Type": "Pass", "InputPath": "$.a", "Result": "result", "ResultPath": "$.a", "End": true }"#,
+        json!({ "a": 1 }),
+        json!({ "a": "result" }),
+    )]
+    #[case::wait_seconds_path(
+        r#"{ "Type": "Wait", "InputPath": "$.wait", "SecondsPath": "$.seconds", "OutputPath": "$.seconds", "End": true }"#,
+        json!({ "wait": { "seconds": 0 } }),
+        json!(0),
+    )]
+    #[case::succeed_paths(
+        r#"{ "Type": "Succeed", "InputPath": "$.a", "OutputPath": "$.b" }"#,
+        json!({ "a": { "b": 1 } }),
+        json!(1),
+    )]
+    fn run_input_output_processing(#[case] state: &str, #[case] input: Value, #[case] expected: Value) -> Result<()> {
+        let definition = format!(r#"{{ "StartAt": "State", "States": {{ "State": {state} }} }}"#);
+        let state_machine = StateMachine::parse_with_resources(&definition, resources())?;
+
+        let output = state_machine.start(&input).run()?;
+
+        assert_eq!(output, expected);
+        Ok(())
+    }
+
+    #[rstest]
+    #[case::null_input("valid-null-input.json", json!({ "a": 1 }), json!({ "a": 1, "foo": {} }))]
+    #[case::null_parameter("valid-null-parameter.json", json!({ "a": 1 }), json!({ "foo": null }))]
+    #[case::null_result("valid-null-result.json", json!({ "foo": 1 }), json!({ "foo": 1 }))]
+    #[case::null_result_selector("valid-null-resultSelector.json", json!({ "a": 1 }), json!({ "absurd": null, "output": { "foo": "abc" } }))]
+    fn run_null_fields(#[case] file: &str, #[case] input: Value, #[case] expected: Value) -> Result<()> {
+        let definition = std::fs::read_to_string(format!("src/asl/test-data/asl-validator/{file}"))?;
+        let mut resources = Resources::new();
+        resources.register("arn:aws:lambda:us-east-1:123456789012:function:foo", |_: &str, input: &Value| Ok(input.to_owned()));
+        let state_machine = StateMachine::parse_with_resources(&definition, resources)?;
+
+        let output = state_machine.start(&input).run()?;
+
+        assert_eq!(output, expected);
+        Ok(())
+    }
+
+    #[rstest]
+    #[case::first_rule(json!({ "n": 1 }), json!("first"))]
+    #[case::second_rule(json!({ "n": 5 }), json!("second"))]
+    fn run_choice(#[case] input: Value, #[case] expected: Value) -> Result<()> {
+        let definition = r#"{
+            "StartAt": "Choice",
+            "States": {
+                "Choice": {
+                    "Type": "Choice",
+                    "InputPath": "$.n",
+                    "Choices": [
+                        { "Variable": "$", "NumericLessThan": 3, "Next": "First" },
+                        { "Not": { "Variable": "$", "NumericLessThan": 3 }, "Next": "Second" }
+                    ]
+                },
+                "First": { "Type": "Pass", "Result": "first", "End": true },
+                "Second": { "Type": "Pass", "Result": "second", "End": true }
+            }
+        }"#;
+        let state_machine = StateMachine::parse(definition)?;
+
+        let output = state_machine.start(&input).run()?;
+
+        assert_eq!(output, expected);
+        Ok(())
+    }
+
+    #[rstest]
+    fn run_choice_without_match() -> Result<()> {
+        let definition = r#"{
+            "StartAt": "Choice",
+            "States": {
+                "Choice": { "Type": "Choice", "Choices": [{ "Variable": "$", "NumericEquals": 1, "Next": "Done" }] },
+                "Done": { "Type": "Succeed" }
+            }
+        }"#;
+        let state_machine = StateMachine::parse(definition)?;
+
+        let error = state_machine.start(&json!(2)).run().unwrap_err();
+
+        let expected = ExecutionError::new(ErrorName::StatesNoChoiceMatched, "No Choice Rule matched the input and the Choice state has no \"Default\"");
+        assert_eq!(error, expected);
+        Ok(())
+    }
+
+    #[rstest]
+    #[case::matching_rule(json!({ "foo": 1 }), "FirstMatchState")]
+    #[case::default(json!({ "foo": 4 }), "DefaultState")]
+    fn run_choice_default(#[case] input: Value, #[case] expected: &str) -> Result<()> {
+        let definition = include_str!("test-data/asl-validator/valid-choice-state.json");
+        let mut resources = Resources::new();
+        resources.register_pattern(ResourcePattern::Prefix("arn:".to_string()), |_: &str, input: &Value| Ok(input.to_owned()));
+        let state_machine = StateMachine::parse_with_resources(definition, resources)?;
+        let mut execution = state_machine.start(&input);
+
+        let steps: Vec<_> = execution.by_ref().map(|step| step.name).collect();
+
+        assert_eq!(steps[2], expected);
+        Ok(())
+    }
+
+    #[rstest]
+    fn run_parallel() -> Result<()> {
+        let definition = r#"{
+            "StartAt": "Parallel",
+            "States": {
+                "Parallel": {
+                    "Type": "Parallel",
+                    "InputPath": "$.n",
+                    "ResultPath": "$.results",
+                    "Branches": [
+                        {
+                            "StartAt": "Increment",
+                            "States": {
+                                "Increment": { "Type": "Task", "Resource": "increment", "Next": "Wait" },
+                                "Wait": { "Type": "Wait", "Seconds": 0, "End": true }
+                            }
+                        },
+                        {
+                            "StartAt": "Pass",
+                            "States": { "Pass": { "Type": "Pass", "End": true } }
+                        },
+                        {
+                            "StartAt": "Nested",
+                            "States": {
+                                "Nested": {
+                                    "Type": "Parallel",
+                                    "Branches": [{ "StartAt": "NestedPass", "States": { "NestedPass": { "Type": "Pass", "Result": "nested", "End": true } } }],
+                                    "End": true
+                                }
+                            }
+                        }
+                    ],
+                    "End": true
+                }
+            }
+        }"#;
+        let state_machine = StateMachine::parse_with_resources(definition, resources())?;
+
+        let output = state_machine.start(&json!({ "n": 1 })).run()?;
+
+        assert_eq!(output, json!({ "n": 1, "results": [2, 1, ["nested"]] }));
+        Ok(())
+    }
+
+    #[rstest]
+    fn run_parallel_branches_concurrently() -> Result<()> {
+        let branch = |name: &str| json!({ "StartAt": name, "States": { name: { "Type": "Task", "Resource": "meet", "End": true } } });
+        let definition = json!({
+            "StartAt": "Parallel",
+            "States": { "Parallel": { "Type": "Parallel", "Branches": [branch("First"), branch("Second"), branch("Third")], "End": true } }
+        });
+        let (rendezvous, resources) = rendezvous_resources(3);
+        let state_machine = StateMachine::parse_with_resources(&definition.to_string(), resources)?;
+
+        let output = state_machine.start(&json!(1)).run()?;
+
+        assert_eq!(output, json!([1, 1, 1]));
+        assert_eq!(rendezvous.peak.load(Ordering::SeqCst), 3);
+        Ok(())
+    }
+
+    #[rstest]
+    fn parallel_failure_cancels_waiting_branches() -> Result<()> {
+        let definition = r#"{
+            "StartAt": "Parallel",
+            "States": {
+                "Parallel": {
+                    "Type": "Parallel",
+                    "Branches": [
+                        {
+                            "StartAt": "WaitLong",
+                            "States": {
+                                "WaitLong": { "Type": "Wait", "Seconds": 86400, "Next": "Count" },
+                                "Count": { "Type": "Task", "Resource": "count", "End": true }
+                            }
+                        },
+                        {
+                            "StartAt": "Task",
+                            "States": {
+                                "Task": { "Type": "Task", "Resource": "count", "Next": "Fail" },
+                                "Fail": { "Type": "Fail", "Error": "MyError" }
+                            }
+                        }
+                    ],
+                    "End": true
+                }
+            }
+        }"#;
+        let counter = Arc::new(Counter::default());
+        let mut resources = Resources::new();
+        let handler = counter.clone();
+        resources.register("count", move |resource: &str, input: &Value| handler.invoke(resource, input));
+        let state_machine = StateMachine::parse_with_resources(definition, resources)?;
+
+        // Returning at all shows that the waiting branch was dropped, as it waits for a day.
+        let error = state_machine.start(&Value::Null).run().unwrap_err();
+
+        assert_eq!(error.error, Some(ErrorName::Custom("MyError".to_string())));
+        assert_eq!(counter.count.load(Ordering::SeqCst), 1);
+        Ok(())
+    }
+
+    #[cfg(feature = "tokio")]
+    #[tokio::test(start_paused = true)]
+    async fn parallel_failure_cancels_other_branches() -> Result<()> {
+        let definition = r#"{
+            "StartAt": "Parallel",
+            "States": {
+                "Parallel": {
+                    "Type": "Parallel",
+                    "Branches": [
+                        {
+                            "StartAt": "WaitLong",
+                            "States": {
+                                "WaitLong": { "Type": "Wait", "Seconds": 10, "Next": "Count" },
+                                "Count": { "Type": "Task", "Resource": "count", "End": true }
+                            }
+                        },
+                        {
+                            "StartAt": "WaitShort",
+                            "States": {
+                                "WaitShort": { "Type": "Wait", "Seconds": 1, "Next": "Fail" },
+                                "Fail": { "Type": "Fail", "Error": "MyError" }
+                            }
+                        }
+                    ],
+                    "End": true
+                }
+            }
+        }"#;
+        let counter = std::sync::Arc::new(Counter::default());
+        let mut resources = Resources::new();
+        let handler = counter.clone();
+        resources.register("count", move |resource: &str, input: &Value| handler.invoke(resource, input));
+        let state_machine = StateMachine::parse_with_resources(definition, resources)?;
+        let started = tokio::time::Instant::now();
+
+        let error = state_machine.start(&Value::Null).run_async().await.unwrap_err();
+
+        assert_eq!(error.error, Some(ErrorName::Custom("MyError".to_string())));
+        assert_eq!(started.elapsed().as_secs(), 1);
+        tokio::time::sleep(Duration::from_secs(20)).await;
+        assert_eq!(counter.count.load(Ordering::SeqCst), 0);
+        Ok(())
+    }
+
+    #[rstest]
+    #[case::item_selector(
+        r#""ItemSelector": { "index.$": "$$.Map.Item.Index", "value.$": "$$.Map.Item.Value", "constant.$": "$.constant" }"#,
+        json!([{ "index": 0, "value": 1, "constant": "c" }, { "index": 1, "value": 2, "constant": "c" }, { "index": 2, "value": 3, "constant": "c" }]),
+    )]
+    #[case::parameters(
+        r#""Parameters": { "value.$": "$$.Map.Item.Value" }"#,
+        json!([{ "value": 1 }, { "value": 2 }, { "value": 3 }]),
+    )]
+    #[case::items(r#""MaxConcurrency": 1"#, json!([1, 2, 3]))]
+    fn run_map(#[case] fields: &str, #[case] expected: Value) -> Result<()> {
+        let definition = format!(r#"{{
+            "StartAt": "Map",
+            "States": {{
+                "Map": {{
+                    "Type": "Map",
+                    "ItemsPath": "$.items",
+                    {fields},
+                    "ItemProcessor": {{
+                        "StartAt": "Pass",
+                        "States": {{ "Pass": {{ "Type": "Pass", "End": true }} }}
+                    }},
+                    "ResultPath": "$.results",
+                    "End": true
+                }}
+            }}
+        }}"#);
+        let state_machine = StateMachine::parse(&definition)?;
+
+        let output = state_machine.start(&json!({ "items": [1, 2, 3], "constant": "c" })).run()?;
+
+        assert_eq!(output["results"], expected);
+        Ok(())
+    }
+
+    #[rstest]
+    fn run_map_with_item_batcher() -> Result<()> {
+        let definition = r#"{
+            "StartAt": "Map",
+            "States": {
+                "Map": {
+                    "Type": "Map",
+                    "ItemsPath": "$.items",
+                    "ItemSelector": { "doubled.$": "States.Array($$.Map.Item.Value, $$.Map.Item.Value)" },
+                    "ItemBatcher": { "MaxItemsPerBatch": 2, "BatchInput": { "batch.$": "$.name" } },
+                    "ItemProcessor": {
+                        "StartAt": "Pass",
+                        "States": { "Pass": { "Type": "Pass", "End": true } }
+                    },
+                    "End": true
+                }
+            }
+        }"#;
+        let state_machine = StateMachine::parse(definition)?;
+
+        let output = state_machine.start(&json!({ "items": [1, 2, 3], "name": "batch" })).run()?;
+
+        assert_eq!(output, json!([
+            { "BatchInput": { "batch": "batch" }, "Items": [{ "doubled": [1, 1] }, { "doubled": [2, 2] }] },
+            { "BatchInput": { "batch": "batch" }, "Items": [{ "doubled": [3, 3] }] },
+        ]));
+        Ok(())
+    }
+
+    #[rstest]
+    fn run_map_with_item_reader() -> Result<()> {
+        let root = TempDir::new("asl-execution")?;
+        std::fs::create_dir_all(root.path().join("bucket"))?;
+        std::fs::write(root.path().join("bucket/ratings.csv"), "user,rating\nalice,5\nbob,3\ncarol,4\n")?;
+        let definition = r#"{
+            "StartAt": "Map",
+            "States": {
+                "Map": {
+                    "Type": "Map",
+                    "ItemReader": {
+                        "Resource": "arn:aws:states:::s3:getObject",
+                        "ReaderConfig": { "InputType": "CSV", "MaxItemsPath": "$.max" },
+                        "Parameters": { "Bucket": "bucket", "Key.$": "$.key" }
+                    },
+                    "ItemProcessor": {
+                        "StartAt": "Pass",
+                        "States": { "Pass": { "Type": "Pass", "Parameters": { "user.$": "$.user" }, "End": true } }
+                    },
+                    "End": true
+                }
+            }
+        }"#;
+        let mut resources = Resources::new();
+        resources.register_item_reader(ResourcePattern::Prefix("arn:aws:states:::s3:".to_string()), LocalDirectoryReader::new(root.path()));
+        let state_machine = StateMachine::parse_with_resources(definition, resources)?;
+
+        let output = state_machine.start(&json!({ "key": "ratings.csv", "max": 2 })).run()?;
+        let missing = state_machine.start(&json!({ "key": "missing.csv", "max": 2 })).run();
+
+        assert_eq!(output, json!([{ "user": "alice" }, { "user": "bob" }]));
+        assert_eq!(missing.unwrap_err().error, Some(ErrorName::StatesItemReaderFailed));
+        Ok(())
+    }
+
+    #[rstest]
+    fn catch_missing_item_reader() -> Result<()> {
+        let definition = r#"{
+            "StartAt": "Map",
+            "States": {
+                "Map": {
+                    "Type": "Map",
+                    "ItemReader": { "Resource": "arn:aws:states:::s3:getObject", "Parameters": { "Bucket": "bucket", "Key": "items.json" } },
+                    "ItemProcessor": { "StartAt": "Pass", "States": { "Pass": { "Type": "Pass", "End": true } } },
+                    "Catch": [{ "ErrorEquals": ["States.ItemReaderFailed"], "ResultPath": "$.error", "Next": "Caught" }],
+                    "End": true
+                },
+                "Caught": { "Type": "Pass", "End": true }
+            }
+        }"#;
+        let state_machine = StateMachine::parse(definition)?;
+
+        let output = state_machine.start(&json!({})).run()?;
+
+        assert_eq!(output["error"]["Error"], json!("States.ItemReaderFailed"));
+        Ok(())
+    }
+
+    #[rstest]
+    fn run_map_with_result_writer() -> Result<()> {
+        let root = TempDir::new("asl-execution")?;
+        let definition = r#"{
+            "StartAt": "Map",
+            "States": {
+                "Map": {
+                    "Type": "Map",
+                    "ToleratedFailureCount": 1,
+                    "ItemProcessor": {
+                        "StartAt": "Increment",
+                        "States": { "Increment": { "Type": "Task", "Resource": "increment", "End": true } }
+                    },
+                    "ResultWriter": {
+                        "Resource": "arn:aws:states:::s3:putObject",
+                        "Parameters": { "Bucket.$": "$.bucket", "Prefix": "jobs" }
+                    },
+                    "ItemsPath": "$.items",
+                    "End": true
+                }
+            }
+        }"#;
+        let mut resources = resources();
+        resources.register_result_writer(ResourcePattern::Prefix("arn:aws:states:::s3:".to_string()), LocalDirectoryWriter::new(root.path()));
+        let state_machine = StateMachine::parse_with_resources(definition, resources)?;
+
+        let output = state_machine.start(&json!({ "bucket": "results", "items": [1, "two", 3] })).run()?;
+        let invalid_bucket = state_machine.start(&json!({ "bucket": "../results", "items": [1] })).run();
+
+        let manifest = root.read(format!("results/{}", output["ResultWriterDetails"]["Key"].as_str().unwrap_or_default()))?;
+        let succeeded = root.read(format!("results/{}", manifest["ResultFiles"]["SUCCEEDED"][0]["Key"].as_str().unwrap_or_default()))?;
+        let failed = root.read(format!("results/{}", manifest["ResultFiles"]["FAILED"][0]["Key"].as_str().unwrap_or_default()))?;
+        let map_run_id = output["MapRunArn"].as_str().unwrap_or_default();
+        assert_eq!(output["ResultWriterDetails"], json!({ "Bucket": "results", "Key": format!("jobs/{map_run_id}/manifest.json") }));
+        assert_eq!(manifest["ResultFiles"]["PENDING"], json!([]));
+        assert_eq!(succeeded, json!([
+            { "Input": "1", "Output": "2", "Status": "SUCCEEDED" },
+            { "Input": "3", "Output": "4", "Status": "SUCCEEDED" },
+        ]));
+        assert_eq!(failed, json!([{ "Input": "\"two\"", "Error": "NotANumber", "Cause": "Can't increment \"two\"", "Status": "FAILED" }]));
+        assert_eq!(invalid_bucket.unwrap_err().error, Some(ErrorName::StatesResultWriterFailed));
+        Ok(())
+    }
+
+    #[rstest]
+    fn failed_map_writes_pending_results() -> Result<()> {
+        let definition = r#"{
+            "StartAt": "Map",
+            "States": {
+                "Map": {
+                    "Type": "Map",
+                    "MaxConcurrency": 1,
+                    "ItemProcessor": {
+                        "StartAt": "Increment",
+                        "States": { "Increment": { "Type": "Task", "Resource": "increment", "End": true } }
+                    },
+                    "ResultWriter": { "Resource": "writer" },
+                    "End": true
+                }
+            }
+        }"#;
+        let written = Arc::new(Mutex::new(None));
+        let mut resources = resources();
+        let recorder = written.clone();
+        resources.register_result_writer(ResourcePattern::Prefix("writer".to_string()), move |_: &str, _: &Value, results: &MapRunResults| {
+            *recorder.lock().unwrap() = Some(results.clone());
+            Err(ExecutionError::new(ErrorName::StatesResultWriterFailed, "Unreachable destination"))
+        });
+        let state_machine = StateMachine::parse_with_resources(definition, resources)?;
+
+        let error = state_machine.start(&json!([1, "two", 3])).run().unwrap_err();
+
+        // The failure of an iteration takes precedence over the failure to write the results.
+        assert_eq!(error.error, Some(ErrorName::Custom("NotANumber".to_string())));
+        let results = written.lock().unwrap().take().unwrap_or_default();
+        assert_eq!(results.succeeded.len(), 1);
+        assert_eq!(results.failed.len(), 1);
+        assert_eq!(results.pending, vec![json!({ "Input": "3", "Status": "PENDING" })]);
+        Ok(())
+    }
+
+    #[rstest]
+    fn run_distributed_map() -> Result<()> {
+        let definition = r#"{
+            "StartAt": "Map",
+            "States": {
+                "Map": {
+                    "Type": "Map",
+                    "MaxConcurrency": 1,
+                    "ToleratedFailureCount": 1,
+                    "ItemProcessor": {
+                        "ProcessorConfig": { "Mode": "DISTRIBUTED", "ExecutionType": "STANDARD" },
+                        "StartAt": "Increment",
+                        "States": {
+                            "Increment": { "Type": "Task", "Resource": "increment", "Next": "Describe" },
+                            "Describe": {
+                                "Type": "Pass",
+                                "Parameters": { "Result.$": "$", "Input.$": "$$.Execution.Input", "Id.$": "$$.Execution.Id" },
+                                "End": true
+                            }
+                        }
+                    },
+                    "End": true
+                }
+            }
+        }"#;
+        let state_machine = StateMachine::parse_with_resources(definition, resources())?;
+        let mut execution = state_machine.start(&json!([1, "two", 3]));
+        execution.by_ref().for_each(drop);
+
+        let children = execution.child_executions();
+        let ids: Vec<&str> = children.iter().map(|child| child.id.as_str()).collect();
+        assert_eq!(execution.status(), &ExecutionStatus::Succeeded(json!([
+            { "Result": 2, "Input": 1, "Id": ids[0] },
+            { "Error": "NotANumber", "Cause": "Can't increment \"two\"" },
+            { "Result": 4, "Input": 3, "Id": ids[2] },
+        ])));
+        assert!(ids.iter().all(|id| *id != execution.id()));
+        assert_ne!(ids[0], ids[2]);
+        assert!(children.iter().all(|child| child.parent_id == execution.id() && child.map_state == "Map"));
+        assert!(children.iter().all(|child| child.execution_type == Some(ExecutionType::Standard)));
+        assert_eq!(children.iter().map(|child| child.input.clone()).collect::<Vec<_>>(), vec![json!(1), json!("two"), json!(3)]);
+        // The failure of an iteration only fails its own child execution.
+        assert_eq!(children[1].status, ExecutionStatus::Failed(ExecutionError::new(ErrorName::Custom("NotANumber".to_string()), "Can't increment \"two\"")));
+        assert_eq!(children.iter().map(|child| child.history.len()).collect::<Vec<_>>(), vec![2, 1, 2]);
+        assert_eq!(children[2].history[0].output, Ok(json!(4)));
+        Ok(())
+    }
+
+    #[rstest]
+    fn run_map_iteration_fails() -> Result<()> {
+        let definition = r#"{
+            "StartAt": "Map",
+            "States": {
+                "Map": {
+                    "Type": "Map",
+                    "ItemProcessor": {
+                        "StartAt": "Increment",
+                        "States": { "Increment": { "Type": "Task", "Resource": "increment", "End": true } }
+                    },
+                    "End": true
+                }
+            }
+        }"#;
+        let state_machine = StateMachine::parse_with_resources(definition, resources())?;
+
+        let error = state_machine.start(&json!([1, "two", 3])).run().unwrap_err();
+
+        assert_eq!(error, ExecutionError::new(ErrorName::Custom("NotANumber".to_string()), "Can't increment \"two\""));
+        Ok(())
+    }
+
+    #[rstest]
+    #[case::within_count(r#""ToleratedFailureCount": 1"#, Ok(json!([2, { "Error": "NotANumber", "Cause": "Can't increment \"two\"" }, 4, 5])))]
+    #[case::within_count_path(r#""ToleratedFailureCountPath": "$[2]""#, Ok(json!([2, { "Error": "NotANumber", "Cause": "Can't increment \"two\"" }, 4, 5])))]
+    #[case::within_percentage(r#""ToleratedFailurePercentage": 25"#, Ok(json!([2, { "Error": "NotANumber", "Cause": "Can't increment \"two\"" }, 4, 5])))]
+    #[case::within_decimal_percentage(r#""ToleratedFailurePercentage": 25.5"#, Ok(json!([2, { "Error": "NotANumber", "Cause": "Can't increment \"two\"" }, 4, 5])))]
+    #[case::exceeds_count(
+        r#""ToleratedFailureCount": 0"#,
+        Err(ExecutionError::new(ErrorName::StatesExceedToleratedFailureThreshold, "1 of the 4 iterations failed, which exceeds the tolerated failure threshold")),
+    )]
+    #[case::exceeds_percentage(
+        r#""ToleratedFailurePercentage": 20"#,
+        Err(ExecutionError::new(ErrorName::StatesExceedToleratedFailureThreshold, "1 of the 4 iterations failed, which exceeds the tolerated failure threshold")),
+    )]
+    #[case::exceeds_decimal_percentage(
+        r#""ToleratedFailurePercentage": 24.5"#,
+        Err(ExecutionError::new(ErrorName::StatesExceedToleratedFailureThreshold, "1 of the 4 iterations failed, which exceeds the tolerated failure threshold")),
+    )]
+    #[case::exceeds_either(
+        r#""ToleratedFailureCount": 5, "ToleratedFailurePercentagePath": "$[0]""#,
+        Err(ExecutionError::new(ErrorName::StatesExceedToleratedFailureThreshold, "1 of the 4 iterations failed, which exceeds the tolerated failure threshold")),
+    )]
+    #[case::invalid_percentage_path(
+        r#""ToleratedFailurePercentagePath": "$[1]""#,
+        Err(ExecutionError::runtime("The JSONPath '$[1]' specified for the field 'ToleratedFailurePercentagePath' must select a number between 0 and 100")),
+    )]
+    fn run_map_tolerated_failures(#[case] fields: &str, #[case] expected: Result<Value, ExecutionError>) -> Result<()> {
+        let definition = format!(r#"{{
+            "StartAt": "Map",
+            "States": {{
+                "Map": {{
+                    "Type": "Map",
+                    {fields},
+                    "ItemProcessor": {{
+                        "StartAt": "Increment",
+                        "States": {{ "Increment": {{ "Type": "Task", "Resource": "increment", "End": true }} }}
+                    }},
+                    "End": true
+                }}
+            }}
+        }}"#);
+        let state_machine = StateMachine::parse_with_resources(&definition, resources())?;
+
+        let output = state_machine.start(&json!([1, "two", 3, 4])).run();
+
+        assert_eq!(output, expected);
+        Ok(())
+    }
+
+    #[rstest]
+    #[case::limited(json!(2), 2)]
+    #[case::unlimited(json!(0), 6)]
+    fn run_map_iterations_concurrently(#[case] max_concurrency: Value, #[case] peak: usize) -> Result<()> {
+        let definition = json!({
+            "StartAt": "Map",
+            "States": {
+                "Map": {
+                    "Type": "Map",
+                    "MaxConcurrency": max_concurrency,
+                    "ItemProcessor": { "StartAt": "Meet", "States": { "Meet": { "Type": "Task", "Resource": "meet", "End": true } } },
+                    "End": true
+                }
+            }
+        });
+        let (rendezvous, resources) = rendezvous_resources(peak);
+        let state_machine = StateMachine::parse_with_resources(&definition.to_string(), resources)?;
+
+        let output = state_machine.start(&json!([1, 2, 3, 4, 5, 6])).run()?;
+
+        assert_eq!(output, json!([1, 2, 3, 4, 5, 6]));
+        assert_eq!(rendezvous.peak.load(Ordering::SeqCst), peak);
+        Ok(())
+    }
+
+    #[cfg(feature = "tokio")]
+    #[tokio::test(start_paused = true)]
+    async fn map_honors_max_concurrency() -> Result<()> {
+        let definition = r#"{
+            "StartAt": "Map",
+            "States": {
+                "Map": {
+                    "Type": "Map",
+                    "MaxConcurrency": 2,
+                    "ItemProcessor": {
+                        "StartAt": "Wait",
+                        "States": { "Wait": { "Type": "Wait", "SecondsPath": "$", "End": true } }
+                    },
+                    "End": true
+                }
+            }
+        }"#;
+        let state_machine = StateMachine::parse(definition)?;
+        let started = tokio::time::Instant::now();
+
+        let output = state_machine.start(&json!([10, 1, 10, 1])).run_async().await?;
+
+        assert_eq!(output, json!([10, 1, 10, 1]));
+        // The second slot is reused as soon as an iteration finishes: 1 + 10 on one side, 10 + 1 on the other.
+        assert_eq!(started.elapsed().as_secs(), 11);
+        Ok(())
+    }
+
+    #[rstest]
+    fn context_object() -> Result<()> {
+        let definition = r#"{
+            "StartAt": "State",
+            "States": {
+                "State": { "Type": "Pass", "Parameters": { "id.$": "$$.Execution.Id", "input.$": "$$.Execution.Input" }, "End": true }
+            }
+        }"#;
+        let state_machine = StateMachine::parse(definition)?;
+        let execution = state_machine.start(&json!("input"));
+        let id = execution.id().to_string();
+
+        let output = execution.run()?;
+
+        assert_eq!(output, json!({ "id": id, "input": "input" }));
+        Ok(())
+    }
+
+    #[rstest]
+    fn step_through_execution() -> Result<()> {
+        let definition = r#"{
+            "StartAt": "Start",
+            "States": {
+                "Start": { "Type": "Pass", "Result": 1, "Next": "Increment" },
+                "Increment": { "Type": "Task", "Resource": "increment", "End": true }
+            }
+        }"#;
+        let state_machine = StateMachine::parse_with_resources(definition, resources())?;
+        let mut execution = state_machine.start(&json!("input"));
+        assert_eq!(execution.current_state(), Some("Start"));
+        assert_eq!(execution.status(), &ExecutionStatus::Running);
+
+        let step = execution.next();
+        assert_eq!(step, Some(StateExecution {
+            name: "Start",
+            input: json!("input"),
+            output: Ok(json!(1)),
+            next: Some("Increment"),
+        }));
+        assert_eq!(execution.current_state(), Some("Increment"));
+        assert_eq!(execution.input(), &json!(1));
+        assert!(!execution.is_finished());
+
+        let step = execution.next();
+        assert_eq!(step, Some(StateExecution {
+            name: "Increment",
+            input: json!(1),
+            output: Ok(json!(2)),
+            next: None,
+        }));
+        assert_eq!(execution.current_state(), None);
+        assert_eq!(execution.status(), &ExecutionStatus::Succeeded(json!(2)));
+
+        assert_eq!(execution.next(), None);
+        Ok(())
+    }
+
+    #[rstest]
+    fn step_into_failure() -> Result<()> {
+        let definition = r#"{
+            "StartAt": "Fail",
+            "States": {
+                "Fail": { "Type": "Fail", "Error": "MyError" }
+            }
+        }"#;
+        let state_machine = StateMachine::parse(definition)?;
+        let mut execution = state_machine.start(&Value::Null);
+
+        let step = execution.next().expect("The execution should run the Fail state");
+        let expected_error = ExecutionError { error: Some(ErrorName::Custom("MyError".to_string())), cause: None };
+        assert_eq!(step.output, Err(expected_error.clone()));
+        assert!(execution.is_finished());
+        assert_eq!(execution.status(), &ExecutionStatus::Failed(expected_error));
+        assert_eq!(execution.next(), None);
+        Ok(())
+    }
+
+    #[rstest]
+    #[case::fail_state(
+        r#"{ "Type": "Fail", "Error": "MyError", "Cause": "Something happened" }"#,
+        ExecutionError::new(ErrorName::Custom("MyError".to_string()), "Something happened"),
+    )]
+    #[case::fail_state_without_fields(
+        r#"{ "Type": "Fail" }"#,
+        ExecutionError { error: None, cause: None },
+    )]
+    #[case::task_without_handler(
+        r#"{ "Type": "Task", "Resource": "unknown", "End": true }"#,
+        ExecutionError::runtime("No handler registered for resource 'unknown'"),
+    )]
+    #[case::task_error(
+        r#"{ "Type": "Task", "Resource": "increment", "End": true }"#,
+        ExecutionError::new(ErrorName::Custom("NotANumber".to_string()), "Can't increment \"not a number\""),
+    )]
+    #[case::input_path_not_found(
+        r#"{ "Type": "Pass", "InputPath": "$.missing", "End": true }"#,
+        ExecutionError::runtime("The JSONPath '$.missing' specified for the field 'InputPath' could not be found in the input"),
+    )]
+    #[case::output_path_not_found(
+        r#"{ "Type": "Pass", "OutputPath": "$.missing", "End": true }"#,
+        ExecutionError::runtime("The JSONPath '$.missing' specified for the field 'OutputPath' could not be found in the output"),
+    )]
+    #[case::parameter_path_not_found(
+        r#"{ "Type": "Task", "Resource": "return", "Parameters": { "value.$": "$.missing" }, "End": true }"#,
+        ExecutionError::new(ErrorName::StatesParameterPathFailure, "The JSONPath '$.missing' specified for the field 'value.$' could not be found in the input"),
+    )]
+    #[case::result_path_match_failure(
+        r#"{ "Type": "Pass", "Result": 1, "ResultPath": "$.kind.nested", "End": true }"#,
+        ExecutionError::new(ErrorName::StatesResultPathMatchFailure, "Can't write to '$.kind.nested': can't go through a string"),
+    )]
+    #[case::parallel_branch_error(
+        r#"{ "Type": "Parallel", "Branches": [
+            { "StartAt": "Pass", "States": { "Pass": { "Type": "Pass", "End": true } } },
+            { "StartAt": "Increment", "States": { "Increment": { "Type": "Task", "Resource": "increment", "End": true } } }
+        ], "End": true }"#,
+        ExecutionError::new(ErrorName::Custom("NotANumber".to_string()), "Can't increment \"not a number\""),
+    )]
+    #[case::parallel_branch_failed(
+        r#"{ "Type": "Parallel", "Branches": [
+            { "StartAt": "Fail", "States": { "Fail": { "Type": "Fail", "Cause": "Something happened" } } }
+        ], "End": true }"#,
+        ExecutionError::new(ErrorName::StatesBranchFailed, "Something happened"),
+    )]
+    #[case::map_items_not_an_array(
+        r#"{ "Type": "Map", "ItemProcessor": { "StartAt": "Pass", "States": { "Pass": { "Type": "Pass", "End": true } } }, "End": true }"#,
+        ExecutionError::runtime("The field 'ItemsPath' must select an array, but it selects \"not a number\""),
+    )]
+    fn run_fails(#[case] state: &str, #[case] expected: ExecutionError) -> Result<()> {
+        let definition = format!(r#"{{ "StartAt": "State", "States": {{ "State": {state} }} }}"#);
+        let state_machine = StateMachine::parse_with_resources(&definition, resources())?;
+
+        let error = state_machine.start(&json!("not a number")).run().unwrap_err();
+
+        assert_eq!(error, expected);
+        Ok(())
+    }
+
+    #[rstest]
+    #[case::fail_state_paths(
+        r#"{ "Type": "Fail", "ErrorPath": "States.Format('{}Error', $.kind)", "CausePath": "$.cause" }"#,
+        ExecutionError::new(ErrorName::Custom("MyError".to_string()), "Something happened"),
+    )]
+    #[case::fail_state_path_not_a_string(
+        r#"{ "Type": "Fail", "ErrorPath": "$.count" }"#,
+        ExecutionError::runtime("The field 'ErrorPath' must resolve to a string, but it resolves to 1"),
+    )]
+    fn run_fail_state_paths(#[case] state: &str, #[case] expected: ExecutionError) -> Result<()> {
+        let definition = format!(r#"{{ "StartAt": "State", "States": {{ "State": {state} }} }}"#);
+        let state_machine = StateMachine::parse(&definition)?;
+
+        let error = state_machine.start(&json!({ "kind": "My", "cause": "Something happened", "count": 1 })).run().unwrap_err();
+
+        assert_eq!(error, expected);
+        Ok(())
+    }
+}
@@ -0,0 +1,713 @@
+use std::fmt::{Debug, Display, Formatter};
+use std::str::FromStr;
+use base64::Engine;
+use base64::engine::general_purpose::STANDARD as BASE64;
+use md5::Md5;
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use serde_json::Value;
+use sha1::Sha1;
+use sha2::{Digest, Sha256, Sha384, Sha512};
+use thiserror::Error;
+use uuid::Uuid;
+use crate::asl::error_handling::ErrorName;
+use crate::asl::execution::ExecutionError;
+use crate::asl::json_path::JsonPath;
+
+#[derive(Error, Debug, Clone, PartialEq, Eq)]
+#[error("Invalid Intrinsic Function '{function}' at position {position}: {message}")]
+pub struct IntrinsicFunctionError {
+    pub function: String,
+    pub position: usize,
+    pub message: String,
+}
+
+/// The Intrinsic Functions, see https://states-language.net/spec.html#appendix-b
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Function {
+    /// `States.Format(template, ...values)`: replaces each `{}` in the template with the next value.
+    ///
+    /// The template must be a string literal, which is stored with its escape sequences so that
+    /// `\{` and `\}` are not taken as placeholders.
+    Format,
+    StringToJson,
+    JsonToString,
+    Array,
+    ArrayPartition,
+    ArrayContains,
+    ArrayRange,
+    ArrayGetItem,
+    ArrayLength,
+    ArrayUnique,
+    Base64Encode,
+    Base64Decode,
+    Hash,
+    JsonMerge,
+    MathRandom,
+    MathAdd,
+    StringSplit,
+    Uuid,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum ArgumentType {
+    Any,
+    String,
+    Integer,
+    Boolean,
+    Array,
+    Object,
+}
+
+impl ArgumentType {
+    fn matches(self, value: &Value) -> bool {
+        match self {
+            ArgumentType::Any => true,
+            ArgumentType::String => value.is_string(),
+            ArgumentType::Integer => value.is_i64(),
+            ArgumentType::Boolean => value.is_boolean(),
+            ArgumentType::Array => value.is_array(),
+            ArgumentType::Object => value.is_object(),
+        }
+    }
+}
+
+impl Display for ArgumentType {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            ArgumentType::Any => "any value",
+            ArgumentType::String => "a string",
+            ArgumentType::Integer => "an integer",
+            ArgumentType::Boolean => "a boolean",
+            ArgumentType::Array => "an array",
+            ArgumentType::Object => "an object",
+        })
+    }
+}
+
+/// The arguments a function accepts: the `required` ones, then the `optional` ones, then any
+/// number of `variadic` ones.
+struct Signature {
+    required: &'static [ArgumentType],
+    optional: &'static [ArgumentType],
+    variadic: Option<ArgumentType>,
+}
+
+impl Signature {
+    const fn fixed(required: &'static [ArgumentType]) -> Signature {
+        Signature {
+            required,
+            optional: &[],
+            variadic: None,
+        }
+    }
+
+    fn argument_type(&self, index: usize) -> Option<ArgumentType> {
+        self.required.iter().chain(self.optional).nth(index).copied().or(self.variadic)
+    }
+
+    fn check_arity(&self, count: usize) -> Result<(), String> {
+        let min = self.required.len();
+        let max = min + self.optional.len();
+        match (self.variadic, count) {
+            (_, count) if count < min => Err(format!("expected at least {min} arguments, but got {count}")),
+            (None, count) if count > max && min == max => Err(format!("expected {min} arguments, but got {count}")),
+            (None, count) if count > max => Err(format!("expected at most {max} arguments, but got {count}")),
+            _ => Ok(()),
+        }
+    }
+}
+
+const HASH_ALGORITHMS: &[&str] = &["MD5", "SHA-1", "SHA-256", "SHA-384", "SHA-512"];
+
+impl Function {
+    const ALL: [Function; 18] = [
+        Function::Format, Function::StringToJson, Function::JsonToString, Function::Array,
+        Function::ArrayPartition, Function::ArrayContains, Function::ArrayRange, Function::ArrayGetItem,
+        Function::ArrayLength, Function::ArrayUnique, Function::Base64Encode, Function::Base64Decode,
+        Function::Hash, Function::JsonMerge, Function::MathRandom, Function::MathAdd,
+        Function::StringSplit, Function::Uuid,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Function::Format => "States.Format",
+            Function::StringToJson => "States.StringToJson",
+            Function::JsonToString => "States.JsonToString",
+            Function::Array => "States.Array",
+            Function::ArrayPartition => "States.ArrayPartition",
+            Function::ArrayContains => "States.ArrayContains",
+            Function::ArrayRange => "States.ArrayRange",
+            Function::ArrayGetItem => "States.ArrayGetItem",
+            Function::ArrayLength => "States.ArrayLength",
+            Function::ArrayUnique => "States.ArrayUnique",
+            Function::Base64Encode => "States.Base64Encode",
+            Function::Base64Decode => "States.Base64Decode",
+            Function::Hash => "States.Hash",
+            Function::JsonMerge => "States.JsonMerge",
+            Function::MathRandom => "States.MathRandom",
+            Function::MathAdd => "States.MathAdd",
+            Function::StringSplit => "States.StringSplit",
+            Function::Uuid => "States.UUID",
+        }
+    }
+
+    fn signature(self) -> Signature {
+        use ArgumentType::*;
+        match self {
+            Function::Format => Signature {
+                required: &[String],
+                optional: &[],
+                variadic: Some(Any),
+            },
+            Function::StringToJson => Signature::fixed(&[String]),
+            Function::JsonToString => Signature::fixed(&[Any]),
+            Function::Array => Signature {
+                required: &[],
+                optional: &[],
+                variadic: Some(Any),
+            },
+            Function::ArrayPartition => Signature::fixed(&[Array, Integer]),
+            Function::ArrayContains => Signature::fixed(&[Array, Any]),
+            Function::ArrayRange => Signature::fixed(&[Integer, Integer, Integer]),
+            Function::ArrayGetItem => Signature::fixed(&[Array, Integer]),
+            Function::ArrayLength => Signature::fixed(&[Array]),
+            Function::ArrayUnique => Signature::fixed(&[Array]),
+            Function::Base64Encode => Signature::fixed(&[String]),
+            Function::Base64Decode => Signature::fixed(&[String]),
+            Function::Hash => Signature::fixed(&[String, String]),
+            Function::JsonMerge => Signature::fixed(&[Object, Object, Boolean]),
+            Function::MathRandom => Signature {
+                required: &[Integer, Integer],
+                optional: &[Integer],
+                variadic: None,
+            },
+            Function::MathAdd => Signature::fixed(&[Integer, Integer]),
+            Function::StringSplit => Signature::fixed(&[String, String]),
+            Function::Uuid => Signature::fixed(&[]),
+        }
+    }
+
+    /// Applies the function to already evaluated arguments, whose types were checked against the
+    /// signature of the function.
+    fn apply(self, arguments: Vec<Value>) -> Result<Value, String> {
+        let string = |index: usize| arguments[index].as_str().unwrap_or_default();
+        let integer = |index: usize| arguments[index].as_i64().unwrap_or_default();
+        let array = |index: usize| arguments[index].as_array().map(Vec::as_slice).unwrap_or_default();
+        match self {
+            Function::Format => format(string(0), &arguments[1..]).map(Value::String),
+            Function::StringToJson => serde_json::from_str(string(0))
+                .map_err(|e| format!("Can't parse '{}' as JSON: {e}", string(0))),
+            Function::JsonToString => Ok(Value::String(arguments[0].to_string())),
+            Function::Array => Ok(Value::Array(arguments)),
+            Function::ArrayPartition => {
+                let size = usize::try_from(integer(1)).ok().filter(|size| *size > 0)
+                    .ok_or_else(|| format!("The chunk size must be a positive integer, but it is {}", integer(1)))?;
+                Ok(Value::Array(array(0).chunks(size).map(|chunk| Value::Array(chunk.to_vec())).collect()))
+            }
+            Function::ArrayContains => Ok(Value::Bool(array(0).contains(&arguments[1]))),
+            Function::ArrayRange => array_range(integer(0), integer(1), integer(2)),
+            Function::ArrayGetItem => usize::try_from(integer(1)).ok()
+                .and_then(|index| array(0).get(index))
+                .cloned()
+                .ok_or_else(|| format!("The index {} is out of bounds for an array of length {}", integer(1), array(0).len())),
+            Function::ArrayLength => Ok(Value::from(array(0).len())),
+            Function::ArrayUnique => {
+                let mut unique: Vec<Value> = vec![];
+                for item in array(0) {
+                    if !unique.contains(item) {
+                        unique.push(item.clone());
+                    }
+                }
+                Ok(Value::Array(unique))
+            }
+            Function::Base64Encode => {
+                if string(0).chars().count() > 10_000 {
+                    return Err("The input must have at most 10000 characters".to_string());
+                }
+                Ok(Value::String(BASE64.encode(string(0))))
+            }
+            Function::Base64Decode => BASE64.decode(string(0)).ok()
+                .and_then(|decoded| String::from_utf8(decoded).ok())
+                .map(Value::String)
+                .ok_or_else(|| format!("Can't decode '{}' as a base64 encoded string", string(0))),
+            Function::Hash => hash(string(0), string(1)).map(Value::String),
+            Function::JsonMerge => {
+                let mut merged = arguments[0].clone();
+                merge(&mut merged, &arguments[1], arguments[2].as_bool().unwrap_or_default());
+                Ok(merged)
+            }
+            Function::MathRandom => {
+                let (start, end) = (integer(0), integer(1));
+                if start >= end {
+                    return Err(format!("The start {start} must be smaller than the end {end}"));
+                }
+                let value = match arguments.get(2) {
+                    Some(seed) => StdRng::seed_from_u64(seed.as_i64().unwrap_or_default() as u64).gen_range(start..end),
+                    None => rand::thread_rng().gen_range(start..end),
+                };
+                Ok(Value::from(value))
+            }
+            Function::MathAdd => integer(0).checked_add(integer(1))
+                .map(Value::from)
+                .ok_or_else(|| "The result overflows".to_string()),
+            Function::StringSplit => {
+                let delimiters = string(1);
+                Ok(Value::Array(string(0)
+                    .split(|c| delimiters.contains(c))
+                    .filter(|part| !part.is_empty())
+                    .map(Value::from)
+                    .collect()))
+            }
+            Function::Uuid => Ok(Value::String(Uuid::new_v4().to_string())),
+        }
+    }
+}
+
+/// Renders a `States.Format` template, which still contains its escape sequences.
+fn format(template: &str, values: &[Value]) -> Result<String, String> {
+    let mut values = values.iter();
+    let mut formatted = String::new();
+    let mut chars = template.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' => formatted.extend(chars.next()),
+            '{' if chars.peek() == Some(&'}') => {
+                chars.next();
+                match values.next() {
+                    Some(Value::String(value)) => formatted.push_str(value),
+                    Some(value @ (Value::Number(_) | Value::Bool(_) | Value::Null)) => formatted.push_str(&value.to_string()),
+                    Some(value) => return Err(format!("Can't format {value}: only strings, numbers, booleans and null are allowed")),
+                    None => return Err("There are more placeholders than values".to_string()),
+                }
+            }
+            c => formatted.push(c),
+        }
+    }
+    Ok(formatted)
+}
+
+/// Counts the `{}` placeholders of a `States.Format` template, rejecting unescaped braces that
+/// aren't placeholders.
+fn count_placeholders(template: &str) -> Result<usize, String> {
+    let mut count = 0;
+    let mut chars = template.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' => {
+                chars.next();
+            }
+            '{' if chars.peek() == Some(&'}') => {
+                chars.next();
+                count += 1;
+            }
+            '{' | '}' => return Err(format!("The template has an unescaped '{c}'")),
+            _ => {}
+        }
+    }
+    Ok(count)
+}
+
+fn array_range(start: i64, end: i64, step: i64) -> Result<Value, String> {
+    if step == 0 {
+        return Err("The step can't be 0".to_string());
+    }
+    let count = if (step > 0 && start <= end) || (step < 0 && start >= end) {
+        // The distance between the bounds doesn't always fit in an i64, but such ranges are too
+        // long anyway.
+        end.checked_sub(start).and_then(|distance| distance.checked_div(step)).and_then(|count| count.checked_add(1))
+    } else {
+        Some(0)
+    };
+    let count = match count {
+        Some(count) if count <= 1000 => count,
+        Some(count) => return Err(format!("The range would have {count} items, but at most 1000 are allowed")),
+        None => return Err("The range would have more than 1000 items, but at most 1000 are allowed".to_string()),
+    };
+    Ok(Value::Array((0..count).map(|index| Value::from(start + index * step)).collect()))
+}
+
+fn hash(data: &str, algorithm: &str) -> Result<String, String> {
+    let digest = match algorithm {
+        "MD5" => Md5::digest(data).to_vec(),
+        "SHA-1" => Sha1::digest(data).to_vec(),
+        "SHA-256" => Sha256::digest(data).to_vec(),
+        "SHA-384" => Sha384::digest(data).to_vec(),
+        "SHA-512" => Sha512::digest(data).to_vec(),
+        algorithm => return Err(format!("Unknown hash algorithm '{algorithm}', expected one of {}", HASH_ALGORITHMS.join(", "))),
+    };
+    Ok(digest.iter().map(|byte| format!("{byte:02x}")).collect())
+}
+
+/// Merges `other` into `target`. A shallow merge replaces the fields of `target`, while a deep
+/// merge recursively merges the fields that are objects in both.
+fn merge(target: &mut Value, other: &Value, deep: bool) {
+    let (Value::Object(target), Value::Object(other)) = (target, other) else {
+        return;
+    };
+    for (key, value) in other {
+        match target.get_mut(key) {
+            Some(existing) if deep && existing.is_object() && value.is_object() => merge(existing, value, deep),
+            _ => {
+                target.insert(key.clone(), value.clone());
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Argument {
+    /// A string, number, boolean or null literal.
+    Literal(Value),
+    Path(JsonPath),
+    Function(IntrinsicFunction),
+}
+
+impl Argument {
+    fn evaluate(&self, input: &Value, context: &Value) -> Result<Value, ExecutionError> {
+        match self {
+            Argument::Literal(value) => Ok(value.clone()),
+            Argument::Path(path) => path.evaluate(input, context).ok_or_else(|| ExecutionError::new(
+                ErrorName::StatesIntrinsicFailure,
+                format!("The JSONPath '{path}' could not be found"),
+            )),
+            Argument::Function(function) => function.evaluate(input, context),
+        }
+    }
+}
+
+/// A parsed Intrinsic Function call, such as `States.Format('Hello {}', $.name)`.
+///
+/// The number of arguments and the types of the literal arguments are checked when parsing. The
+/// types of the arguments resolved from paths or nested calls are checked on evaluation.
+#[derive(Clone, PartialEq, Eq)]
+pub struct IntrinsicFunction {
+    source: String,
+    function: Function,
+    arguments: Vec<Argument>,
+}
+
+impl IntrinsicFunction {
+    pub fn function(&self) -> Function {
+        self.function
+    }
+
+    pub fn arguments(&self) -> &[Argument] {
+        &self.arguments
+    }
+
+    /// Evaluates the function, resolving its paths against the input and the Context Object.
+    ///
+    /// Fails with `States.IntrinsicFailure` if an argument is invalid.
+    pub fn evaluate(&self, input: &Value, context: &Value) -> Result<Value, ExecutionError> {
+        let name = self.function.name();
+        let signature = self.function.signature();
+        let mut arguments = Vec::with_capacity(self.arguments.len());
+        for (index, argument) in self.arguments.iter().enumerate() {
+            let value = argument.evaluate(input, context)?;
+            let expected = signature.argument_type(index).unwrap_or(ArgumentType::Any);
+            if !expected.matches(&value) {
+                return Err(ExecutionError::new(
+                    ErrorName::StatesIntrinsicFailure,
+                    format!("The argument {} of {name} must be {expected}, but it is {value}", index + 1),
+                ));
+            }
+            arguments.push(value);
+        }
+        self.function.apply(arguments)
+            .map_err(|message| ExecutionError::new(ErrorName::StatesIntrinsicFailure, format!("{name}: {message}")))
+    }
+}
+
+impl FromStr for IntrinsicFunction {
+    type Err = IntrinsicFunctionError;
+
+    fn from_str(source: &str) -> Result<Self, Self::Err> {
+        let mut parser = Parser { source, position: 0 };
+        let function = parser.call()?;
+        if parser.position < source.len() {
+            return Err(parser.error("Unexpected character"));
+        }
+        Ok(function)
+    }
+}
+
+impl Display for IntrinsicFunction {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.write_str(&self.source)
+    }
+}
+
+impl Debug for IntrinsicFunction {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "IntrinsicFunction({:?})", self.source)
+    }
+}
+
+struct Parser<'s> {
+    source: &'s str,
+    position: usize,
+}
+
+impl<'s> Parser<'s> {
+    fn error(&self, message: impl Into<String>) -> IntrinsicFunctionError {
+        IntrinsicFunctionError {
+            function: self.source.to_string(),
+            position: self.position,
+            message: message.into(),
+        }
+    }
+
+    fn rest(&self) -> &'s str {
+        &self.source[self.position..]
+    }
+
+    fn eat(&mut self, token: &str) -> bool {
+        if self.rest().starts_with(token) {
+            self.position += token.len();
+            true
+        } else {
+            false
+        }
+    }
+
+    fn expect(&mut self, token: &str) -> Result<(), IntrinsicFunctionError> {
+        if self.eat(token) {
+            Ok(())
+        } else {
+            Err(self.error(format!("Expected '{token}'")))
+        }
+    }
+
+    fn skip_whitespace(&mut self) {
+        let trimmed = self.rest().trim_start();
+        self.position = self.source.len() - trimmed.len();
+    }
+
+    fn call(&mut self) -> Result<IntrinsicFunction, IntrinsicFunctionError> {
+        let start = self.position;
+        let rest = self.rest();
+        let length = rest.find(|c: char| !(c.is_ascii_alphanumeric() || c == '.')).unwrap_or(rest.len());
+        let function = Function::ALL.into_iter()
+            .find(|function| function.name() == &rest[..length])
+            .ok_or_else(|| self.error(format!("Unknown Intrinsic Function '{}'", &rest[..length])))?;
+        self.position += length;
+        self.skip_whitespace();
+        self.expect("(")?;
+        self.skip_whitespace();
+        let mut arguments = vec![];
+        if !self.eat(")") {
+            loop {
+                arguments.push(self.argument(function, arguments.len())?);
+                self.skip_whitespace();
+                if self.eat(")") {
+                    break;
+                }
+                self.expect(",")?;
+                self.skip_whitespace();
+            }
+        }
+        let call = IntrinsicFunction {
+            source: self.source[start..self.position].to_string(),
+            function,
+            arguments,
+        };
+        self.check(&call).map_err(|message| IntrinsicFunctionError {
+            function: self.source.to_string(),
+            position: start,
+            message: format!("{}: {message}", function.name()),
+        })?;
+        Ok(call)
+    }
+
+    /// Checks the arity and the types of the literal arguments of a call.
+    fn check(&self, call: &IntrinsicFunction) -> Result<(), String> {
+        let signature = call.function.signature();
+        signature.check_arity(call.arguments.len())?;
+        for (index, argument) in call.arguments.iter().enumerate() {
+            let expected = signature.argument_type(index).unwrap_or(ArgumentType::Any);
+            if let Argument::Literal(value) = argument {
+                if !expected.matches(value) {
+                    return Err(format!("The argument {} must be {expected}, but it is {value}", index + 1));
+                }
+            }
+        }
+        match (call.function, call.arguments.as_slice()) {
+            (Function::Format, [Argument::Literal(Value::String(template)), values @ ..]) => {
+                let placeholders = count_placeholders(template)?;
+                if placeholders != values.len() {
+                    return Err(format!("The template has {placeholders} placeholders, but {} values were given", values.len()));
+                }
+            }
+            (Function::Format, _) => return Err("The template must be a string literal".to_string()),
+            (Function::Hash, [_, Argument::Literal(Value::String(algorithm))]) if !HASH_ALGORITHMS.contains(&algorithm.as_str()) => {
+                return Err(format!("Unknown hash algorithm '{algorithm}', expected one of {}", HASH_ALGORITHMS.join(", ")));
+            }
+            _ => {}
+        }
+        Ok(())
+    }
+
+    fn argument(&mut self, function: Function, index: usize) -> Result<Argument, IntrinsicFunctionError> {
+        let rest = self.rest();
+        if rest.starts_with('\'') {
+            let raw = self.string()?;
+            // The template of States.Format keeps its escape sequences, see Function::Format
+            let value = if function == Function::Format && index == 0 { raw } else { unescape(&raw) };
+            Ok(Argument::Literal(Value::String(value)))
+        } else if rest.starts_with('$') {
+            let (path, length) = JsonPath::parse_prefix(rest).map_err(|e| self.error(e.to_string()))?;
+            self.position += length;
+            Ok(Argument::Path(path))
+        } else if rest.starts_with("States.") {
+            self.call().map(Argument::Function)
+        } else if self.eat("null") {
+            Ok(Argument::Literal(Value::Null))
+        } else if self.eat("true") {
+            Ok(Argument::Literal(Value::Bool(true)))
+        } else if self.eat("false") {
+            Ok(Argument::Literal(Value::Bool(false)))
+        } else {
+            let length = rest.find(|c: char| !(c.is_ascii_digit() || matches!(c, '-' | '+' | '.' | 'e' | 'E'))).unwrap_or(rest.len());
+            let number = serde_json::Number::from_str(&rest[..length]).map_err(|_| self.error("Expected an argument"))?;
+            self.position += length;
+            Ok(Argument::Literal(Value::Number(number)))
+        }
+    }
+
+    /// Parses a string literal, returning its content with the escape sequences.
+    fn string(&mut self) -> Result<String, IntrinsicFunctionError> {
+        self.expect("'")?;
+        let mut raw = String::new();
+        let mut chars = self.rest().chars();
+        loop {
+            match chars.next() {
+                None => return Err(self.error("Unterminated string")),
+                Some('\\') => match chars.next() {
+                    Some(escaped) => {
+                        raw.push('\\');
+                        raw.push(escaped);
+                    }
+                    None => return Err(self.error("Unterminated string")),
+                },
+                Some('\'') => break,
+                Some(c) => raw.push(c),
+            }
+        }
+        self.position = self.source.len() - chars.as_str().len();
+        Ok(raw)
+    }
+}
+
+fn unescape(raw: &str) -> String {
+    let mut unescaped = String::with_capacity(raw.len());
+    let mut chars = raw.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' => unescaped.extend(chars.next()),
+            c => unescaped.push(c),
+        }
+    }
+    unescaped
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rstest::*;
+    use serde_json::json;
+
+    fn input() -> Value {
+        json!({
+            "firstName": "John",
+            "lastName": "Doe",
+            "someString": "{\"a\": 1}",
+            "someJson": { "a": 1 },
+            "inputArray": [1, 2, 3, 4, 5, 6, 7, 8, 9, 1, 2],
+            "lookingFor": 5,
+            "index": 2,
+            "input": "Data to encode",
+            "base64": "RGF0YSB0byBlbmNvZGU=",
+            "json1": { "a": { "a1": 1, "a2": 2 }, "b": 2 },
+            "json2": { "a": { "a3": 1, "a4": 2 }, "c": 3 },
+            "inputString": "This.is+a,test=string",
+            "splitter": ".+,="
+        })
+    }
+
+    #[rstest]
+    #[case::format("States.Format('Welcome to {} {}\\'s playlist.', $.firstName, $.lastName)", json!("Welcome to John Doe's playlist."))]
+    #[case::format_escaped_braces("States.Format('\\{\\} {} {}', 1, null)", json!("{} 1 null"))]
+    #[case::string_to_json("States.StringToJson($.someString)", json!({ "a": 1 }))]
+    #[case::json_to_string("States.JsonToString($.someJson)", json!("{\"a\":1}"))]
+    #[case::array("States.Array('Foo', 2020, $.someJson, null)", json!(["Foo", 2020, { "a": 1 }, null]))]
+    #[case::array_partition("States.ArrayPartition($.inputArray, 4)", json!([[1, 2, 3, 4], [5, 6, 7, 8], [9, 1, 2]]))]
+    #[case::array_contains("States.ArrayContains($.inputArray, $.lookingFor)", json!(true))]
+    #[case::array_range("States.ArrayRange(1, 9, 2)", json!([1, 3, 5, 7, 9]))]
+    #[case::array_range_descending("States.ArrayRange(5, 1, -2)", json!([5, 3, 1]))]
+    #[case::array_get_item("States.ArrayGetItem($.inputArray, $.index)", json!(3))]
+    #[case::array_length("States.ArrayLength($.inputArray)", json!(11))]
+    #[case::array_unique("States.ArrayUnique($.inputArray)", json!([1, 2, 3, 4, 5, 6, 7, 8, 9]))]
+    #[case::base64_encode("States.Base64Encode($.input)", json!("RGF0YSB0byBlbmNvZGU="))]
+    #[case::base64_decode("States.Base64Decode($.base64)", json!("Data to encode"))]
+    #[case::hash("States.Hash($.input, 'SHA-1')", json!("72a42d0f8593b8ce67954a60e19afdaa929600e5"))]
+    #[case::hash_md5("States.Hash('', 'MD5')", json!("d41d8cd98f00b204e9800998ecf8427e"))]
+    #[case::json_merge_shallow("States.JsonMerge($.json1, $.json2, false)", json!({ "a": { "a3": 1, "a4": 2 }, "b": 2, "c": 3 }))]
+    #[case::json_merge_deep("States.JsonMerge($.json1, $.json2, true)", json!({ "a": { "a1": 1, "a2": 2, "a3": 1, "a4": 2 }, "b": 2, "c": 3 }))]
+    #[case::math_random_seeded("States.MathRandom(1, 2, 1234)", json!(1))]
+    #[case::math_add("States.MathAdd($.index, -3)", json!(-1))]
+    #[case::string_split("States.StringSplit($.inputString, $.splitter)", json!(["This", "is", "a", "test", "string"]))]
+    #[case::nested("States.ArrayLength(States.StringSplit(States.Format('{}-{}', $.firstName, $.lastName), '-'))", json!(2))]
+    fn evaluate(#[case] function: &str, #[case] expected: Value) {
+        let function: IntrinsicFunction = function.parse().unwrap();
+
+        assert_eq!(function.evaluate(&input(), &Value::Null), Ok(expected));
+    }
+
+    #[rstest]
+    fn evaluate_uuid() {
+        let function: IntrinsicFunction = "States.UUID()".parse().unwrap();
+
+        let uuid = function.evaluate(&input(), &Value::Null).unwrap();
+
+        assert!(Uuid::parse_str(uuid.as_str().unwrap()).is_ok());
+    }
+
+    #[rstest]
+    #[case::wrong_type("States.ArrayLength($.input)")]
+    #[case::missing_path("States.ArrayLength($.missing)")]
+    #[case::invalid_json("States.StringToJson($.input)")]
+    #[case::index_out_of_bounds("States.ArrayGetItem($.inputArray, 20)")]
+    #[case::zero_chunk_size("States.ArrayPartition($.inputArray, 0)")]
+    #[case::too_many_items("States.ArrayRange(1, 2000, 1)")]
+    #[case::overflowing_range("States.ArrayRange(-9223372036854775808, 9223372036854775807, 1)")]
+    #[case::invalid_base64("States.Base64Decode($.input)")]
+    #[case::invalid_range("States.MathRandom(2, 1)")]
+    #[case::format_object("States.Format('{}', $.someJson)")]
+    fn evaluate_fails(#[case] function: &str) {
+        let function: IntrinsicFunction = function.parse().unwrap();
+
+        let error = function.evaluate(&input(), &Value::Null).unwrap_err();
+
+        assert_eq!(error.error, Some(ErrorName::StatesIntrinsicFailure));
+    }
+
+    #[rstest]
+    #[case::unknown_function("States.Unknown()")]
+    #[case::not_a_function("$.a")]
+    #[case::missing_parenthesis("States.UUID(")]
+    #[case::trailing_characters("States.UUID() ")]
+    #[case::too_many_arguments("States.UUID(1)")]
+    #[case::too_few_arguments("States.MathAdd(1)")]
+    #[case::literal_type("States.MathAdd('1', 2)")]
+    #[case::literal_array("States.ArrayLength('abc')")]
+    #[case::format_placeholders("States.Format('{} {}', 1)")]
+    #[case::format_path_template("States.Format($.template, 1)")]
+    #[case::format_unescaped_brace("States.Format('{', 1)")]
+    #[case::hash_algorithm("States.Hash('data', 'SHA-2')")]
+    #[case::unterminated_string("States.Base64Encode('abc)")]
+    #[case::invalid_path("States.ArrayLength($.)")]
+    fn parse_invalid(#[case] function: &str) {
+        assert!(function.parse::<IntrinsicFunction>().is_err(), "'{function}' should be invalid");
+    }
+}
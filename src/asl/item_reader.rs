@@ -0,0 +1,284 @@
+use std::fs;
+use std::path::{Component, Path, PathBuf};
+use serde_json::{json, Map, Value};
+use crate::asl::error_handling::ErrorName;
+use crate::asl::execution::ExecutionError;
+use crate::asl::states::map::{CsvHeaderLocation, InputType, ReaderConfig};
+
+/// Reads the items of a Map state from the source identified by the "Resource" field of its
+/// "ItemReader".
+///
+/// Readers are registered in [Resources](crate::asl::resources::Resources) and shared by every
+/// execution, so any state they hold must use interior mutability.
+///
+/// Returning an error fails the Map state with that error, which should usually be
+/// `States.ItemReaderFailed`.
+pub trait ItemReader: Send + Sync {
+    /// Reads the items from `resource`, given the rendered "Parameters" of the "ItemReader" and its
+    /// "ReaderConfig".
+    fn read(&self, resource: &str, parameters: &Value, config: &ReaderConfig) -> Result<Vec<Value>, ExecutionError>;
+}
+
+impl<F> ItemReader for F
+where
+    F: Fn(&str, &Value, &ReaderConfig) -> Result<Vec<Value>, ExecutionError> + Send + Sync,
+{
+    fn read(&self, resource: &str, parameters: &Value, config: &ReaderConfig) -> Result<Vec<Value>, ExecutionError> {
+        self(resource, parameters, config)
+    }
+}
+
+fn reader_failed(cause: impl Into<String>) -> ExecutionError {
+    ExecutionError::new(ErrorName::StatesItemReaderFailed, cause)
+}
+
+/// Parses the content of a file into items, according to the "InputType" of the "ReaderConfig".
+///
+/// - `CSV`: each row is an object keyed by the headers, with string values.
+/// - `JSON`: the content must be an array, whose elements are the items.
+/// - `JSONL`: each non-empty line is an item.
+pub fn parse_items(content: &str, config: &ReaderConfig) -> Result<Vec<Value>, ExecutionError> {
+    match config.input_type() {
+        Some(InputType::Csv) => parse_csv(content, config),
+        Some(InputType::Json) | None => match serde_json::from_str(content) {
+            Ok(Value::Array(items)) => Ok(items),
+            Ok(_) => Err(reader_failed("The JSON content must be an array")),
+            Err(e) => Err(reader_failed(format!("Invalid JSON content: {e}"))),
+        },
+        Some(InputType::Jsonl) => content.lines()
+            .enumerate()
+            .filter(|(_, line)| !line.trim().is_empty())
+            .map(|(index, line)| serde_json::from_str(line)
+                .map_err(|e| reader_failed(format!("Invalid JSON on line {}: {e}", index + 1))))
+            .collect(),
+        Some(input_type) => Err(reader_failed(format!("The input type {input_type:?} is not supported"))),
+    }
+}
+
+fn parse_csv(content: &str, config: &ReaderConfig) -> Result<Vec<Value>, ExecutionError> {
+    let mut rows = csv_rows(content)?.into_iter();
+    let headers = match (config.csv_header_location(), config.csv_headers()) {
+        (CsvHeaderLocation::Given, Some(headers)) => headers.to_vec(),
+        _ => rows.next().ok_or_else(|| reader_failed("The CSV content has no header row"))?,
+    };
+    rows.enumerate()
+        .map(|(index, row)| {
+            if row.len() != headers.len() {
+                return Err(reader_failed(format!(
+                    "The CSV row {} has {} fields, but there are {} headers", index + 1, row.len(), headers.len()
+                )));
+            }
+            Ok(Value::Object(headers.iter().cloned().zip(row.into_iter().map(Value::String)).collect::<Map<_, _>>()))
+        })
+        .collect()
+}
+
+/// Splits CSV content into rows of fields, following RFC 4180: fields may be quoted, and quotes
+/// are escaped by doubling them.
+fn csv_rows(content: &str) -> Result<Vec<Vec<String>>, ExecutionError> {
+    let mut rows = vec![];
+    let mut row = vec![];
+    let mut field = String::new();
+    let mut quoted = false;
+    let mut chars = content.chars().peekable();
+    while let Some(c) = chars.next() {
+        match (quoted, c) {
+            (true, '"') if chars.peek() == Some(&'"') => {
+                chars.next();
+                field.push('"');
+            }
+            (true, '"') => quoted = false,
+            (true, c) => field.push(c),
+            (false, '"') if field.is_empty() => quoted = true,
+            (false, ',') => row.push(std::mem::take(&mut field)),
+            (false, '\r') if chars.peek() == Some(&'\n') => {}
+            (false, '\n') => {
+                row.push(std::mem::take(&mut field));
+                rows.push(std::mem::take(&mut row));
+            }
+            (false, c) => field.push(c),
+        }
+    }
+    if quoted {
+        return Err(reader_failed("The CSV content has an unterminated quoted field"));
+    }
+    if !field.is_empty() || !row.is_empty() {
+        row.push(field);
+        rows.push(row);
+    }
+    Ok(rows)
+}
+
+/// An [ItemReader] reading from a local directory standing in for S3, so that Map states using an
+/// "ItemReader" can run offline.
+///
+/// Each bucket is a subdirectory of the root directory, and each object a file within it. The
+/// "Bucket" parameter selects the bucket and:
+/// - for resources ending with `listObjectsV2`, the items describe the files whose key starts
+///   with the "Prefix" parameter, if any;
+/// - for other resources, such as `s3:getObject`, the file named by the "Key" parameter is parsed
+///   according to the "ReaderConfig".
+pub struct LocalDirectoryReader {
+    root: PathBuf,
+}
+
+impl LocalDirectoryReader {
+    pub fn new(root: impl Into<PathBuf>) -> Self {
+        LocalDirectoryReader {
+            root: root.into(),
+        }
+    }
+
+    fn bucket(&self, parameters: &Value) -> Result<PathBuf, ExecutionError> {
+        let bucket = string_parameter(parameters, "Bucket").map_err(reader_failed)?;
+        Ok(self.root.join(relative_path(bucket).map_err(reader_failed)?))
+    }
+
+    fn list_objects(bucket: &Path, prefix: &str) -> Result<Vec<Value>, ExecutionError> {
+        let mut keys = vec![];
+        let mut directories = vec![bucket.to_path_buf()];
+        while let Some(directory) = directories.pop() {
+            let entries = fs::read_dir(&directory)
+                .map_err(|e| reader_failed(format!("Can't list '{}': {e}", directory.display())))?;
+            for entry in entries {
+                let path = entry.map_err(|e| reader_failed(e.to_string()))?.path();
+                if path.is_dir() {
+                    directories.push(path);
+                } else if let Ok(key) = path.strip_prefix(bucket) {
+                    let key = key.components()
+                        .map(|component| component.as_os_str().to_string_lossy())
+                        .collect::<Vec<_>>()
+                        .join("/");
+                    if key.starts_with(prefix) {
+                        let size = path.metadata().map(|metadata| metadata.len()).unwrap_or_default();
+                        keys.push((key, size));
+                    }
+                }
+            }
+        }
+        keys.sort();
+        Ok(keys.into_iter().map(|(key, size)| json!({ "Key": key, "Size": size })).collect())
+    }
+}
+
+impl ItemReader for LocalDirectoryReader {
+    fn read(&self, resource: &str, parameters: &Value, config: &ReaderConfig) -> Result<Vec<Value>, ExecutionError> {
+        let bucket = self.bucket(parameters)?;
+        if resource.ends_with("listObjectsV2") {
+            let prefix = parameters.get("Prefix").and_then(Value::as_str).unwrap_or_default();
+            return LocalDirectoryReader::list_objects(&bucket, prefix);
+        }
+        let key = string_parameter(parameters, "Key").map_err(reader_failed)?;
+        let path = bucket.join(relative_path(key).map_err(reader_failed)?);
+        let content = fs::read_to_string(&path)
+            .map_err(|e| reader_failed(format!("Can't read '{}': {e}", path.display())))?;
+        parse_items(&content, config)
+    }
+}
+
+pub(crate) fn string_parameter<'p>(parameters: &'p Value, name: &str) -> Result<&'p str, String> {
+    parameters.get(name)
+        .and_then(Value::as_str)
+        .ok_or_else(|| format!("The parameter \"{name}\" must be a string"))
+}
+
+/// Rejects paths that could escape the root directory.
+pub(crate) fn relative_path(path: &str) -> Result<&Path, String> {
+    let relative = Path::new(path);
+    if relative.components().all(|component| matches!(component, Component::Normal(_))) {
+        Ok(relative)
+    } else {
+        Err(format!("Invalid path '{path}'"))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rstest::*;
+    use anyhow::Result;
+    use crate::asl::temp_dir::TempDir;
+
+    /// A temporary directory with a `bucket` holding a few files.
+    struct Bucket {
+        root: TempDir,
+    }
+
+    impl Bucket {
+        fn new() -> Result<Self> {
+            let root = TempDir::new("asl-item-reader")?;
+            fs::create_dir_all(root.path().join("bucket/nested"))?;
+            fs::write(root.path().join("bucket/items.csv"), "name,comment\r\nfoo,\"a, \"\"quoted\"\" comment\"\nbar,\n")?;
+            fs::write(root.path().join("bucket/items.json"), r#"[{ "name": "foo" }, 2]"#)?;
+            fs::write(root.path().join("bucket/nested/items.jsonl"), "{ \"name\": \"foo\" }\n\n3\n")?;
+            Ok(Bucket { root })
+        }
+    }
+
+    fn config(config: Value) -> ReaderConfig {
+        serde_json::from_value(config).unwrap()
+    }
+
+    #[rstest]
+    #[case::csv_first_row(
+        "items.csv",
+        json!({ "InputType": "CSV" }),
+        vec![json!({ "name": "foo", "comment": "a, \"quoted\" comment" }), json!({ "name": "bar", "comment": "" })],
+    )]
+    #[case::csv_given_headers(
+        "items.csv",
+        json!({ "InputType": "CSV", "CSVHeaderLocation": "GIVEN", "CSVHeaders": ["a", "b"] }),
+        vec![json!({ "a": "name", "b": "comment" }), json!({ "a": "foo", "b": "a, \"quoted\" comment" }), json!({ "a": "bar", "b": "" })],
+    )]
+    #[case::json("items.json", json!({ "InputType": "JSON" }), vec![json!({ "name": "foo" }), json!(2)])]
+    #[case::jsonl("nested/items.jsonl", json!({ "InputType": "JSONL" }), vec![json!({ "name": "foo" }), json!(3)])]
+    fn read_object(#[case] key: &str, #[case] reader_config: Value, #[case] expected: Vec<Value>) -> Result<()> {
+        let bucket = Bucket::new()?;
+        let reader = LocalDirectoryReader::new(bucket.root.path());
+
+        let items = reader.read("arn:aws:states:::s3:getObject", &json!({ "Bucket": "bucket", "Key": key }), &config(reader_config))?;
+
+        assert_eq!(items, expected);
+        Ok(())
+    }
+
+    #[rstest]
+    fn list_objects() -> Result<()> {
+        let bucket = Bucket::new()?;
+        let reader = LocalDirectoryReader::new(bucket.root.path());
+
+        let items = reader.read("arn:aws:states:::s3:listObjectsV2", &json!({ "Bucket": "bucket", "Prefix": "items" }), &ReaderConfig::default())?;
+
+        let keys: Vec<&Value> = items.iter().map(|item| &item["Key"]).collect();
+        assert_eq!(keys, vec!["items.csv", "items.json"]);
+        Ok(())
+    }
+
+    #[rstest]
+    #[case::missing_file(json!({ "Bucket": "bucket", "Key": "missing.json" }), json!({ "InputType": "JSON" }))]
+    #[case::missing_key(json!({ "Bucket": "bucket" }), json!({ "InputType": "JSON" }))]
+    #[case::escaping_root(json!({ "Bucket": "bucket", "Key": "../bucket/items.json" }), json!({ "InputType": "JSON" }))]
+    #[case::not_an_array(json!({ "Bucket": "bucket", "Key": "nested/items.jsonl" }), json!({ "InputType": "JSON" }))]
+    #[case::csv_field_count(json!({ "Bucket": "bucket", "Key": "items.csv" }), json!({ "InputType": "CSV", "CSVHeaderLocation": "GIVEN", "CSVHeaders": ["a"] }))]
+    #[case::unsupported_type(json!({ "Bucket": "bucket", "Key": "items.json" }), json!({ "InputType": "PARQUET" }))]
+    fn read_fails(#[case] parameters: Value, #[case] reader_config: Value) -> Result<()> {
+        let bucket = Bucket::new()?;
+        let reader = LocalDirectoryReader::new(bucket.root.path());
+
+        let error = reader.read("arn:aws:states:::s3:getObject", &parameters, &config(reader_config)).unwrap_err();
+
+        assert_eq!(error.error, Some(ErrorName::StatesItemReaderFailed));
+        Ok(())
+    }
+
+    #[rstest]
+    #[case::missing_given_headers(json!({ "InputType": "CSV", "CSVHeaderLocation": "GIVEN" }))]
+    #[case::unknown_input_type(json!({ "InputType": "XML" }))]
+    #[case::max_items_and_path(json!({ "MaxItems": 1, "MaxItemsPath": "$.max" }))]
+    #[case::string_max_items(json!({ "MaxItems": "x" }))]
+    #[case::negative_max_items(json!({ "MaxItems": -1 }))]
+    #[case::zero_max_items(json!({ "MaxItems": 0 }))]
+    fn parse_invalid_reader_config(#[case] reader_config: Value) {
+        assert!(serde_json::from_value::<ReaderConfig>(reader_config).is_err());
+    }
+}
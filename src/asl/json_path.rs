@@ -0,0 +1,859 @@
+use std::cmp::Ordering;
+use std::fmt::{Debug, Display, Formatter};
+use std::str::FromStr;
+use serde::Deserialize;
+use serde_json::{Number, Value};
+use thiserror::Error;
+
+#[derive(Error, Debug, Clone, PartialEq, Eq)]
+pub enum JsonPathError {
+    #[error("Invalid JSONPath '{path}' at position {position}: {message}")]
+    Syntax {
+        path: String,
+        position: usize,
+        message: String,
+    },
+    #[error("'{path}' is not a Reference Path: {message}")]
+    NotAReferencePath {
+        path: String,
+        message: String,
+    },
+    #[error("Can't write to '{path}': {message}")]
+    Write {
+        path: String,
+        message: String,
+    },
+}
+
+/// Where a [JsonPath] starts selecting from.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Root {
+    /// `$`: the input of the state.
+    Input,
+    /// `$$`: the Context Object, see https://states-language.net/spec.html#contextobject
+    Context,
+    /// `@`: the node being tested by a filter expression.
+    Current,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Segment {
+    /// `.name` or `[...]`: selects among the children of the current nodes.
+    Child(Selector),
+    /// `..name` or `..[...]`: selects among the current nodes and all their descendants.
+    Descendant(Selector),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Selector {
+    /// `name`, `['name']` or `['a','b']`.
+    Names(Vec<String>),
+    /// `[0]`, `[-1]` or `[0,2]`. Negative indexes count from the end of the array.
+    Indexes(Vec<i64>),
+    /// `[start:end:step]`, with the same semantics as Python slices.
+    Slice {
+        start: Option<i64>,
+        end: Option<i64>,
+        step: Option<i64>,
+    },
+    /// `*` or `[*]`.
+    Wildcard,
+    /// `[(@.length-1)]`: the index computed from the length of the array.
+    FromEnd(i64),
+    /// `[?(...)]`: the children for which the expression holds.
+    Filter(Box<FilterExpression>),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum FilterExpression {
+    /// `@.name`: true if the path selects anything.
+    Exists(JsonPath),
+    Compare(Operand, ComparisonOperator, Operand),
+    Not(Box<FilterExpression>),
+    And(Box<FilterExpression>, Box<FilterExpression>),
+    Or(Box<FilterExpression>, Box<FilterExpression>),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Operand {
+    Path(JsonPath),
+    Literal(Value),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ComparisonOperator {
+    Equals,
+    NotEquals,
+    LessThan,
+    LessThanEquals,
+    GreaterThan,
+    GreaterThanEquals,
+}
+
+/// A parsed JSONPath expression, see https://states-language.net/spec.html#path
+///
+/// The supported syntax is the subset of the [Jayway JSONPath](https://github.com/json-path/JsonPath)
+/// implementation that is used by ASL:
+///
+/// - `$` for the input and `$$` for the Context Object.
+/// - Dot and bracket notation for fields: `$.a.b`, `$['a']['b']`, `$.input-foo-bar`.
+/// - Array indexes, negative ones counting from the end: `$.books[0]`, `$.books[-2]`.
+/// - Slices: `$.vals[3:]`, `$.vals[1:5:2]`.
+/// - Wildcards and deep scans: `$.a[*]`, `$..name`.
+/// - Filters: `$.books[?(@.price < 10 && @.isbn)]`.
+/// - Indexes relative to the length of the array: `$[(@.length-1)]`.
+#[derive(Clone, PartialEq, Eq)]
+pub struct JsonPath {
+    source: String,
+    root: Root,
+    segments: Vec<Segment>,
+}
+
+impl JsonPath {
+    pub fn root(&self) -> Root {
+        self.root
+    }
+
+    pub fn segments(&self) -> &[Segment] {
+        &self.segments
+    }
+
+    /// A path is definite if it can select at most one node, i.e. it only has names and indexes.
+    pub fn is_definite(&self) -> bool {
+        self.segments.iter().all(|segment| matches!(
+            segment,
+            Segment::Child(Selector::Names(names)) if names.len() == 1
+        ) || matches!(
+            segment,
+            Segment::Child(Selector::Indexes(indexes)) if indexes.len() == 1
+        ) || matches!(segment, Segment::Child(Selector::FromEnd(_))))
+    }
+
+    /// Selects every node matched by this path.
+    ///
+    /// `input` is the value of `$` and `context` is the value of `$$`.
+    pub fn select<'v>(&self, input: &'v Value, context: &'v Value) -> Vec<&'v Value> {
+        self.select_from(input, input, context)
+    }
+
+    /// Evaluates this path the way ASL does.
+    ///
+    /// A definite path evaluates to the single node it selects, or `None` if there's no such node.
+    /// Any other path evaluates to the array of the selected nodes, which might be empty.
+    pub fn evaluate(&self, input: &Value, context: &Value) -> Option<Value> {
+        let selected = self.select(input, context);
+        if self.is_definite() {
+            selected.first().map(|value| (*value).clone())
+        } else {
+            Some(Value::Array(selected.into_iter().cloned().collect()))
+        }
+    }
+
+    /// Selects relative to `current`, which is the value of `@`.
+    fn select_from<'v>(&self, current: &'v Value, input: &'v Value, context: &'v Value) -> Vec<&'v Value> {
+        let start = match self.root {
+            Root::Input => input,
+            Root::Context => context,
+            Root::Current => current,
+        };
+        self.segments.iter().fold(vec![start], |nodes, segment| {
+            let mut selected = vec![];
+            for node in nodes {
+                match segment {
+                    Segment::Child(selector) => selector.select(node, &mut selected, input, context),
+                    Segment::Descendant(selector) => {
+                        for descendant in descendants(node) {
+                            selector.select(descendant, &mut selected, input, context);
+                        }
+                    }
+                }
+            }
+            selected
+        })
+    }
+}
+
+impl Selector {
+    fn select<'v>(&self, node: &'v Value, selected: &mut Vec<&'v Value>, input: &'v Value, context: &'v Value) {
+        match (self, node) {
+            (Selector::Names(names), Value::Object(object)) => {
+                selected.extend(names.iter().filter_map(|name| object.get(name)));
+            }
+            (Selector::Indexes(indexes), Value::Array(array)) => {
+                selected.extend(indexes.iter().filter_map(|index| {
+                    let index = if *index < 0 { array.len() as i64 + index } else { *index };
+                    usize::try_from(index).ok().and_then(|index| array.get(index))
+                }));
+            }
+            (Selector::Slice { start, end, step }, Value::Array(array)) => {
+                selected.extend(slice_indexes(array.len(), *start, *end, *step).into_iter().map(|index| &array[index]));
+            }
+            (Selector::FromEnd(offset), Value::Array(array)) => {
+                let index = (array.len() as i64).checked_sub(*offset);
+                selected.extend(index.and_then(|index| usize::try_from(index).ok()).and_then(|index| array.get(index)));
+            }
+            (Selector::Wildcard, Value::Object(object)) => selected.extend(object.values()),
+            (Selector::Wildcard, Value::Array(array)) => selected.extend(array.iter()),
+            (Selector::Filter(filter), Value::Object(object)) => {
+                selected.extend(object.values().filter(|child| filter.test(child, input, context)));
+            }
+            (Selector::Filter(filter), Value::Array(array)) => {
+                selected.extend(array.iter().filter(|child| filter.test(child, input, context)));
+            }
+            _ => {}
+        }
+    }
+}
+
+fn descendants(node: &Value) -> Vec<&Value> {
+    let mut nodes = vec![node];
+    let mut index = 0;
+    while index < nodes.len() {
+        match nodes[index] {
+            Value::Object(object) => nodes.extend(object.values()),
+            Value::Array(array) => nodes.extend(array.iter()),
+            _ => {}
+        }
+        index += 1;
+    }
+    nodes
+}
+
+fn slice_indexes(len: usize, start: Option<i64>, end: Option<i64>, step: Option<i64>) -> Vec<usize> {
+    let len = len as i64;
+    let step = step.unwrap_or(1);
+    let normalize = |index: i64| if index < 0 { index + len } else { index };
+    if step > 0 {
+        let start = start.map(normalize).unwrap_or(0).clamp(0, len);
+        let end = end.map(normalize).unwrap_or(len).clamp(0, len);
+        (start..end).step_by(step as usize).map(|index| index as usize).collect()
+    } else if step < 0 {
+        let start = start.map(normalize).unwrap_or(len - 1).clamp(-1, len - 1);
+        let end = end.map(normalize).unwrap_or(-1).clamp(-1, len - 1);
+        let mut indexes = vec![];
+        let mut index = start;
+        while index > end {
+            indexes.push(index as usize);
+            index += step;
+        }
+        indexes
+    } else {
+        vec![]
+    }
+}
+
+impl FilterExpression {
+    fn test(&self, current: &Value, input: &Value, context: &Value) -> bool {
+        match self {
+            FilterExpression::Exists(path) => !path.select_from(current, input, context).is_empty(),
+            FilterExpression::Compare(left, operator, right) => {
+                match (left.resolve(current, input, context), right.resolve(current, input, context)) {
+                    (Some(left), Some(right)) => operator.compare(&left, &right),
+                    _ => false,
+                }
+            }
+            FilterExpression::Not(expression) => !expression.test(current, input, context),
+            FilterExpression::And(left, right) => left.test(current, input, context) && right.test(current, input, context),
+            FilterExpression::Or(left, right) => left.test(current, input, context) || right.test(current, input, context),
+        }
+    }
+}
+
+impl Operand {
+    fn resolve(&self, current: &Value, input: &Value, context: &Value) -> Option<Value> {
+        match self {
+            Operand::Literal(value) => Some(value.clone()),
+            Operand::Path(path) => path.select_from(current, input, context).first().map(|value| (*value).clone()),
+        }
+    }
+}
+
+impl ComparisonOperator {
+    fn compare(self, left: &Value, right: &Value) -> bool {
+        let ordering = match (left, right) {
+            (Value::Number(left), Value::Number(right)) => left.as_f64().partial_cmp(&right.as_f64()),
+            (Value::String(left), Value::String(right)) => Some(left.cmp(right)),
+            (left, right) if left == right => Some(Ordering::Equal),
+            _ => None,
+        };
+        match self {
+            ComparisonOperator::Equals => ordering == Some(Ordering::Equal),
+            ComparisonOperator::NotEquals => ordering != Some(Ordering::Equal),
+            ComparisonOperator::LessThan => ordering == Some(Ordering::Less),
+            ComparisonOperator::LessThanEquals => matches!(ordering, Some(Ordering::Less | Ordering::Equal)),
+            ComparisonOperator::GreaterThan => ordering == Some(Ordering::Greater),
+            ComparisonOperator::GreaterThanEquals => matches!(ordering, Some(Ordering::Greater | Ordering::Equal)),
+        }
+    }
+}
+
+impl JsonPath {
+    /// Parses the path at the start of `source`, which may be followed by anything that can't be
+    /// part of a path. Returns the path along with the length of its source.
+    pub(crate) fn parse_prefix(source: &str) -> Result<(JsonPath, usize), JsonPathError> {
+        let mut parser = Parser { source, position: 0 };
+        let json_path = parser.path(false)?;
+        Ok((json_path, parser.position))
+    }
+}
+
+impl FromStr for JsonPath {
+    type Err = JsonPathError;
+
+    fn from_str(path: &str) -> Result<Self, Self::Err> {
+        let mut parser = Parser { source: path, position: 0 };
+        let json_path = parser.path(false)?;
+        if parser.position < path.len() {
+            return Err(parser.error("Unexpected character"));
+        }
+        Ok(json_path)
+    }
+}
+
+impl<'de> Deserialize<'de> for JsonPath {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let path = String::deserialize(deserializer)?;
+        path.parse().map_err(serde::de::Error::custom)
+    }
+}
+
+impl Display for JsonPath {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.write_str(&self.source)
+    }
+}
+
+impl Debug for JsonPath {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "JsonPath({:?})", self.source)
+    }
+}
+
+/// A [JsonPath] that identifies a single node, see https://states-language.net/spec.html#ref-paths
+///
+/// The only allowed components are field names and array indexes: no wildcards, slices, filters,
+/// deep scans or unions. Fields like "ResultPath", "ItemsPath" or the "Variable" of a Choice rule
+/// must be Reference Paths.
+#[derive(Clone, PartialEq, Eq)]
+pub struct ReferencePath(JsonPath);
+
+impl ReferencePath {
+    pub fn path(&self) -> &JsonPath {
+        &self.0
+    }
+
+    /// Returns the node identified by this path, if there's one.
+    ///
+    /// `input` is the value of `$` and `context` is the value of `$$`.
+    pub fn get<'v>(&self, input: &'v Value, context: &'v Value) -> Option<&'v Value> {
+        self.0.select(input, context).into_iter().next()
+    }
+
+    /// Writes `value` at the node identified by this path, as done when applying a "ResultPath".
+    ///
+    /// Missing fields are created as objects along the way. Writing fails if the path goes through
+    /// a node that isn't an object (for field names) or an array (for indexes), if an index is out
+    /// of bounds, or if the path starts at the Context Object.
+    pub fn set(&self, target: &mut Value, value: Value) -> Result<(), JsonPathError> {
+        let error = |message: String| JsonPathError::Write {
+            path: self.0.source.clone(),
+            message,
+        };
+        if self.0.root != Root::Input {
+            return Err(error("only paths starting with '$' can be written".to_string()));
+        }
+        let mut node = target;
+        for segment in &self.0.segments {
+            node = match (segment, node) {
+                (Segment::Child(Selector::Names(names)), Value::Object(object)) => {
+                    object.entry(names[0].as_str()).or_insert_with(|| Value::Object(Default::default()))
+                }
+                (Segment::Child(Selector::Indexes(indexes)), Value::Array(array)) => {
+                    let index = if indexes[0] < 0 { array.len() as i64 + indexes[0] } else { indexes[0] };
+                    let len = array.len();
+                    usize::try_from(index).ok()
+                        .and_then(|index| array.get_mut(index))
+                        .ok_or_else(|| error(format!("index {} is out of bounds for an array of length {len}", indexes[0])))?
+                }
+                (_, node) => return Err(error(format!("can't go through {}", value_type(node)))),
+            };
+        }
+        *node = value;
+        Ok(())
+    }
+}
+
+fn value_type(value: &Value) -> &'static str {
+    match value {
+        Value::Null => "null",
+        Value::Bool(_) => "a boolean",
+        Value::Number(_) => "a number",
+        Value::String(_) => "a string",
+        Value::Array(_) => "an array",
+        Value::Object(_) => "an object",
+    }
+}
+
+impl TryFrom<JsonPath> for ReferencePath {
+    type Error = JsonPathError;
+
+    fn try_from(path: JsonPath) -> Result<Self, Self::Error> {
+        let error = |message: &str| JsonPathError::NotAReferencePath {
+            path: path.source.clone(),
+            message: message.to_string(),
+        };
+        for segment in &path.segments {
+            match segment {
+                Segment::Child(Selector::Names(names)) if names.len() == 1 => {}
+                Segment::Child(Selector::Indexes(indexes)) if indexes.len() == 1 => {}
+                Segment::Descendant(_) => return Err(error("deep scans are not allowed")),
+                Segment::Child(Selector::Names(_) | Selector::Indexes(_)) => return Err(error("unions are not allowed")),
+                Segment::Child(Selector::Slice { .. }) => return Err(error("slices are not allowed")),
+                Segment::Child(Selector::Wildcard) => return Err(error("wildcards are not allowed")),
+                Segment::Child(Selector::FromEnd(_)) => return Err(error("expressions are not allowed")),
+                Segment::Child(Selector::Filter(_)) => return Err(error("filters are not allowed")),
+            }
+        }
+        Ok(ReferencePath(path))
+    }
+}
+
+impl FromStr for ReferencePath {
+    type Err = JsonPathError;
+
+    fn from_str(path: &str) -> Result<Self, Self::Err> {
+        ReferencePath::try_from(path.parse::<JsonPath>()?)
+    }
+}
+
+impl<'de> Deserialize<'de> for ReferencePath {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let path = String::deserialize(deserializer)?;
+        path.parse().map_err(serde::de::Error::custom)
+    }
+}
+
+impl Display for ReferencePath {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        Display::fmt(&self.0, f)
+    }
+}
+
+impl Debug for ReferencePath {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "ReferencePath({:?})", self.0.source)
+    }
+}
+
+/// Characters that end a field name in dot notation.
+const NAME_TERMINATORS: &[char] = &['.', '[', ']', '(', ')', ',', '\'', '"', '=', '!', '<', '>', '&', '|', '*'];
+
+struct Parser<'s> {
+    source: &'s str,
+    position: usize,
+}
+
+impl<'s> Parser<'s> {
+    fn error(&self, message: impl Into<String>) -> JsonPathError {
+        JsonPathError::Syntax {
+            path: self.source.to_string(),
+            position: self.position,
+            message: message.into(),
+        }
+    }
+
+    fn rest(&self) -> &'s str {
+        &self.source[self.position..]
+    }
+
+    fn peek(&self) -> Option<char> {
+        self.rest().chars().next()
+    }
+
+    fn eat(&mut self, token: &str) -> bool {
+        if self.rest().starts_with(token) {
+            self.position += token.len();
+            true
+        } else {
+            false
+        }
+    }
+
+    fn expect(&mut self, token: &str) -> Result<(), JsonPathError> {
+        if self.eat(token) {
+            Ok(())
+        } else {
+            Err(self.error(format!("Expected '{token}'")))
+        }
+    }
+
+    fn skip_whitespace(&mut self) {
+        let trimmed = self.rest().trim_start();
+        self.position = self.source.len() - trimmed.len();
+    }
+
+    /// Parses a whole path. Paths relative to `@` are only allowed inside filters.
+    fn path(&mut self, in_filter: bool) -> Result<JsonPath, JsonPathError> {
+        let start = self.position;
+        let root = if self.eat("$$") {
+            Root::Context
+        } else if self.eat("$") {
+            Root::Input
+        } else if in_filter && self.eat("@") {
+            Root::Current
+        } else {
+            return Err(self.error("A path must start with '$'"));
+        };
+        let mut segments = vec![];
+        loop {
+            if self.eat("..") {
+                let selector = match self.peek() {
+                    Some('[') => self.bracket_selector()?,
+                    _ => self.dot_selector()?,
+                };
+                segments.push(Segment::Descendant(selector));
+            } else if self.eat(".") {
+                segments.push(Segment::Child(self.dot_selector()?));
+            } else if self.peek() == Some('[') {
+                segments.push(Segment::Child(self.bracket_selector()?));
+            } else {
+                break;
+            }
+        }
+        Ok(JsonPath {
+            source: self.source[start..self.position].to_string(),
+            root,
+            segments,
+        })
+    }
+
+    fn dot_selector(&mut self) -> Result<Selector, JsonPathError> {
+        if self.eat("*") {
+            return Ok(Selector::Wildcard);
+        }
+        let rest = self.rest();
+        let length = rest.find(|c: char| c.is_whitespace() || NAME_TERMINATORS.contains(&c)).unwrap_or(rest.len());
+        if length == 0 {
+            return Err(self.error("Expected a field name"));
+        }
+        self.position += length;
+        Ok(Selector::Names(vec![rest[..length].to_string()]))
+    }
+
+    fn bracket_selector(&mut self) -> Result<Selector, JsonPathError> {
+        self.expect("[")?;
+        self.skip_whitespace();
+        let selector = match self.peek() {
+            Some('*') => {
+                self.position += 1;
+                Selector::Wildcard
+            }
+            Some('\'' | '"') => {
+                let mut names = vec![self.quoted_string()?];
+                self.skip_whitespace();
+                while self.eat(",") {
+                    self.skip_whitespace();
+                    names.push(self.quoted_string()?);
+                    self.skip_whitespace();
+                }
+                Selector::Names(names)
+            }
+            Some('?') => {
+                self.position += 1;
+                self.expect("(")?;
+                let filter = self.filter_or()?;
+                self.skip_whitespace();
+                self.expect(")")?;
+                Selector::Filter(Box::new(filter))
+            }
+            Some('(') => {
+                self.position += 1;
+                self.skip_whitespace();
+                self.expect("@.length")?;
+                self.skip_whitespace();
+                let offset = if self.eat("-") {
+                    self.skip_whitespace();
+                    self.integer()?
+                } else {
+                    0
+                };
+                self.skip_whitespace();
+                self.expect(")")?;
+                Selector::FromEnd(offset)
+            }
+            _ => self.indexes_or_slice()?,
+        };
+        self.skip_whitespace();
+        self.expect("]")?;
+        Ok(selector)
+    }
+
+    fn indexes_or_slice(&mut self) -> Result<Selector, JsonPathError> {
+        let start = self.optional_integer()?;
+        self.skip_whitespace();
+        if self.eat(":") {
+            self.skip_whitespace();
+            let end = self.optional_integer()?;
+            self.skip_whitespace();
+            let step = if self.eat(":") {
+                self.skip_whitespace();
+                self.optional_integer()?
+            } else {
+                None
+            };
+            return Ok(Selector::Slice { start, end, step });
+        }
+        let mut indexes = vec![start.ok_or_else(|| self.error("Expected an index"))?];
+        while self.eat(",") {
+            self.skip_whitespace();
+            indexes.push(self.integer()?);
+            self.skip_whitespace();
+        }
+        Ok(Selector::Indexes(indexes))
+    }
+
+    fn optional_integer(&mut self) -> Result<Option<i64>, JsonPathError> {
+        match self.peek() {
+            Some(c) if c == '-' || c.is_ascii_digit() => self.integer().map(Some),
+            _ => Ok(None),
+        }
+    }
+
+    fn integer(&mut self) -> Result<i64, JsonPathError> {
+        let rest = self.rest();
+        let sign = usize::from(rest.starts_with('-'));
+        let length = sign + rest[sign..].find(|c: char| !c.is_ascii_digit()).unwrap_or(rest.len() - sign);
+        let integer = rest[..length].parse().map_err(|_| self.error("Expected an integer"))?;
+        self.position += length;
+        Ok(integer)
+    }
+
+    fn quoted_string(&mut self) -> Result<String, JsonPathError> {
+        let quote = self.peek().ok_or_else(|| self.error("Expected a quoted string"))?;
+        self.position += 1;
+        let mut string = String::new();
+        let mut chars = self.rest().chars();
+        loop {
+            match chars.next() {
+                None => return Err(self.error("Unterminated string")),
+                Some('\\') => match chars.next() {
+                    Some(escaped) => string.push(escaped),
+                    None => return Err(self.error("Unterminated string")),
+                },
+                Some(c) if c == quote => break,
+                Some(c) => string.push(c),
+            }
+        }
+        self.position = self.source.len() - chars.as_str().len();
+        Ok(string)
+    }
+
+    fn filter_or(&mut self) -> Result<FilterExpression, JsonPathError> {
+        let mut expression = self.filter_and()?;
+        loop {
+            self.skip_whitespace();
+            if !self.eat("||") {
+                return Ok(expression);
+            }
+            expression = FilterExpression::Or(Box::new(expression), Box::new(self.filter_and()?));
+        }
+    }
+
+    fn filter_and(&mut self) -> Result<FilterExpression, JsonPathError> {
+        let mut expression = self.filter_unary()?;
+        loop {
+            self.skip_whitespace();
+            if !self.eat("&&") {
+                return Ok(expression);
+            }
+            expression = FilterExpression::And(Box::new(expression), Box::new(self.filter_unary()?));
+        }
+    }
+
+    fn filter_unary(&mut self) -> Result<FilterExpression, JsonPathError> {
+        self.skip_whitespace();
+        if self.rest().starts_with('!') && !self.rest().starts_with("!=") {
+            self.position += 1;
+            return Ok(FilterExpression::Not(Box::new(self.filter_unary()?)));
+        }
+        if self.eat("(") {
+            let expression = self.filter_or()?;
+            self.skip_whitespace();
+            self.expect(")")?;
+            return Ok(expression);
+        }
+        let left = self.operand()?;
+        self.skip_whitespace();
+        let operator = [
+            ("==", ComparisonOperator::Equals),
+            ("!=", ComparisonOperator::NotEquals),
+            ("<=", ComparisonOperator::LessThanEquals),
+            (">=", ComparisonOperator::GreaterThanEquals),
+            ("<", ComparisonOperator::LessThan),
+            (">", ComparisonOperator::GreaterThan),
+        ].into_iter().find(|(token, _)| self.eat(token));
+        match (operator, left) {
+            (Some((_, operator)), left) => {
+                self.skip_whitespace();
+                Ok(FilterExpression::Compare(left, operator, self.operand()?))
+            }
+            (None, Operand::Path(path)) => Ok(FilterExpression::Exists(path)),
+            (None, Operand::Literal(_)) => Err(self.error("Expected a comparison operator")),
+        }
+    }
+
+    fn operand(&mut self) -> Result<Operand, JsonPathError> {
+        match self.peek() {
+            Some('$' | '@') => Ok(Operand::Path(self.path(true)?)),
+            Some('\'' | '"') => Ok(Operand::Literal(Value::String(self.quoted_string()?))),
+            _ if self.eat("true") => Ok(Operand::Literal(Value::Bool(true))),
+            _ if self.eat("false") => Ok(Operand::Literal(Value::Bool(false))),
+            _ if self.eat("null") => Ok(Operand::Literal(Value::Null)),
+            _ => {
+                let rest = self.rest();
+                let length = rest.find(|c: char| !(c.is_ascii_digit() || matches!(c, '-' | '+' | '.' | 'e' | 'E'))).unwrap_or(rest.len());
+                let number = Number::from_str(&rest[..length]).map_err(|_| self.error("Expected a path or a literal"))?;
+                self.position += length;
+                Ok(Operand::Literal(Value::Number(number)))
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rstest::*;
+    use serde_json::json;
+
+    fn input() -> Value {
+        json!({
+            "store": {
+                "books": [
+                    { "title": "Sayings of the Century", "price": 8.95, "category": "reference" },
+                    { "title": "Sword of Honour", "price": 12.99, "category": "fiction" },
+                    { "title": "Moby Dick", "price": 8.99, "category": "fiction", "isbn": "0-553-21311-3" },
+                    { "title": "The Lord of the Rings", "price": 22.99, "category": "fiction", "isbn": "0-395-19395-8" }
+                ],
+                "input-foo-bar": "hyphenated",
+                "special key": 1
+            },
+            "vals": [0, 1, 2, 3, 4, 5]
+        })
+    }
+
+    #[rstest]
+    #[case::root("$", Some(input()))]
+    #[case::field("$.store.input-foo-bar", Some(json!("hyphenated")))]
+    #[case::bracket_field("$['store']['special key']", Some(json!(1)))]
+    #[case::missing_field("$.store.missing", None)]
+    #[case::index("$.vals[1]", Some(json!(1)))]
+    #[case::negative_index("$.vals[-2]", Some(json!(4)))]
+    #[case::index_out_of_bounds("$.vals[10]", None)]
+    #[case::length_expression("$[(@.length-1)]", None)]
+    #[case::length_expression_on_array("$.vals[(@.length-1)]", Some(json!(5)))]
+    #[case::length_expression_overflow("$.vals[(@.length--9223372036854775807)]", None)]
+    #[case::slice_open_end("$.vals[3:]", Some(json!([3, 4, 5])))]
+    #[case::slice_open_start("$.vals[:2]", Some(json!([0, 1])))]
+    #[case::slice_negative("$.vals[-2:]", Some(json!([4, 5])))]
+    #[case::slice_step("$.vals[::2]", Some(json!([0, 2, 4])))]
+    #[case::slice_reverse("$.vals[::-2]", Some(json!([5, 3, 1])))]
+    #[case::union("$.vals[0,2]", Some(json!([0, 2])))]
+    #[case::wildcard("$.store.books[*].price", Some(json!([8.95, 12.99, 8.99, 22.99])))]
+    #[case::deep_scan("$..isbn", Some(json!(["0-553-21311-3", "0-395-19395-8"])))]
+    #[case::filter_exists("$.store.books[?(@.isbn)].title", Some(json!(["Moby Dick", "The Lord of the Rings"])))]
+    #[case::filter_compare("$.store.books[?(@.price < 10)].title", Some(json!(["Sayings of the Century", "Moby Dick"])))]
+    #[case::filter_logic("$.store.books[?(@.category == 'fiction' && !(@.price > 20))].title", Some(json!(["Sword of Honour", "Moby Dick"])))]
+    #[case::filter_root("$.vals[?(@ > $.vals[3] || @ == 0)]", Some(json!([0, 4, 5])))]
+    #[case::filter_nothing("$.store.books[?(@.price > 100)]", Some(json!([])))]
+    fn evaluate(#[case] path: &str, #[case] expected: Option<Value>) {
+        let path: JsonPath = path.parse().unwrap();
+
+        assert_eq!(path.evaluate(&input(), &Value::Null), expected);
+    }
+
+    #[rstest]
+    fn evaluate_context() {
+        let path: JsonPath = "$$.Execution.Id".parse().unwrap();
+        let context = json!({ "Execution": { "Id": "execution-id" } });
+
+        assert_eq!(path.evaluate(&input(), &context), Some(json!("execution-id")));
+    }
+
+    #[rstest]
+    #[case(".guid")]
+    #[case("..guid")]
+    #[case("()")]
+    #[case("$...")]
+    #[case("$.")]
+    #[case("bug$.library.movies")]
+    #[case("$.a[")]
+    #[case("$.a['b]")]
+    #[case("$.a[?(@.b ==)]")]
+    #[case("$.a[b]")]
+    #[case("@.a")]
+    #[case("")]
+    fn parse_invalid(#[case] path: &str) {
+        assert!(path.parse::<JsonPath>().is_err(), "'{path}' should be invalid");
+    }
+
+    #[rstest]
+    #[case::root("$", json!({ "a": 1 }), json!("result"))]
+    #[case::new_field("$.b", json!({ "a": 1 }), json!({ "a": 1, "b": "result" }))]
+    #[case::existing_field("$.a", json!({ "a": 1 }), json!({ "a": "result" }))]
+    #[case::intermediate_objects("$.b.c['d']", json!({ "a": 1 }), json!({ "a": 1, "b": { "c": { "d": "result" } } }))]
+    #[case::index("$.a[1].b", json!({ "a": [{}, {}] }), json!({ "a": [{}, { "b": "result" }] }))]
+    #[case::negative_index("$.a[-1]", json!({ "a": [1, 2] }), json!({ "a": [1, "result"] }))]
+    fn reference_path_set(#[case] path: &str, #[case] mut target: Value, #[case] expected: Value) {
+        let path: ReferencePath = path.parse().unwrap();
+
+        path.set(&mut target, json!("result")).unwrap();
+
+        assert_eq!(target, expected);
+    }
+
+    #[rstest]
+    #[case::through_string("$.a.b", json!({ "a": "string" }))]
+    #[case::through_null("$.a.b", json!({ "a": null }))]
+    #[case::index_on_object("$.a[0]", json!({ "a": {} }))]
+    #[case::index_out_of_bounds("$.a[2]", json!({ "a": [1, 2] }))]
+    #[case::context("$$.Execution", json!({}))]
+    fn reference_path_set_fails(#[case] path: &str, #[case] mut target: Value) {
+        let path: ReferencePath = path.parse().unwrap();
+
+        assert!(matches!(path.set(&mut target, json!("result")), Err(JsonPathError::Write { .. })));
+    }
+
+    #[rstest]
+    #[case("$..a")]
+    #[case("$.a[0,1]")]
+    #[case("$['a','b']")]
+    #[case("$.a[1:]")]
+    #[case("$.a[*]")]
+    #[case("$.*")]
+    #[case("$.a[?(@.b)]")]
+    #[case("$[(@.length-1)]")]
+    fn parse_not_a_reference_path(#[case] path: &str) {
+        assert!(matches!(path.parse::<ReferencePath>(), Err(JsonPathError::NotAReferencePath { .. })));
+    }
+
+    #[rstest]
+    fn reference_path_get() {
+        let path: ReferencePath = "$.store.books[-1].title".parse().unwrap();
+
+        assert_eq!(path.get(&input(), &Value::Null), Some(&json!("The Lord of the Rings")));
+    }
+
+    #[rstest]
+    #[case("$", true)]
+    #[case("$.a.b[0]", true)]
+    #[case("$[(@.length-1)].bar", true)]
+    #[case("$.a[0,1]", false)]
+    #[case("$.a[1:]", false)]
+    #[case("$..a", false)]
+    #[case("$.a[*]", false)]
+    #[case("$.a[?(@.b)]", false)]
+    fn definite(#[case] path: &str, #[case] expected: bool) {
+        assert_eq!(path.parse::<JsonPath>().unwrap().is_definite(), expected);
+    }
+}
@@ -2,4 +2,14 @@ pub mod state_machine;
 pub mod execution;
 pub mod states;
 pub mod types;
+pub mod json_path;
+pub mod payload_template;
+pub mod intrinsic_functions;
+pub mod item_reader;
+pub mod result_writer;
+mod validation;
+mod blocking;
+#[cfg(test)]
+mod temp_dir;
 pub mod error_handling;
+pub mod resources;
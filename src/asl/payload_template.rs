@@ -0,0 +1,218 @@
+use std::collections::BTreeMap;
+use serde::Deserialize;
+use serde_json::{Map, Value};
+use thiserror::Error;
+use crate::asl::error_handling::ErrorName;
+use crate::asl::execution::ExecutionError;
+use crate::asl::intrinsic_functions::{IntrinsicFunction, IntrinsicFunctionError};
+use crate::asl::json_path::{JsonPath, JsonPathError, Root};
+
+#[derive(Error, Debug, Clone, PartialEq, Eq)]
+pub enum PayloadTemplateError {
+    #[error("The field '{0}' is defined more than once after removing the '.$' suffix")]
+    DuplicateField(String),
+    #[error("The value of the field '{0}' must be a string")]
+    NotAString(String),
+    #[error("The value of the field '{field}' must be a Path or an Intrinsic Function, but it is '{value}'")]
+    InvalidValue {
+        field: String,
+        value: String,
+    },
+    #[error("Invalid value for the field '{field}': {source}")]
+    InvalidPath {
+        field: String,
+        source: JsonPathError,
+    },
+    #[error("Invalid value for the field '{field}': {source}")]
+    InvalidIntrinsicFunction {
+        field: String,
+        source: IntrinsicFunctionError,
+    },
+}
+
+/// A Payload Template, used by fields such as "Parameters" and "ResultSelector".
+///
+/// See https://states-language.net/spec.html#payload-template
+///
+/// The value of a field whose name ends with ".$" is resolved when the template is rendered: it
+/// is either a Path over the input, a Path over the Context Object (starting with "$$") or an
+/// Intrinsic Function call. Every other field is copied as is.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PayloadTemplate {
+    /// An object, keyed by the field names without their ".$" suffix.
+    Object(BTreeMap<String, PayloadTemplate>),
+    Array(Vec<PayloadTemplate>),
+    /// A value copied as is to the rendered payload.
+    Value(Value),
+    /// A Path over the input.
+    Path(JsonPath),
+    /// A Path over the Context Object.
+    ContextPath(JsonPath),
+    /// An Intrinsic Function call, such as `States.Format('{}', $.name)`.
+    IntrinsicFunction(IntrinsicFunction),
+}
+
+impl PayloadTemplate {
+    /// Renders the template, resolving the dynamic fields against the input and the Context Object.
+    ///
+    /// Fails with `States.ParameterPathFailure` if a path doesn't match the input and with
+    /// `States.IntrinsicFailure` if an Intrinsic Function can't be evaluated.
+    pub fn render(&self, input: &Value, context: &Value) -> Result<Value, ExecutionError> {
+        self.render_field("$", input, context)
+    }
+
+    fn render_field(&self, field: &str, input: &Value, context: &Value) -> Result<Value, ExecutionError> {
+        match self {
+            PayloadTemplate::Object(fields) => {
+                let mut object = Map::new();
+                for (name, template) in fields {
+                    object.insert(name.clone(), template.render_field(name, input, context)?);
+                }
+                Ok(Value::Object(object))
+            }
+            PayloadTemplate::Array(items) => items.iter()
+                .map(|item| item.render_field(field, input, context))
+                .collect::<Result<_, _>>()
+                .map(Value::Array),
+            PayloadTemplate::Value(value) => Ok(value.clone()),
+            PayloadTemplate::Path(path) | PayloadTemplate::ContextPath(path) => {
+                path.evaluate(input, context).ok_or_else(|| {
+                    let target = if path.root() == Root::Context { "the Context Object" } else { "the input" };
+                    ExecutionError::new(
+                        ErrorName::StatesParameterPathFailure,
+                        format!("The JSONPath '{path}' specified for the field '{field}.$' could not be found in {target}"),
+                    )
+                })
+            }
+            PayloadTemplate::IntrinsicFunction(function) => function.evaluate(input, context),
+        }
+    }
+
+    fn dynamic_field(field: &str, value: Value) -> Result<PayloadTemplate, PayloadTemplateError> {
+        let Value::String(value) = value else {
+            return Err(PayloadTemplateError::NotAString(format!("{field}.$")));
+        };
+        let invalid_path = |source| PayloadTemplateError::InvalidPath {
+            field: format!("{field}.$"),
+            source,
+        };
+        if value.starts_with("$$") {
+            value.parse().map(PayloadTemplate::ContextPath).map_err(invalid_path)
+        } else if value.starts_with('$') {
+            value.parse().map(PayloadTemplate::Path).map_err(invalid_path)
+        } else if value.starts_with("States.") {
+            value.parse().map(PayloadTemplate::IntrinsicFunction).map_err(|source| PayloadTemplateError::InvalidIntrinsicFunction {
+                field: format!("{field}.$"),
+                source,
+            })
+        } else {
+            Err(PayloadTemplateError::InvalidValue {
+                field: format!("{field}.$"),
+                value,
+            })
+        }
+    }
+}
+
+impl TryFrom<Value> for PayloadTemplate {
+    type Error = PayloadTemplateError;
+
+    fn try_from(value: Value) -> Result<Self, Self::Error> {
+        match value {
+            Value::Object(object) => {
+                let mut fields = BTreeMap::new();
+                for (name, value) in object {
+                    let (name, template) = match name.strip_suffix(".$") {
+                        Some(name) => (name.to_string(), PayloadTemplate::dynamic_field(name, value)?),
+                        None => (name, PayloadTemplate::try_from(value)?),
+                    };
+                    if fields.contains_key(&name) {
+                        return Err(PayloadTemplateError::DuplicateField(name));
+                    }
+                    fields.insert(name, template);
+                }
+                Ok(PayloadTemplate::Object(fields))
+            }
+            Value::Array(items) => items.into_iter()
+                .map(PayloadTemplate::try_from)
+                .collect::<Result<_, _>>()
+                .map(PayloadTemplate::Array),
+            value => Ok(PayloadTemplate::Value(value)),
+        }
+    }
+}
+
+impl<'de> Deserialize<'de> for PayloadTemplate {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let value = Value::deserialize(deserializer)?;
+        PayloadTemplate::try_from(value).map_err(serde::de::Error::custom)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rstest::*;
+    use serde_json::json;
+
+    #[rstest]
+    fn render() {
+        let template = PayloadTemplate::try_from(json!({
+            "flagged": true,
+            "parts": {
+                "first.$": "$.vals[0]",
+                "last3.$": "$.vals[3:]"
+            },
+            "list": [{ "execution.$": "$$.Execution.Id" }, null],
+            "whole.$": "$",
+            "length.$": "States.ArrayLength($.vals)"
+        })).unwrap();
+        let input = json!({ "vals": [0, 1, 2, 3, 4, 5] });
+        let context = json!({ "Execution": { "Id": "execution-id" } });
+
+        let rendered = template.render(&input, &context);
+
+        assert_eq!(rendered, Ok(json!({
+            "flagged": true,
+            "parts": {
+                "first": 0,
+                "last3": [3, 4, 5]
+            },
+            "list": [{ "execution": "execution-id" }, null],
+            "whole": input,
+            "length": 6
+        })));
+    }
+
+    #[rstest]
+    #[case::input("$.missing", "The JSONPath '$.missing' specified for the field 'field.$' could not be found in the input")]
+    #[case::context("$$.Missing", "The JSONPath '$$.Missing' specified for the field 'field.$' could not be found in the Context Object")]
+    fn render_missing_path(#[case] path: &str, #[case] cause: &str) {
+        let template = PayloadTemplate::try_from(json!({ "nested": { "field.$": path } })).unwrap();
+
+        let rendered = template.render(&json!({}), &json!({}));
+
+        assert_eq!(rendered, Err(ExecutionError::new(ErrorName::StatesParameterPathFailure, cause)));
+    }
+
+    #[rstest]
+    #[case::path("$.a", PayloadTemplate::Path("$.a".parse().unwrap()))]
+    #[case::context_path("$$.Execution.Id", PayloadTemplate::ContextPath("$$.Execution.Id".parse().unwrap()))]
+    #[case::intrinsic_function("States.UUID()", PayloadTemplate::IntrinsicFunction("States.UUID()".parse().unwrap()))]
+    fn parse_dynamic_field(#[case] value: &str, #[case] expected: PayloadTemplate) {
+        let template = PayloadTemplate::try_from(json!({ "field.$": value })).unwrap();
+
+        assert_eq!(template, PayloadTemplate::Object(BTreeMap::from([("field".to_string(), expected)])));
+    }
+
+    #[rstest]
+    #[case::not_a_path(json!({ "lorem.$": "ipsum" }))]
+    #[case::invalid_path(json!({ "lorem.$": "$." }))]
+    #[case::not_a_string(json!({ "lorem.$": 1 }))]
+    #[case::invalid_intrinsic_function(json!({ "lorem.$": "States.MathAdd(1)" }))]
+    #[case::duplicate_field(json!({ "dynamic": { "conflict": "value", "conflict.$": "$.value" } }))]
+    #[case::nested_in_array(json!([{ "lorem.$": "ipsum" }]))]
+    fn parse_invalid(#[case] value: Value) {
+        assert!(PayloadTemplate::try_from(value).is_err());
+    }
+}
@@ -0,0 +1,225 @@
+use std::collections::HashMap;
+use std::future::Future;
+use std::sync::Arc;
+use futures::future::BoxFuture;
+use serde_json::Value;
+use crate::asl::execution::ExecutionError;
+use crate::asl::item_reader::ItemReader;
+use crate::asl::result_writer::ResultWriter;
+
+/// Executes the work identified by the "Resource" field of a Task state.
+///
+/// Handlers are shared by every execution of a [StateMachine](crate::asl::state_machine::StateMachine),
+/// so any state they hold must use interior mutability.
+///
+/// Returning an error fails the Task with that error, which is then matched against the "Retry" and
+/// "Catch" fields of the state.
+pub trait ResourceHandler: Send + Sync {
+    /// Runs the task identified by `resource` with the given input, returning its result.
+    fn invoke(&self, resource: &str, input: &Value) -> Result<Value, ExecutionError>;
+}
+
+impl<F> ResourceHandler for F
+where
+    F: Fn(&str, &Value) -> Result<Value, ExecutionError> + Send + Sync,
+{
+    fn invoke(&self, resource: &str, input: &Value) -> Result<Value, ExecutionError> {
+        self(resource, input)
+    }
+}
+
+/// The async counterpart of [ResourceHandler], for tasks doing I/O such as HTTP calls, queues or
+/// databases.
+///
+/// Async handlers don't depend on a specific runtime. However, if the returned futures need a
+/// tokio reactor, the state machine must be executed with `Execution::run_async`, which requires
+/// the `tokio` feature.
+pub trait AsyncResourceHandler: Send + Sync {
+    /// Runs the task identified by `resource` with the given input, returning its result.
+    fn invoke<'a>(&'a self, resource: &'a str, input: &'a Value) -> BoxFuture<'a, Result<Value, ExecutionError>>;
+}
+
+impl<F, Fut> AsyncResourceHandler for F
+where
+    F: Fn(&str, &Value) -> Fut + Send + Sync,
+    Fut: Future<Output = Result<Value, ExecutionError>> + Send + 'static,
+{
+    fn invoke<'a>(&'a self, resource: &'a str, input: &'a Value) -> BoxFuture<'a, Result<Value, ExecutionError>> {
+        Box::pin(self(resource, input))
+    }
+}
+
+/// A handler registered in [Resources].
+pub enum Handler {
+    Blocking(Arc<dyn ResourceHandler>),
+    Async(Box<dyn AsyncResourceHandler>),
+}
+
+impl Handler {
+    /// Runs the task identified by `resource`.
+    ///
+    /// Blocking handlers run to completion on the current thread before the returned future is
+    /// polled for the first time.
+    pub async fn invoke(&self, resource: &str, input: &Value) -> Result<Value, ExecutionError> {
+        match self {
+            Handler::Blocking(handler) => handler.invoke(resource, input),
+            Handler::Async(handler) => handler.invoke(resource, input).await,
+        }
+    }
+}
+
+/// A pattern matched against the "Resource" of a Task state.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ResourcePattern {
+    /// Matches resources starting with the given string, e.g. `arn:aws:lambda:`.
+    Prefix(String),
+    /// Matches resources against a pattern where `*` matches zero or more characters, e.g.
+    /// `arn:aws:lambda:*:*:function:*`. Useful to match any region, account or alias of an ARN.
+    Wildcard(String),
+}
+
+impl ResourcePattern {
+    pub fn matches(&self, resource: &str) -> bool {
+        match self {
+            ResourcePattern::Prefix(prefix) => resource.starts_with(prefix.as_str()),
+            ResourcePattern::Wildcard(pattern) => wildcard_matches(pattern, resource),
+        }
+    }
+}
+
+fn wildcard_matches(pattern: &str, value: &str) -> bool {
+    let mut parts = pattern.split('*');
+    // There's always at least one part, even for an empty pattern.
+    let first = parts.next().unwrap_or_default();
+    let Some(mut remaining) = value.strip_prefix(first) else {
+        return false;
+    };
+    let parts: Vec<&str> = parts.collect();
+    let Some((last, middle)) = parts.split_last() else {
+        // No wildcard at all: the whole value must match.
+        return remaining.is_empty();
+    };
+    for part in middle {
+        match remaining.find(part) {
+            Some(index) => remaining = &remaining[index + part.len()..],
+            None => return false,
+        }
+    }
+    remaining.ends_with(last)
+}
+
+/// The registry of [ResourceHandler]s available to the Task states of a state machine, and of the
+/// [ItemReader]s and [ResultWriter]s available to its Map states.
+///
+/// A resource is looked up first by its exact name, then against the registered patterns in the
+/// order they were registered.
+#[derive(Default)]
+pub struct Resources {
+    exact: HashMap<String, Handler>,
+    patterns: Vec<(ResourcePattern, Handler)>,
+    item_readers: Vec<(ResourcePattern, Box<dyn ItemReader>)>,
+    result_writers: Vec<(ResourcePattern, Box<dyn ResultWriter>)>,
+}
+
+impl Resources {
+    pub fn new() -> Self {
+        Resources::default()
+    }
+
+    /// Registers a handler for the resource with exactly the given name.
+    pub fn register(&mut self, resource: impl Into<String>, handler: impl ResourceHandler + 'static) -> &mut Self {
+        self.exact.insert(resource.into(), Handler::Blocking(Arc::new(handler)));
+        self
+    }
+
+    /// Registers a handler for every resource matching the given pattern.
+    pub fn register_pattern(&mut self, pattern: ResourcePattern, handler: impl ResourceHandler + 'static) -> &mut Self {
+        self.patterns.push((pattern, Handler::Blocking(Arc::new(handler))));
+        self
+    }
+
+    /// Registers an async handler for the resource with exactly the given name.
+    pub fn register_async(&mut self, resource: impl Into<String>, handler: impl AsyncResourceHandler + 'static) -> &mut Self {
+        self.exact.insert(resource.into(), Handler::Async(Box::new(handler)));
+        self
+    }
+
+    /// Registers an async handler for every resource matching the given pattern.
+    pub fn register_async_pattern(&mut self, pattern: ResourcePattern, handler: impl AsyncResourceHandler + 'static) -> &mut Self {
+        self.patterns.push((pattern, Handler::Async(Box::new(handler))));
+        self
+    }
+
+    /// Registers an item reader for every "ItemReader" resource matching the given pattern.
+    pub fn register_item_reader(&mut self, pattern: ResourcePattern, reader: impl ItemReader + 'static) -> &mut Self {
+        self.item_readers.push((pattern, Box::new(reader)));
+        self
+    }
+
+    pub fn item_reader(&self, resource: &str) -> Option<&dyn ItemReader> {
+        self.item_readers.iter()
+            .find(|(pattern, _)| pattern.matches(resource))
+            .map(|(_, reader)| reader.as_ref())
+    }
+
+    /// Registers a result writer for every "ResultWriter" resource matching the given pattern.
+    pub fn register_result_writer(&mut self, pattern: ResourcePattern, writer: impl ResultWriter + 'static) -> &mut Self {
+        self.result_writers.push((pattern, Box::new(writer)));
+        self
+    }
+
+    pub fn result_writer(&self, resource: &str) -> Option<&dyn ResultWriter> {
+        self.result_writers.iter()
+            .find(|(pattern, _)| pattern.matches(resource))
+            .map(|(_, writer)| writer.as_ref())
+    }
+
+    pub fn get(&self, resource: &str) -> Option<&Handler> {
+        self.exact.get(resource)
+            .or_else(|| self.patterns.iter()
+                .find(|(pattern, _)| pattern.matches(resource))
+                .map(|(_, handler)| handler))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use futures::executor::block_on;
+    use rstest::*;
+    use serde_json::json;
+
+    fn handler(name: &'static str) -> impl ResourceHandler {
+        move |_: &str, _: &Value| Ok(json!(name))
+    }
+
+    #[rstest]
+    #[case::exact("arn:aws:lambda:us-east-1:123456789012:function:exact", Some("exact"))]
+    #[case::prefix("arn:aws:states:::sqs:sendMessage", Some("prefix"))]
+    #[case::wildcard("arn:aws:lambda:eu-west-1:123456789012:function:other:alias", Some("wildcard"))]
+    #[case::wildcard_no_match("arn:aws:lambda:eu-west-1:123456789012:layer:other", None)]
+    #[case::unknown("unknown", None)]
+    fn lookup(#[case] resource: &str, #[case] expected: Option<&str>) {
+        let mut resources = Resources::new();
+        resources
+            .register_pattern(ResourcePattern::Wildcard("arn:aws:lambda:*:*:function:*".to_string()), handler("wildcard"))
+            .register("arn:aws:lambda:us-east-1:123456789012:function:exact", handler("exact"))
+            .register_async_pattern(ResourcePattern::Prefix("arn:aws:states:::".to_string()), |_: &str, _: &Value| async { Ok(json!("prefix")) });
+
+        let result = resources.get(resource).map(|handler| block_on(handler.invoke(resource, &Value::Null)));
+
+        assert_eq!(result, expected.map(|name| Ok(json!(name))));
+    }
+
+    #[rstest]
+    #[case("a*c", "abc", true)]
+    #[case("a*c", "ac", true)]
+    #[case("a*c", "abd", false)]
+    #[case("*", "", true)]
+    #[case("a*b*c", "aXbYc", true)]
+    #[case("a*b*c", "aXcYb", false)]
+    #[case("abc", "abcd", false)]
+    fn wildcard(#[case] pattern: &str, #[case] value: &str, #[case] expected: bool) {
+        assert_eq!(wildcard_matches(pattern, value), expected);
+    }
+}
@@ -0,0 +1,161 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+use serde_json::{json, Value};
+use crate::asl::error_handling::ErrorName;
+use crate::asl::execution::ExecutionError;
+use crate::asl::item_reader::{relative_path, string_parameter};
+
+/// The results of the iterations of a Map state, grouped by status.
+///
+/// Each result describes an iteration with its "Input", its "Status" and either its "Output" or
+/// its "Error" and "Cause". Like in Step Functions, the input and output are serialized as JSON
+/// strings.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct MapRunResults {
+    /// Identifies the run of the Map state, so that the results of different runs don't collide.
+    pub map_run_id: String,
+    pub succeeded: Vec<Value>,
+    pub failed: Vec<Value>,
+    /// The iterations that didn't complete because the Map state failed before.
+    pub pending: Vec<Value>,
+}
+
+/// Writes the results of a Map state to the destination identified by the "Resource" field of its
+/// "ResultWriter".
+///
+/// Writers are registered in [Resources](crate::asl::resources::Resources) and shared by every
+/// execution, so any state they hold must use interior mutability.
+///
+/// The returned value describes where the results were written, and becomes the
+/// "ResultWriterDetails" of the Map state output. Returning an error fails the Map state with that
+/// error, which should usually be `States.ResultWriterFailed`.
+pub trait ResultWriter: Send + Sync {
+    /// Writes `results` to `resource`, given the rendered "Parameters" of the "ResultWriter".
+    fn write(&self, resource: &str, parameters: &Value, results: &MapRunResults) -> Result<Value, ExecutionError>;
+}
+
+impl<F> ResultWriter for F
+where
+    F: Fn(&str, &Value, &MapRunResults) -> Result<Value, ExecutionError> + Send + Sync,
+{
+    fn write(&self, resource: &str, parameters: &Value, results: &MapRunResults) -> Result<Value, ExecutionError> {
+        self(resource, parameters, results)
+    }
+}
+
+fn writer_failed(cause: impl Into<String>) -> ExecutionError {
+    ExecutionError::new(ErrorName::StatesResultWriterFailed, cause)
+}
+
+/// A [ResultWriter] writing to a local directory standing in for S3, laid out like the results
+/// Step Functions writes.
+///
+/// Each bucket is a subdirectory of the root directory, selected by the "Bucket" parameter. The
+/// results are written under `<Prefix>/<map run id>/`, "Prefix" being optional:
+/// - `SUCCEEDED_0.json`, `FAILED_0.json` and `PENDING_0.json` hold the results with that status,
+///   and are only written when there is at least one;
+/// - `manifest.json` lists the result files, and is returned as `{ "Bucket", "Key" }`.
+pub struct LocalDirectoryWriter {
+    root: PathBuf,
+}
+
+impl LocalDirectoryWriter {
+    pub fn new(root: impl Into<PathBuf>) -> Self {
+        LocalDirectoryWriter {
+            root: root.into(),
+        }
+    }
+
+    fn write_file(bucket: &Path, key: &str, content: &Value) -> Result<u64, ExecutionError> {
+        let path = bucket.join(relative_path(key).map_err(writer_failed)?);
+        let content = content.to_string();
+        path.parent()
+            .map_or(Ok(()), fs::create_dir_all)
+            .and_then(|_| fs::write(&path, &content))
+            .map_err(|e| writer_failed(format!("Can't write '{}': {e}", path.display())))?;
+        Ok(content.len() as u64)
+    }
+}
+
+impl ResultWriter for LocalDirectoryWriter {
+    fn write(&self, _resource: &str, parameters: &Value, results: &MapRunResults) -> Result<Value, ExecutionError> {
+        let bucket_name = string_parameter(parameters, "Bucket").map_err(writer_failed)?;
+        let bucket = self.root.join(relative_path(bucket_name).map_err(writer_failed)?);
+        let prefix = parameters.get("Prefix").and_then(Value::as_str).unwrap_or_default().trim_end_matches('/');
+        let key = |name: &str| match prefix {
+            "" => format!("{}/{name}", results.map_run_id),
+            prefix => format!("{prefix}/{}/{name}", results.map_run_id),
+        };
+
+        let mut result_files = serde_json::Map::new();
+        for (status, entries) in [("SUCCEEDED", &results.succeeded), ("FAILED", &results.failed), ("PENDING", &results.pending)] {
+            let mut files = vec![];
+            if !entries.is_empty() {
+                let file_key = key(&format!("{status}_0.json"));
+                let size = LocalDirectoryWriter::write_file(&bucket, &file_key, &Value::Array(entries.clone()))?;
+                files.push(json!({ "Key": file_key, "Size": size }));
+            }
+            result_files.insert(status.to_string(), Value::Array(files));
+        }
+        let manifest_key = key("manifest.json");
+        let manifest = json!({
+            "DestinationBucket": bucket_name,
+            "MapRunArn": results.map_run_id,
+            "ResultFiles": result_files,
+        });
+        LocalDirectoryWriter::write_file(&bucket, &manifest_key, &manifest)?;
+        Ok(json!({ "Bucket": bucket_name, "Key": manifest_key }))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rstest::*;
+    use anyhow::Result;
+    use crate::asl::temp_dir::TempDir;
+
+    fn results() -> MapRunResults {
+        MapRunResults {
+            map_run_id: "run".to_string(),
+            succeeded: vec![json!({ "Input": "1", "Output": "2", "Status": "SUCCEEDED" })],
+            failed: vec![json!({ "Input": "3", "Error": "Oops", "Cause": null, "Status": "FAILED" })],
+            pending: vec![],
+        }
+    }
+
+    #[rstest]
+    #[case::with_prefix(json!({ "Bucket": "bucket", "Prefix": "jobs/" }), "jobs/run")]
+    #[case::without_prefix(json!({ "Bucket": "bucket" }), "run")]
+    fn write(#[case] parameters: Value, #[case] directory: &str) -> Result<()> {
+        let root = TempDir::new("asl-result-writer")?;
+        let writer = LocalDirectoryWriter::new(root.path());
+
+        let details = writer.write("arn:aws:states:::s3:putObject", &parameters, &results())?;
+
+        assert_eq!(details, json!({ "Bucket": "bucket", "Key": format!("{directory}/manifest.json") }));
+        let manifest = root.read(format!("bucket/{directory}/manifest.json"))?;
+        assert_eq!(manifest["DestinationBucket"], json!("bucket"));
+        assert_eq!(manifest["MapRunArn"], json!("run"));
+        assert_eq!(manifest["ResultFiles"]["SUCCEEDED"][0]["Key"], json!(format!("{directory}/SUCCEEDED_0.json")));
+        assert_eq!(manifest["ResultFiles"]["FAILED"][0]["Key"], json!(format!("{directory}/FAILED_0.json")));
+        assert_eq!(manifest["ResultFiles"]["PENDING"], json!([]));
+        assert_eq!(root.read(format!("bucket/{directory}/SUCCEEDED_0.json"))?, json!(results().succeeded));
+        assert_eq!(root.read(format!("bucket/{directory}/FAILED_0.json"))?, json!(results().failed));
+        assert!(!root.path().join(format!("bucket/{directory}/PENDING_0.json")).exists());
+        Ok(())
+    }
+
+    #[rstest]
+    #[case::missing_bucket(json!({ "Prefix": "jobs" }))]
+    #[case::escaping_root(json!({ "Bucket": "bucket", "Prefix": "../jobs" }))]
+    fn write_fails(#[case] parameters: Value) -> Result<()> {
+        let root = TempDir::new("asl-result-writer")?;
+        let writer = LocalDirectoryWriter::new(root.path());
+
+        let error = writer.write("arn:aws:states:::s3:putObject", &parameters, &results()).unwrap_err();
+
+        assert_eq!(error.error, Some(ErrorName::StatesResultWriterFailed));
+        Ok(())
+    }
+}
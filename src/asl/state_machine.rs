@@ -1,26 +1,152 @@
 use std::collections::HashMap;
+use std::fmt::{Display, Formatter};
 use thiserror::Error;
 use serde::Deserialize;
 use serde_json::{Error as SerdeError, Number, Value};
 use crate::asl::error_handling::{Catcher, Retrier};
-use crate::asl::states::choice::ChoiceRule;
-use crate::asl::states::fail::{FailStateCauseField, FailStateErrorField};
-use crate::asl::states::task::{HeartbeatSecondsOrPath, TimeoutSecondsOrPath};
+use crate::asl::execution::Execution;
+use crate::asl::resources::Resources;
+use crate::asl::states::choice::{deserialize_choices, ChoiceRule};
+use crate::asl::states::fail::{deserialize_cause, deserialize_error, FailStateCauseField, FailStateErrorField};
+use crate::asl::states::task::{deserialize_credentials, deserialize_heartbeat, deserialize_timeout, HeartbeatSecondsOrPath, TimeoutSecondsOrPath};
 use crate::asl::states::wait::WaitDuration;
-use crate::asl::states::map::{ItemBatcherConfiguration, MapStateIterator, ResultWriterConfiguration};
-use crate::asl::types::{InvertedJsonPath, MyJsonPath, Parameters, Payload, ResultSelector};
-
-struct Execution {}
+use crate::asl::states::parallel::ParallelBranch;
+use crate::asl::states::map::{ItemBatcherConfiguration, ItemReaderConfiguration, MapStateIterator, ResultWriterConfiguration, ToleratedFailures};
+use crate::asl::json_path::{JsonPath, ReferencePath};
+use crate::asl::payload_template::PayloadTemplate;
+use crate::asl::types::{Parameters, ResultSelector};
+use crate::asl::validation;
 
 #[derive(Error, Debug)]
 pub enum ParseError {
-    #[error("Missing a field")]
-    MissingField,
-    #[error("Missing the 'StartsAt' field")]
-    StartStateNotDefinedInListOfStates,
-
+    /// The definition isn't valid JSON, or doesn't have the shape of a state machine. Only the
+    /// first such problem is reported.
     #[error("Malformed input: {0}")]
     MalformedInput(SerdeError),
+
+    /// The definition has the shape of a state machine, but breaks some rules of the spec. Every
+    /// problem found is reported.
+    #[error("Invalid definition: {}", .0.iter().map(ToString::to_string).collect::<Vec<_>>().join("; "))]
+    InvalidDefinition(Vec<Diagnostic>),
+}
+
+impl ParseError {
+    /// The problems found in the definition, which are empty if it's malformed.
+    pub fn diagnostics(&self) -> &[Diagnostic] {
+        match self {
+            ParseError::MalformedInput(_) => &[],
+            ParseError::InvalidDefinition(diagnostics) => diagnostics,
+        }
+    }
+}
+
+/// A problem found in a definition.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Diagnostic {
+    pub code: DiagnosticCode,
+    /// The JSON Pointer to the offending node, e.g. `/States/Map/ItemProcessor/States/Task/Next`,
+    /// see https://www.rfc-editor.org/rfc/rfc6901
+    pub pointer: String,
+    /// The name of the state the problem is about, if any.
+    pub state: Option<String>,
+    pub message: String,
+}
+
+impl Display for Diagnostic {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} (at '{}')", self.message, self.pointer)
+    }
+}
+
+/// The kind of a [Diagnostic], meant to be matched by tools such as editors.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum DiagnosticCode {
+    /// A "StartAt" field names a state that isn't defined in its scope.
+    StartStateNotDefined,
+    /// A state transitions to a state that isn't defined in its scope.
+    UnknownTransition,
+    /// A state can't be reached from the start state of its scope.
+    UnreachableState,
+    /// No state of a scope ends its execution.
+    MissingTerminalState,
+    /// A state name is used more than once in the state machine.
+    DuplicateStateName,
+    /// A state name is longer than 80 characters.
+    StateNameTooLong,
+    /// An object defines the same field more than once.
+    DuplicateField,
+    /// An object has a field the spec doesn't define, see [ParseMode].
+    UnknownField,
+    /// A state lacks a field it requires, or all the alternatives of a group of fields.
+    MissingField,
+    /// A state combines fields that are alternative ways of providing the same value.
+    MutuallyExclusiveFields,
+    /// A field has a value the spec doesn't allow.
+    InvalidFieldValue,
+}
+
+impl DiagnosticCode {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            DiagnosticCode::StartStateNotDefined => "START_STATE_NOT_DEFINED",
+            DiagnosticCode::UnknownTransition => "UNKNOWN_TRANSITION",
+            DiagnosticCode::UnreachableState => "UNREACHABLE_STATE",
+            DiagnosticCode::MissingTerminalState => "MISSING_TERMINAL_STATE",
+            DiagnosticCode::DuplicateStateName => "DUPLICATE_STATE_NAME",
+            DiagnosticCode::StateNameTooLong => "STATE_NAME_TOO_LONG",
+            DiagnosticCode::DuplicateField => "DUPLICATE_FIELD",
+            DiagnosticCode::UnknownField => "UNKNOWN_FIELD",
+            DiagnosticCode::MissingField => "MISSING_FIELD",
+            DiagnosticCode::MutuallyExclusiveFields => "MUTUALLY_EXCLUSIVE_FIELDS",
+            DiagnosticCode::InvalidFieldValue => "INVALID_FIELD_VALUE",
+        }
+    }
+}
+
+impl Display for DiagnosticCode {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// How a definition is parsed when it has fields the spec doesn't define, or states that can't
+/// be reached.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum ParseMode {
+    /// Unknown fields and unreachable states are problems, and the definition is rejected.
+    #[default]
+    Strict,
+    /// Unknown fields are ignored while executing, but kept and reported as warnings, so that
+    /// definitions using fields this crate doesn't support yet still load. Unreachable states are
+    /// accepted, as they never run.
+    Lenient,
+}
+
+/// A field the spec doesn't define, kept when parsing in [ParseMode::Lenient].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct UnknownField {
+    /// The JSON Pointer to the field.
+    pub pointer: String,
+    /// The name of the state the field belongs to, if any.
+    pub state: Option<String>,
+    pub name: String,
+    pub value: Value,
+}
+
+impl UnknownField {
+    /// Describes the field as a problem of the definition.
+    pub fn diagnostic(&self) -> Diagnostic {
+        let message = match &self.state {
+            Some(state) => format!("The state '{state}' has the unknown field '{}'", self.name),
+            None => format!("Unknown field '{}'", self.name),
+        };
+        Diagnostic {
+            code: DiagnosticCode::UnknownField,
+            pointer: self.pointer.clone(),
+            state: self.state.clone(),
+            message,
+        }
+    }
 }
 
 #[derive(Deserialize, Debug, PartialEq, Eq)]
@@ -41,6 +167,11 @@ pub enum EndOrNext {
 /// | Parameters                     | Allowed  | Allowed  | Allowed  | Allowed  |          |          |          |          |
 /// | ResultSelector                 | Allowed  | Allowed  | Allowed  |          |          |          |          |          |
 /// | Retry, Catch                   | Allowed  | Allowed  | Allowed  |          |          |          |          |          |
+///
+/// "InputPath", "OutputPath" and "ResultPath" default to "$" when they are not provided. They can
+/// also be set to `null`, which is kept as `None`, see https://states-language.net/spec.html#filters
+// States are only built when parsing a definition, so boxing the big variants isn't worth it.
+#[allow(clippy::large_enum_variant)]
 #[derive(Deserialize, Debug, PartialEq, Eq)]
 #[serde(rename_all = "PascalCase", tag = "Type")]
 pub enum State {
@@ -59,155 +190,246 @@ pub enum State {
         /// If provided, the "HeartbeatSeconds" interval MUST be smaller than the "TimeoutSeconds" value.
         ///
         /// If not provided, the default value of "TimeoutSeconds" is 60.
-        #[serde(flatten, default)]
+        #[serde(flatten, deserialize_with = "deserialize_timeout")]
         timeout: Option<TimeoutSecondsOrPath>,
 
         /// See docs for 'timeout' field
-        #[serde(flatten)]
-        heartbeat: Option<HeartbeatSecondsOrPath>, //TODO: validation! If provided, the "HeartbeatSeconds" interval MUST be smaller than the "TimeoutSeconds" value.
+        #[serde(flatten, deserialize_with = "deserialize_heartbeat")]
+        heartbeat: Option<HeartbeatSecondsOrPath>,
 
         /// A Task State MAY include a "Credentials" field, whose value MUST be a JSON object whose
         /// value is defined by the interpreter.
         /// The States language does not constrain the value of the "Credentials" field.
         /// The interpreter will use the specified credentials to execute the work identified by the state's "Resource" field.
-        credentials: Option<Value>,
+        #[serde(default, deserialize_with = "deserialize_credentials")]
+        credentials: Option<PayloadTemplate>,
 
         // Common fields
         comment: Option<String>,
-        input_path: Option<MyJsonPath>,
-        output_path: Option<MyJsonPath>,
+        #[serde(default = "path_default")]
+        input_path: Option<JsonPath>,
+        #[serde(default = "path_default")]
+        output_path: Option<JsonPath>,
         #[serde(flatten)]
         end_or_next: EndOrNext,
-        result_path: Option<MyJsonPath>,
+        #[serde(default = "reference_path_default")]
+        result_path: Option<ReferencePath>,
         parameters: Option<Parameters>,
         result_selector: Option<ResultSelector>,
         retry: Option<Vec<Retrier>>,
         catch: Option<Vec<Catcher>>,
     },
-    ///
+    /// See docs: https://states-language.net/spec.html#parallel-state
     #[serde(rename_all = "PascalCase")]
     Parallel {
+        /// A Parallel State MUST contain a field named "Branches" which is an array whose elements
+        /// MUST be objects. Each object MUST contain fields named "States" and "StartAt" whose
+        /// meanings are exactly like those in the top level of a state machine.
+        branches: Vec<ParallelBranch>,
+
         // Common fields
         comment: Option<String>,
-        input_path: Option<MyJsonPath>,
-        output_path: Option<MyJsonPath>,
+        #[serde(default = "path_default")]
+        input_path: Option<JsonPath>,
+        #[serde(default = "path_default")]
+        output_path: Option<JsonPath>,
         #[serde(flatten)]
         end_or_next: EndOrNext,
-        result_path: Option<MyJsonPath>,
+        #[serde(default = "reference_path_default")]
+        result_path: Option<ReferencePath>,
         parameters: Option<Parameters>,
         result_selector: Option<ResultSelector>,
         retry: Option<Vec<Retrier>>,
         catch: Option<Vec<Catcher>>,
     },
-    ///
+    /// See docs: https://states-language.net/spec.html#map-state
     #[serde(rename_all = "PascalCase")]
     Map {
         max_concurrency: Option<u32>,
         #[serde(alias="Iterator")]
         item_processor: MapStateIterator,
-        items_path: Option<MyJsonPath>,
-        item_selector: Option<HashMap<InvertedJsonPath, MyJsonPath>>,
+        items_path: Option<ReferencePath>,
+        item_selector: Option<PayloadTemplate>,
         item_batcher: Option<ItemBatcherConfiguration>,
+        /// Reads the items from an external source instead of selecting them with "ItemsPath".
+        item_reader: Option<ItemReaderConfiguration>,
         result_writer: Option<ResultWriterConfiguration>,
-        tolerated_failure_count: Option<u32>,
-        tolerated_failure_percentage: Option<u32>,
+        /// Names the runs of a distributed Map. It only describes the state here, as the child
+        /// executions are identified by their id.
+        label: Option<String>,
+        /// The Map fails with `States.ExceedToleratedFailureThreshold` once more iterations than
+        /// the tolerated count or percentage have failed. Without either, the first failure fails
+        /// the Map.
+        #[serde(flatten)]
+        tolerated_failures: ToleratedFailures,
 
         // Common fields
         comment: Option<String>,
-        input_path: Option<MyJsonPath>,
-        output_path: Option<MyJsonPath>,
+        #[serde(default = "path_default")]
+        input_path: Option<JsonPath>,
+        #[serde(default = "path_default")]
+        output_path: Option<JsonPath>,
         #[serde(flatten)]
         end_or_next: EndOrNext,
-        result_path: Option<MyJsonPath>,
+        #[serde(default = "reference_path_default")]
+        result_path: Option<ReferencePath>,
         #[deprecated] // Use `item_selector` instead
         parameters: Option<Parameters>,
         result_selector: Option<ResultSelector>,
         retry: Option<Vec<Retrier>>,
         catch: Option<Vec<Catcher>>,
     },
+    /// See docs: https://states-language.net/spec.html#pass-state
     #[serde(rename_all = "PascalCase")]
     Pass {
+        /// A Pass State MAY have a field named "Result". If present, its value is treated as the
+        /// output of a virtual task, and placed as prescribed by the "ResultPath" field, if any,
+        /// to be passed on to the next state. If "Result" is not provided, the output is the input.
+        result: Option<Value>,
+
         // Common fields
         comment: Option<String>,
-        input_path: Option<MyJsonPath>,
-        output_path: Option<MyJsonPath>,
+        #[serde(default = "path_default")]
+        input_path: Option<JsonPath>,
+        #[serde(default = "path_default")]
+        output_path: Option<JsonPath>,
         #[serde(flatten)]
         end_or_next: EndOrNext,
-        result_path: Option<MyJsonPath>,
-        parameters: Option<Payload>,
+        #[serde(default = "reference_path_default")]
+        result_path: Option<ReferencePath>,
+        parameters: Option<Parameters>,
     },
+    /// See docs: https://states-language.net/spec.html#wait-state
     #[serde(rename_all = "PascalCase")]
     Wait {
         #[serde(flatten)]
         duration: WaitDuration,
         // Common fields
         comment: Option<String>,
-        input_path: Option<MyJsonPath>,
-        output_path: Option<MyJsonPath>,
+        #[serde(default = "path_default")]
+        input_path: Option<JsonPath>,
+        #[serde(default = "path_default")]
+        output_path: Option<JsonPath>,
         #[serde(flatten)]
         end_or_next: EndOrNext,
     },
 
-    ///
+    /// See docs: https://states-language.net/spec.html#choice-state
     #[serde(rename_all = "PascalCase")]
     Choice {
+        /// A Choice State MUST have a "Choices" field whose value is a non-empty array.
+        #[serde(deserialize_with = "deserialize_choices")]
         choices: Vec<ChoiceRule>,
 
+        /// A Choice State MAY have a "Default" field, whose value MUST be a string whose value is
+        /// the name of one of the States in the state machine. The interpreter transitions to this
+        /// state if none of the Choice Rules match, and raises `States.NoChoiceMatched` if there is
+        /// no "Default".
+        default: Option<String>,
+
         // Common fields
         comment: Option<String>,
-        input_path: Option<MyJsonPath>,
-        output_path: Option<MyJsonPath>,
+        #[serde(default = "path_default")]
+        input_path: Option<JsonPath>,
+        #[serde(default = "path_default")]
+        output_path: Option<JsonPath>,
     },
+    /// See docs: https://states-language.net/spec.html#succeed-state
     #[serde(rename_all = "PascalCase")]
     Succeed {
         // Common fields
         comment: Option<String>,
-        input_path: Option<MyJsonPath>,
-        output_path: Option<MyJsonPath>,
+        #[serde(default = "path_default")]
+        input_path: Option<JsonPath>,
+        #[serde(default = "path_default")]
+        output_path: Option<JsonPath>,
     },
+    /// See docs: https://states-language.net/spec.html#fail-state
     #[serde(rename_all = "PascalCase")]
     Fail {
-        #[serde(flatten)]
+        #[serde(flatten, deserialize_with = "deserialize_error")]
         error: Option<FailStateErrorField>,
-        #[serde(flatten)]
+        #[serde(flatten, deserialize_with = "deserialize_cause")]
         cause: Option<FailStateCauseField>,
         // Common fields
         comment: Option<String>,
     },
 }
 
+pub(crate) fn path_default() -> Option<JsonPath> {
+    "$".parse().ok()
+}
+
+pub(crate) fn reference_path_default() -> Option<ReferencePath> {
+    "$".parse().ok()
+}
+
 #[derive(Deserialize, Debug)]
 #[serde(rename_all = "PascalCase")]
-struct StateMachineDefinition {
-    states: HashMap<String, State>,
-    comment: Option<String>,
-    start_at: String,
-    version: Option<String>,
-    timeout_seconds: Option<Number>,
+pub struct StateMachineDefinition {
+    pub states: HashMap<String, State>,
+    pub comment: Option<String>,
+    pub start_at: String,
+    pub version: Option<String>,
+    pub timeout_seconds: Option<Number>,
 }
 
-type ResourceTypesActions = HashMap<&'static str, fn(&Value) -> Option<Value>>;
-
-struct StateMachine {
-    definition: StateMachineDefinition,
-    resources: ResourceTypesActions,
+pub struct StateMachine {
+    pub(crate) definition: StateMachineDefinition,
+    pub(crate) resources: Resources,
+    unknown_fields: Vec<UnknownField>,
 }
 
 impl StateMachine {
-    // fn parse(definition: &str, resources: ResourceTypesActions) -> Result<StateMachine, ParseError> {
-    fn parse(definition: &str) -> Result<StateMachine, ParseError> {
-        let definition = serde_json::from_str(definition).map_err(|e: SerdeError| ParseError::MalformedInput(e))?;
-        let state_machine = StateMachine {
+    pub fn parse(definition: &str) -> Result<StateMachine, ParseError> {
+        StateMachine::parse_with_resources(definition, Resources::new())
+    }
+
+    /// Parses the definition, using `resources` to execute the Task states.
+    pub fn parse_with_resources(definition: &str, resources: Resources) -> Result<StateMachine, ParseError> {
+        StateMachine::parse_with_mode(definition, resources, ParseMode::Strict)
+    }
+
+    /// Parses the definition, using `resources` to execute the Task states and `mode` to handle
+    /// the fields the spec doesn't define.
+    pub fn parse_with_mode(definition: &str, resources: Resources, mode: ParseMode) -> Result<StateMachine, ParseError> {
+        // The rules are checked on the fields as they were written, which deserializing doesn't
+        // keep: it would silently collapse duplicate keys or combined alternative fields, and
+        // would only report the first problem, without its location.
+        let (raw_definition, mut diagnostics) = validation::parse(definition).map_err(ParseError::MalformedInput)?;
+        let (problems, unknown_fields) = validation::validate(&raw_definition, mode);
+        diagnostics.extend(problems);
+        if !diagnostics.is_empty() {
+            return Err(ParseError::InvalidDefinition(diagnostics));
+        }
+        let definition = serde_json::from_str(definition).map_err(ParseError::MalformedInput)?;
+
+        Ok(StateMachine {
             definition,
-            resources: HashMap::new(), // TODO: Check how to define handling of resource types
-        };
-        // TODO: validate state machine
+            resources,
+            unknown_fields,
+        })
+    }
+
+    pub fn definition(&self) -> &StateMachineDefinition {
+        &self.definition
+    }
+
+    /// The fields the spec doesn't define, which are only kept in [ParseMode::Lenient].
+    pub fn unknown_fields(&self) -> &[UnknownField] {
+        &self.unknown_fields
+    }
 
-        Ok(state_machine)
+    /// The unknown fields, described as problems that didn't prevent parsing the definition.
+    pub fn warnings(&self) -> Vec<Diagnostic> {
+        self.unknown_fields.iter().map(UnknownField::diagnostic).collect()
     }
 
-    fn start(input: &Value) -> Execution {
-        Execution {}
+    /// Starts a new execution of this state machine with the given input.
+    ///
+    /// Nothing runs until the returned [Execution] is driven, see [Execution::run].
+    pub fn start(&self, input: &Value) -> Execution<'_> {
+        Execution::new(self, input.to_owned())
     }
 }
 
@@ -218,15 +440,12 @@ mod tests {
     use super::*;
     use rstest::*;
     use itertools::Itertools;
+    use serde_json::json;
     use anyhow::Result;
 
     #[rstest]
     fn parse_hello_world_state_machine() -> Result<()> {
         let definition = include_str!("test-data/hello-world.json");
-        // let resources = hash_map![
-        //     "return" => |input: &Value| -> Option<Value> { Some(input.to_owned()) }
-        // ];
-        // let ret = resources.get("return").unwrap()(&serde_json::json!(1));
         let state_machine = StateMachine::parse(definition)?;
 
         // Testing internals, but ok for now
@@ -237,9 +456,9 @@ mod tests {
             end_or_next: EndOrNext::End(true),
             resource: String::from("return"),
             credentials: None,
-            input_path: None,
-            output_path: None,
-            result_path: None,
+            input_path: Some("$".parse()?),
+            output_path: Some("$".parse()?),
+            result_path: Some("$".parse()?),
             parameters: None,
             result_selector: None,
             retry: None,
@@ -248,31 +467,257 @@ mod tests {
             // timeout: Some(TimeoutSeconds(60)), //TODO: Check why this is not the case
             timeout: None,
         });
-
-        // API testing
-        // let start_state = state_machine.start_state();
-        //
-        // let execution = state_machine.start(r#"
-        //     { "arg": "Hello world" }
-        // "#);
-        // let result = execution.next();
-        // assert_eq!(result?, "Hello world");
-        // // assert_eq!(execution.state);
         Ok(())
     }
 
     #[rstest]
     fn parse_valid_cases(#[files("src/**/test-data/asl-validator/valid-*.json")] path: PathBuf) -> Result<()> {
         let definition = fs::read_to_string(path)?;
-        StateMachine::parse(definition.as_str())?;
+        // Some of the examples keep states that can't be reached, which only the strict mode rejects.
+        let state_machine = StateMachine::parse_with_mode(definition.as_str(), Resources::new(), ParseMode::Lenient)?;
+        assert_eq!(state_machine.unknown_fields(), &[]);
+        Ok(())
+    }
+
+    #[rstest]
+    #[case::json_path("asl-validator/invalid-json-path.json", &[
+        (DiagnosticCode::InvalidFieldValue, "/States/Invalid1/ResultPath"),
+        (DiagnosticCode::InvalidFieldValue, "/States/Invalid2/ResultPath"),
+        (DiagnosticCode::InvalidFieldValue, "/States/Invalid3/ResultPath"),
+        (DiagnosticCode::InvalidFieldValue, "/States/Invalid4/ResultPath"),
+    ])]
+    #[case::input_path("asl-validator/invalid-exercise-ajv.asl.json", &[(DiagnosticCode::InvalidFieldValue, "/States/PassState/InputPath")])]
+    #[case::choice_variable("asl-validator/invalid-choice-state-variable.json", &[(DiagnosticCode::InvalidFieldValue, "/States/ChoiceState/Choices/0/Variable")])]
+    #[case::payload_template("asl-validator/invalid-payload-template.asl.json", &[(DiagnosticCode::InvalidFieldValue, "/States/Hello, World/Parameters")])]
+    #[case::payload_template_conflict("asl-validator/invalid-dupe-fields.asl.json", &[(DiagnosticCode::InvalidFieldValue, "/States/PassState/Parameters")])]
+    #[case::payload_template_duplicate("asl-validator/invalid-duplicate-fields.json", &[(DiagnosticCode::InvalidFieldValue, "/States/Publish to Slack/Parameters")])]
+    #[case::payload_template_duplicate_nested("asl-validator/invalid-duplicate-fields-nested.json", &[(DiagnosticCode::InvalidFieldValue, "/States/Publish to Slack/Parameters")])]
+    #[case::credentials_null("asl-validator/invalid-task-credentials-null.json", &[(DiagnosticCode::InvalidFieldValue, "/States/X/Credentials")])]
+    #[case::credentials_template("asl-validator/invalid-task-credentials-object.json", &[(DiagnosticCode::InvalidFieldValue, "/States/X/Credentials")])]
+    #[case::choice_without_rules("choice-without-rules.json", &[(DiagnosticCode::InvalidFieldValue, "/States/Choice/Choices")])]
+    #[case::parallel_without_branches("asl-validator/invalid-parallel-missing-branches.json", &[(DiagnosticCode::MissingField, "/States/Parallel")])]
+    fn parse_invalid_field_values(#[case] file: &str, #[case] expected: &[(DiagnosticCode, &str)]) -> Result<()> {
+        let definition = fs::read_to_string(format!("src/asl/test-data/{file}"))?;
+        let ret = StateMachine::parse(definition.as_str());
+        let diagnostics = ret.as_ref().err().map(ParseError::diagnostics).unwrap_or_default();
+        assert_eq!(diagnostics.iter().map(|d| (d.code, d.pointer.as_str())).collect_vec(), expected);
+        Ok(())
+    }
+
+    #[rstest]
+    fn parse_invalid_tolerated_failure_percentage() -> Result<()> {
+        let definition = include_str!("test-data/asl-validator/invalid-map-tolerated-value.json");
+        let ret = StateMachine::parse(definition);
+        let diagnostics = ret.as_ref().err().map(ParseError::diagnostics).unwrap_or_default();
+        assert_eq!(diagnostics.len(), 1);
+        assert_eq!(diagnostics[0].code, DiagnosticCode::InvalidFieldValue);
+        assert_eq!(diagnostics[0].state.as_deref(), Some("Map"));
+        assert_eq!(diagnostics[0].pointer, "/States/Map/ToleratedFailurePercentage");
+        Ok(())
+    }
+
+    #[rstest]
+    #[case::max_concurrency(json!({ "MaxConcurrency": "x" }), "/States/Map/MaxConcurrency")]
+    #[case::max_attempts(json!({ "Retry": [{ "ErrorEquals": ["States.ALL"], "MaxAttempts": -1 }] }), "/States/Map/Retry/0/MaxAttempts")]
+    #[case::error_equals(json!({ "Catch": [{ "ErrorEquals": "A", "Next": "Map" }] }), "/States/Map/Catch/0/ErrorEquals")]
+    #[case::jitter_strategy(json!({ "Retry": [{ "ErrorEquals": ["States.ALL"], "JitterStrategy": "HALF" }] }), "/States/Map/Retry/0/JitterStrategy")]
+    #[case::mode(
+        json!({ "ItemProcessor": { "ProcessorConfig": { "Mode": "REMOTE" }, "StartAt": "Pass", "States": { "Pass": { "Type": "Pass", "End": true } } } }),
+        "/States/Map/ItemProcessor/ProcessorConfig/Mode",
+    )]
+    fn parse_wrongly_typed_fields(#[case] fields: Value, #[case] pointer: &str) {
+        let mut state = json!({
+            "Type": "Map",
+            "ItemProcessor": { "StartAt": "Pass", "States": { "Pass": { "Type": "Pass", "End": true } } },
+            "End": true,
+        });
+        if let (Some(state), Some(fields)) = (state.as_object_mut(), fields.as_object()) {
+            state.extend(fields.clone());
+        }
+        let definition = json!({ "StartAt": "Map", "States": { "Map": state } }).to_string();
+
+        let ret = StateMachine::parse(&definition);
+
+        let diagnostics = ret.as_ref().err().map(ParseError::diagnostics).unwrap_or_default();
+        assert_eq!(diagnostics.iter().map(|d| (d.code, d.pointer.as_str())).collect_vec(), vec![(DiagnosticCode::InvalidFieldValue, pointer)]);
+    }
+
+    #[rstest]
+    #[case::negative_percentage(r#""ToleratedFailurePercentage": -1"#, "/States/Map/ToleratedFailurePercentage", "must be a number between 0 and 100, but it is -1")]
+    #[case::percentage_above_100(r#""ToleratedFailurePercentage": 150.5"#, "/States/Map/ToleratedFailurePercentage", "must be a number between 0 and 100, but it is 150.5")]
+    #[case::string_percentage(r#""ToleratedFailurePercentage": "5""#, "/States/Map/ToleratedFailurePercentage", r#"must be a number between 0 and 100, but it is "5""#)]
+    #[case::negative_count(r#""ToleratedFailureCount": -1"#, "/States/Map/ToleratedFailureCount", "must be a non-negative integer, but it is -1")]
+    #[case::decimal_count(r#""ToleratedFailureCount": 50.5"#, "/States/Map/ToleratedFailureCount", "must be a non-negative integer, but it is 50.5")]
+    #[case::string_count(r#""ToleratedFailureCount": "5""#, "/States/Map/ToleratedFailureCount", r#"must be a non-negative integer, but it is "5""#)]
+    fn parse_invalid_tolerated_failures(#[case] fields: &str, #[case] pointer: &str, #[case] message: &str) {
+        let definition = format!(r#"{{
+            "StartAt": "Map",
+            "States": {{
+                "Map": {{
+                    "Type": "Map",
+                    {fields},
+                    "ItemProcessor": {{ "StartAt": "Pass", "States": {{ "Pass": {{ "Type": "Pass", "End": true }} }} }},
+                    "End": true
+                }}
+            }}
+        }}"#);
+        let ret = StateMachine::parse(&definition);
+        let diagnostics = ret.as_ref().err().map(ParseError::diagnostics).unwrap_or_default();
+        let field = pointer.rsplit('/').next().unwrap_or_default();
+        let message = format!("Invalid value for the field '{field}' of the state 'Map': {message}");
+        assert_eq!(diagnostics.iter().map(|d| (d.code, d.pointer.as_str(), d.message.as_str())).collect_vec(), vec![(DiagnosticCode::InvalidFieldValue, pointer, message.as_str())]);
+    }
+
+    #[rstest]
+    fn parse_decimal_tolerated_failure_percentage() -> Result<()> {
+        let definition = r#"{
+            "StartAt": "Map",
+            "States": {
+                "Map": {
+                    "Type": "Map",
+                    "ToleratedFailurePercentage": 50.5,
+                    "ItemProcessor": { "StartAt": "Pass", "States": { "Pass": { "Type": "Pass", "End": true } } },
+                    "End": true
+                }
+            }
+        }"#;
+        let state_machine = StateMachine::parse(definition)?;
+        let Some(State::Map { tolerated_failures, .. }) = state_machine.definition().states.get("Map") else {
+            anyhow::bail!("The state 'Map' isn't a Map state");
+        };
+        assert_eq!(tolerated_failures.percentage(&Value::Null, &Value::Null)?, Some(50.5));
         Ok(())
     }
 
-    // #[rstest]
-    // fn parse_invalid_cases(#[files("src/**/test-data/asl-validator/invalid-*.json")] path: PathBuf) -> Result<()> {
-    //     let definition = fs::read_to_string(path)?;
-    //     let ret = StateMachine::parse(definition.as_str());
-    //     assert_eq!(ret.is_err(), true);
-    //     Ok(())
-    // }
+    #[rstest]
+    #[case::unknown_target("asl-validator/invalid-inexistant-state.json", &[
+        "The state 'Start State' transitions to the state 'Finished', which is not defined in its scope",
+        "No state ends the execution: at least one state must be terminal in each scope",
+    ])]
+    #[case::map_out_of_bounds_link("asl-validator/invalid-map-ob-link.json", &["The state 'ChoiceState' transitions to the state 'Final State', which is not defined in its scope"])]
+    #[case::parallel_out_of_bounds_link("asl-validator/invalid-parallel-ob-link.json", &["The state 'ChoiceState' transitions to the state 'Final State', which is not defined in its scope"])]
+    #[case::unreachable_state("asl-validator/invalid-unreachable-state.json", &["The state 'Finished Choice' can't be reached from the start state of its scope"])]
+    #[case::missing_terminal("asl-validator/invalid-missing-terminal.json", &["No state ends the execution: at least one state must be terminal in each scope"])]
+    #[case::next_with_end("asl-validator/invalid-next-with-end.json", &["The state 'Send SNS Message' can't have both the fields 'Next' and 'End'"])]
+    #[case::duplicate_state_name("asl-validator/invalid-map-dupe-state.json", &["The state name 'Final State' is used more than once"])]
+    #[case::state_name_too_long(
+        "asl-validator/invalid-state-name-too-long.json",
+        &["The state name 'This is an exceptionally long state name that I know will fail when I try to deploy to AWS' is longer than 80 characters"],
+    )]
+    #[case::unclosed_substitution("asl-validator/invalid-cfn-definition-substitutions.json", &["Invalid value for the field 'Resource' of the state 'X': '${lambdaArn' is not a valid URI"])]
+    #[case::lambda_alias(
+        "asl-validator/invalid-task-alias-function.json",
+        &[
+            "Invalid value for the field 'Resource' of the state 'InvalidAlias': 'arn:aws:lambda:region-1:1234567890:function:FUNCTION_NAME:$*#$7485' is not a valid URI",
+            "Invalid value for the field 'Resource' of the state 'MissingAlias': 'arn:aws:lambda:region-1:1234567890:function:FUNCTION_NAME:' is not a valid URI",
+        ],
+    )]
+    #[case::heartbeat("asl-validator/invalid-task-heartbeat.json", &["The state 'X' can't have both the fields 'HeartbeatSeconds' and 'HeartbeatSecondsPath'"])]
+    #[case::timeout("asl-validator/invalid-task-timout.json", &["The state 'X' can't have both the fields 'TimeoutSeconds' and 'TimeoutSecondsPath'"])]
+    #[case::tolerated_failure_count("asl-validator/invalid-map-tolerated.json", &["The state 'Map' can't have both the fields 'ToleratedFailureCount' and 'ToleratedFailureCountPath'"])]
+    #[case::fail_error("asl-validator/invalid-fail-dupe-error.json", &["The state 'Hello' can't have both the fields 'Error' and 'ErrorPath'"])]
+    #[case::fail_cause("asl-validator/invalid-fail-dupe-cause.json", &["The state 'Hello' can't have both the fields 'Cause' and 'CausePath'"])]
+    #[case::wait_duration("asl-validator/invalid-wait-duration.json", &[
+        "The state 'wait_using_seconds' can't have both the fields 'Seconds' and 'SecondsPath'",
+        "The state 'wait_using_timestamp' can't have both the fields 'Timestamp' and 'TimestampPath'",
+    ])]
+    #[case::item_reader_max_items("asl-validator/invalid-map-distributed.asl.json", &["The state 'Map' can't have both the fields 'MaxItems' and 'MaxItemsPath'"])]
+    #[case::item_batcher("asl-validator/invalid-map-item-batcher-dupe-subfields.json", &[
+        "The state 'Map' can't have both the fields 'MaxItemsPerBatch' and 'MaxItemsPerBatchPath'",
+        "The state 'Map' can't have both the fields 'MaxInputBytesPerBatch' and 'MaxInputBytesPerBatchPath'",
+    ])]
+    #[case::fail_error_not_a_string("fail-error-not-a-string.json", &["Invalid value for the field 'Error' of the state 'Fail': must be a string, but it is 5"])]
+    #[case::task_timeout_not_a_number(
+        "task-timeout-not-a-number.json",
+        &[r#"Invalid value for the field 'TimeoutSeconds' of the state 'Task': must be a positive integer, but it is "x""#],
+    )]
+    #[case::wait_seconds_not_a_number(
+        "wait-seconds-not-a-number.json",
+        &[r#"Invalid value for the field 'Seconds' of the state 'Wait': must be a non-negative integer, but it is "x""#],
+    )]
+    fn parse_invalid_definitions(#[case] file: &str, #[case] expected: &[&str]) -> Result<()> {
+        let definition = fs::read_to_string(format!("src/asl/test-data/{file}"))?;
+        let ret = StateMachine::parse(definition.as_str());
+        let messages = ret.as_ref().err().map(ParseError::diagnostics).unwrap_or_default().iter().map(|diagnostic| diagnostic.message.as_str()).collect::<Vec<_>>();
+        assert_eq!(messages, expected);
+        Ok(())
+    }
+
+    #[rstest]
+    #[case::fail_error(json!({ "Type": "Fail", "Error": 5 }))]
+    #[case::fail_cause_path(json!({ "Type": "Fail", "CausePath": ["$.cause"] }))]
+    #[case::task_timeout(json!({ "Type": "Task", "Resource": "task", "TimeoutSeconds": "x", "End": true }))]
+    #[case::task_heartbeat(json!({ "Type": "Task", "Resource": "task", "HeartbeatSeconds": 0, "End": true }))]
+    #[case::wait_seconds(json!({ "Type": "Wait", "Seconds": "x", "End": true }))]
+    #[case::wait_timestamp(json!({ "Type": "Wait", "Timestamp": "tomorrow", "End": true }))]
+    fn deserialize_wrongly_typed_alternative_fields(#[case] state: Value) {
+        assert!(serde_json::from_value::<State>(state).is_err());
+    }
+
+    #[rstest]
+    fn parse_duplicate_state_names() {
+        let definition = r#"{
+            "StartAt": "Start",
+            "States": {
+                "Start": { "Type": "Pass", "Next": "Start" },
+                "Start": { "Type": "Succeed" }
+            }
+        }"#;
+        let ret = StateMachine::parse(definition);
+        let diagnostics = ret.as_ref().err().map(ParseError::diagnostics).unwrap_or_default();
+        assert_eq!(diagnostics.first().map(|diagnostic| (diagnostic.code, diagnostic.pointer.as_str())), Some((DiagnosticCode::DuplicateStateName, "/States/Start")));
+    }
+
+    #[rstest]
+    fn parse_unknown_fields() -> Result<()> {
+        let definition = include_str!("test-data/asl-validator/invalid-exercise-ajv-additional-properties.asl.json");
+
+        let error = StateMachine::parse(definition).err().ok_or(anyhow::anyhow!("Parsed a definition with unknown fields"))?;
+        let diagnostic = (DiagnosticCode::UnknownField, "/States/PassState/bugInputPath", "The state 'PassState' has the unknown field 'bugInputPath'");
+        assert_eq!(error.diagnostics().iter().map(|d| (d.code, d.pointer.as_str(), d.message.as_str())).collect_vec(), vec![diagnostic]);
+
+        let state_machine = StateMachine::parse_with_mode(definition, Resources::new(), ParseMode::Lenient)?;
+        assert_eq!(state_machine.unknown_fields(), &[UnknownField {
+            pointer: "/States/PassState/bugInputPath".to_string(),
+            state: Some("PassState".to_string()),
+            name: "bugInputPath".to_string(),
+            value: Value::from("$.library.movies"),
+        }]);
+        assert_eq!(state_machine.warnings().iter().map(|d| d.message.as_str()).collect_vec(), vec![diagnostic.2]);
+        Ok(())
+    }
+
+    #[rstest]
+    #[case::top_level(r#"{ "StartAt": "A", "Extra": 1, "States": { "A": { "Type": "Succeed" } } }"#, "/Extra", None)]
+    #[case::retrier(
+        r#"{ "StartAt": "A", "States": { "A": { "Type": "Task", "Resource": "return", "Retry": [{ "ErrorEquals": ["States.ALL"], "Extra": 1 }], "End": true } } }"#,
+        "/States/A/Retry/0/Extra", Some("A"),
+    )]
+    #[case::branch(
+        r#"{ "StartAt": "P", "States": { "P": { "Type": "Parallel", "Branches": [{ "StartAt": "A", "Extra": 1, "States": { "A": { "Type": "Succeed" } } }], "End": true } } }"#,
+        "/States/P/Branches/0/Extra", Some("P"),
+    )]
+    #[case::item_reader(
+        r#"{ "StartAt": "M", "States": { "M": { "Type": "Map", "ItemReader": { "Resource": "arn:aws:states:::s3:getObject", "Extra": 1 }, "ItemProcessor": { "StartAt": "A", "States": { "A": { "Type": "Succeed" } } }, "End": true } } }"#,
+        "/States/M/ItemReader/Extra", Some("M"),
+    )]
+    fn parse_nested_unknown_fields(#[case] definition: &str, #[case] pointer: &str, #[case] state: Option<&str>) -> Result<()> {
+        let error = StateMachine::parse(definition).err().ok_or(anyhow::anyhow!("Parsed a definition with unknown fields"))?;
+        assert_eq!(error.diagnostics().iter().map(|d| (d.code, d.pointer.as_str(), d.state.as_deref())).collect_vec(), vec![(DiagnosticCode::UnknownField, pointer, state)]);
+
+        let state_machine = StateMachine::parse_with_mode(definition, Resources::new(), ParseMode::Lenient)?;
+        assert_eq!(state_machine.unknown_fields().iter().map(|field| field.pointer.as_str()).collect_vec(), vec![pointer]);
+        Ok(())
+    }
+
+    #[rstest]
+    fn parse_invalid_cases(
+        #[files("src/**/test-data/asl-validator/invalid-*.json")]
+        path: PathBuf,
+    ) -> Result<()> {
+        let definition = fs::read_to_string(path)?;
+        let ret = StateMachine::parse(definition.as_str());
+        assert!(ret.is_err());
+        Ok(())
+    }
 }
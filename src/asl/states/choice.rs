@@ -1,23 +1,28 @@
-use serde::Deserialize;
-use serde_json::Number;
-use crate::asl::types::{MyJsonPath, Timestamp};
+use std::cmp::Ordering;
+use chrono::{DateTime, FixedOffset};
+use serde::{Deserialize, Deserializer};
+use serde::de::Error;
+use serde_json::{Number, Value};
+use crate::asl::execution::ExecutionError;
+use crate::asl::json_path::ReferencePath;
+use crate::asl::types::{deserialize_timestamp, Timestamp};
 
 #[derive(Deserialize, Debug, PartialEq, Eq)]
 enum Operation {
     StringEquals(String),
-    StringEqualsPath(String),
+    StringEqualsPath(ReferencePath),
 
     StringLessThan(String),
-    StringLessThanPath(String),
+    StringLessThanPath(ReferencePath),
 
     StringGreaterThan(String),
-    StringGreaterThanPath(String),
+    StringGreaterThanPath(ReferencePath),
 
     StringLessThanEquals(String),
-    StringLessThanEqualsPath(String),
+    StringLessThanEqualsPath(ReferencePath),
 
     StringGreaterThanEquals(String),
-    StringGreaterThanEqualsPath(String),
+    StringGreaterThanEqualsPath(ReferencePath),
 
     /// Note: The value MUST be a String which MAY contain one or more "*" characters.
     /// The expression yields true if the data value selected by the Variable Path matches the value,
@@ -41,50 +46,231 @@ enum Operation {
     StringMatches(String),
 
     NumericEquals(Number),
-    NumericEqualsPath(Number),
+    NumericEqualsPath(ReferencePath),
 
     NumericLessThan(Number),
-    NumericLessThanPath(Number),
+    NumericLessThanPath(ReferencePath),
 
     NumericGreaterThan(Number),
-    NumericGreaterThanPath(Number),
+    NumericGreaterThanPath(ReferencePath),
 
     NumericLessThanEquals(Number),
-    NumericLessThanEqualsPath(Number),
+    NumericLessThanEqualsPath(ReferencePath),
 
     NumericGreaterThanEquals(Number),
-    NumericGreaterThanEqualsPath(Number),
+    NumericGreaterThanEqualsPath(ReferencePath),
 
     BooleanEquals(bool),
+    BooleanEqualsPath(ReferencePath),
 
+    #[serde(deserialize_with = "deserialize_timestamp")]
     TimestampEquals(Timestamp),
-    TimestampEqualsPath(Timestamp),
+    TimestampEqualsPath(ReferencePath),
 
+    #[serde(deserialize_with = "deserialize_timestamp")]
     TimestampLessThan(Timestamp),
-    TimestampLessThanPath(Timestamp),
+    TimestampLessThanPath(ReferencePath),
 
+    #[serde(deserialize_with = "deserialize_timestamp")]
     TimestampGreaterThan(Timestamp),
-    TimestampGreaterThanPath(Timestamp),
+    TimestampGreaterThanPath(ReferencePath),
 
+    #[serde(deserialize_with = "deserialize_timestamp")]
     TimestampLessThanEquals(Timestamp),
-    TimestampLessThanEqualsPath(Timestamp),
+    TimestampLessThanEqualsPath(ReferencePath),
 
+    #[serde(deserialize_with = "deserialize_timestamp")]
     TimestampGreaterThanEquals(Timestamp),
-    TimestampGreaterThanEqualsPath(Timestamp),
+    TimestampGreaterThanEqualsPath(ReferencePath),
 
-    IsNull,
-    IsPresent,
-    IsNumeric,
-    IsString,
-    IsBoolean,
-    IsTimestamp,
+    IsNull(bool),
+    IsPresent(bool),
+    IsNumeric(bool),
+    IsString(bool),
+    IsBoolean(bool),
+    IsTimestamp(bool),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Kind {
+    String,
+    Numeric,
+    Boolean,
+    Timestamp,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Comparison {
+    Equals,
+    LessThan,
+    GreaterThan,
+    LessThanEquals,
+    GreaterThanEquals,
+}
+
+impl Comparison {
+    fn holds(self, ordering: Ordering) -> bool {
+        match self {
+            Comparison::Equals => ordering.is_eq(),
+            Comparison::LessThan => ordering.is_lt(),
+            Comparison::GreaterThan => ordering.is_gt(),
+            Comparison::LessThanEquals => ordering.is_le(),
+            Comparison::GreaterThanEquals => ordering.is_ge(),
+        }
+    }
+}
+
+/// The value a comparison operator compares the variable to.
+enum Operand<'o> {
+    Literal(Value),
+    Path(&'o ReferencePath),
+}
+
+impl Operation {
+    /// Tests the value selected by `variable`, which must be present unless the operator is
+    /// "IsPresent".
+    fn evaluate(&self, variable: &ReferencePath, input: &Value, context: &Value) -> Result<bool, ExecutionError> {
+        use Comparison::*;
+        use Operation::*;
+
+        let value = variable.get(input, context);
+        if let IsPresent(expected) = self {
+            return Ok(value.is_some() == *expected);
+        }
+        let value = value.ok_or_else(|| ExecutionError::runtime(format!(
+            "Invalid path '{variable}': The choice state's condition path references an invalid value"
+        )))?;
+        let string = |string: &String| Operand::Literal(Value::String(string.clone()));
+        let number = |number: &Number| Operand::Literal(Value::Number(number.clone()));
+        let (kind, comparison, operand) = match self {
+            IsPresent(_) => unreachable!("IsPresent is handled before resolving the variable"),
+            IsNull(expected) => return Ok(value.is_null() == *expected),
+            IsNumeric(expected) => return Ok(value.is_number() == *expected),
+            IsString(expected) => return Ok(value.is_string() == *expected),
+            IsBoolean(expected) => return Ok(value.is_boolean() == *expected),
+            IsTimestamp(expected) => return Ok(timestamp(value).is_some() == *expected),
+            StringMatches(pattern) => return match value {
+                Value::String(value) => string_matches(value, pattern),
+                _ => Ok(false),
+            },
+
+            StringEquals(v) => (Kind::String, Equals, string(v)),
+            StringEqualsPath(p) => (Kind::String, Equals, Operand::Path(p)),
+            StringLessThan(v) => (Kind::String, LessThan, string(v)),
+            StringLessThanPath(p) => (Kind::String, LessThan, Operand::Path(p)),
+            StringGreaterThan(v) => (Kind::String, GreaterThan, string(v)),
+            StringGreaterThanPath(p) => (Kind::String, GreaterThan, Operand::Path(p)),
+            StringLessThanEquals(v) => (Kind::String, LessThanEquals, string(v)),
+            StringLessThanEqualsPath(p) => (Kind::String, LessThanEquals, Operand::Path(p)),
+            StringGreaterThanEquals(v) => (Kind::String, GreaterThanEquals, string(v)),
+            StringGreaterThanEqualsPath(p) => (Kind::String, GreaterThanEquals, Operand::Path(p)),
+
+            NumericEquals(v) => (Kind::Numeric, Equals, number(v)),
+            NumericEqualsPath(p) => (Kind::Numeric, Equals, Operand::Path(p)),
+            NumericLessThan(v) => (Kind::Numeric, LessThan, number(v)),
+            NumericLessThanPath(p) => (Kind::Numeric, LessThan, Operand::Path(p)),
+            NumericGreaterThan(v) => (Kind::Numeric, GreaterThan, number(v)),
+            NumericGreaterThanPath(p) => (Kind::Numeric, GreaterThan, Operand::Path(p)),
+            NumericLessThanEquals(v) => (Kind::Numeric, LessThanEquals, number(v)),
+            NumericLessThanEqualsPath(p) => (Kind::Numeric, LessThanEquals, Operand::Path(p)),
+            NumericGreaterThanEquals(v) => (Kind::Numeric, GreaterThanEquals, number(v)),
+            NumericGreaterThanEqualsPath(p) => (Kind::Numeric, GreaterThanEquals, Operand::Path(p)),
+
+            BooleanEquals(v) => (Kind::Boolean, Equals, Operand::Literal(Value::Bool(*v))),
+            BooleanEqualsPath(p) => (Kind::Boolean, Equals, Operand::Path(p)),
+
+            TimestampEquals(v) => (Kind::Timestamp, Equals, string(v)),
+            TimestampEqualsPath(p) => (Kind::Timestamp, Equals, Operand::Path(p)),
+            TimestampLessThan(v) => (Kind::Timestamp, LessThan, string(v)),
+            TimestampLessThanPath(p) => (Kind::Timestamp, LessThan, Operand::Path(p)),
+            TimestampGreaterThan(v) => (Kind::Timestamp, GreaterThan, string(v)),
+            TimestampGreaterThanPath(p) => (Kind::Timestamp, GreaterThan, Operand::Path(p)),
+            TimestampLessThanEquals(v) => (Kind::Timestamp, LessThanEquals, string(v)),
+            TimestampLessThanEqualsPath(p) => (Kind::Timestamp, LessThanEquals, Operand::Path(p)),
+            TimestampGreaterThanEquals(v) => (Kind::Timestamp, GreaterThanEquals, string(v)),
+            TimestampGreaterThanEqualsPath(p) => (Kind::Timestamp, GreaterThanEquals, Operand::Path(p)),
+        };
+        let operand = match &operand {
+            Operand::Literal(operand) => operand,
+            Operand::Path(path) => path.get(input, context).ok_or_else(|| ExecutionError::runtime(format!(
+                "Invalid path '{path}': The choice state's comparison path references an invalid value"
+            )))?,
+        };
+        // Values of a different type than the operator never match.
+        Ok(compare(kind, value, operand).is_some_and(|ordering| comparison.holds(ordering)))
+    }
+}
+
+fn compare(kind: Kind, value: &Value, operand: &Value) -> Option<Ordering> {
+    match (kind, value, operand) {
+        (Kind::String, Value::String(value), Value::String(operand)) => Some(value.cmp(operand)),
+        (Kind::Numeric, Value::Number(value), Value::Number(operand)) => compare_numbers(value, operand),
+        (Kind::Boolean, Value::Bool(value), Value::Bool(operand)) => Some(value.cmp(operand)),
+        (Kind::Timestamp, value, operand) => Some(timestamp(value)?.cmp(&timestamp(operand)?)),
+        _ => None,
+    }
+}
+
+/// Compares integers exactly, as converting them to `f64` loses precision above 2^53.
+fn compare_numbers(value: &Number, operand: &Number) -> Option<Ordering> {
+    let integer = |number: &Number| number.as_i64().map(i128::from).or_else(|| number.as_u64().map(i128::from));
+    match (integer(value), integer(operand)) {
+        (Some(value), Some(operand)) => Some(value.cmp(&operand)),
+        _ => value.as_f64()?.partial_cmp(&operand.as_f64()?),
+    }
+}
+
+fn timestamp(value: &Value) -> Option<DateTime<FixedOffset>> {
+    value.as_str().and_then(|value| DateTime::parse_from_rfc3339(value).ok())
+}
+
+/// Matches a value against a "StringMatches" pattern, see [Operation::StringMatches].
+fn string_matches(value: &str, pattern: &str) -> Result<bool, ExecutionError> {
+    // `None` stands for a wildcard, `Some` for a literal character.
+    let mut tokens = vec![];
+    let mut chars = pattern.chars();
+    while let Some(c) = chars.next() {
+        tokens.push(match c {
+            '*' => None,
+            '\\' => match chars.next() {
+                Some(escaped @ ('*' | '\\')) => Some(escaped),
+                _ => return Err(ExecutionError::runtime(format!("The StringMatches pattern '{pattern}' contains an open escape"))),
+            },
+            c => Some(c),
+        });
+    }
+    let value: Vec<char> = value.chars().collect();
+    // Greedy matching, backtracking to the last wildcard on a mismatch.
+    let (mut v, mut t) = (0, 0);
+    let mut backtrack: Option<(usize, usize)> = None;
+    while v < value.len() {
+        match tokens.get(t) {
+            Some(None) => {
+                backtrack = Some((t, v));
+                t += 1;
+            }
+            Some(Some(c)) if *c == value[v] => {
+                t += 1;
+                v += 1;
+            }
+            _ => match backtrack {
+                Some((wildcard, matched)) => {
+                    t = wildcard + 1;
+                    v = matched + 1;
+                    backtrack = Some((wildcard, matched + 1));
+                }
+                None => return Ok(false),
+            },
+        }
+    }
+    Ok(tokens[t..].iter().all(Option::is_none))
 }
 
 #[derive(Deserialize, Debug, PartialEq, Eq)]
 enum ComposedExpression {
     Not(Box<ChoiceExpression>),
-    And(Vec<Box<ChoiceExpression>>),
-    Or(Vec<Box<ChoiceExpression>>),
+    And(Vec<ChoiceExpression>),
+    Or(Vec<ChoiceExpression>),
 }
 
 #[derive(Deserialize, Debug, PartialEq, Eq)]
@@ -92,7 +278,7 @@ enum ComposedExpression {
 enum ChoiceExpression {
     #[serde(rename_all = "PascalCase")]
     BooleanExpression {
-        variable: MyJsonPath,
+        variable: ReferencePath,
 
         #[serde(flatten)]
         operation: Operation,
@@ -100,6 +286,31 @@ enum ChoiceExpression {
     ComposedExpression(ComposedExpression)
 }
 
+impl ChoiceExpression {
+    fn evaluate(&self, input: &Value, context: &Value) -> Result<bool, ExecutionError> {
+        match self {
+            ChoiceExpression::BooleanExpression { variable, operation } => operation.evaluate(variable, input, context),
+            ChoiceExpression::ComposedExpression(ComposedExpression::Not(expression)) => Ok(!expression.evaluate(input, context)?),
+            ChoiceExpression::ComposedExpression(ComposedExpression::And(expressions)) => {
+                for expression in expressions {
+                    if !expression.evaluate(input, context)? {
+                        return Ok(false);
+                    }
+                }
+                Ok(true)
+            }
+            ChoiceExpression::ComposedExpression(ComposedExpression::Or(expressions)) => {
+                for expression in expressions {
+                    if expression.evaluate(input, context)? {
+                        return Ok(true);
+                    }
+                }
+                Ok(false)
+            }
+        }
+    }
+}
+
 #[derive(Deserialize, Debug, PartialEq, Eq)]
 #[serde(rename_all = "PascalCase")]
 pub struct ChoiceRule {
@@ -107,3 +318,149 @@ pub struct ChoiceRule {
     expression: ChoiceExpression,
     next: String,
 }
+
+impl ChoiceRule {
+    /// The state to transition to when the rule matches.
+    pub fn next(&self) -> &str {
+        &self.next
+    }
+
+    /// Evaluates the rule against the effective input of the Choice state.
+    ///
+    /// Fails with `States.Runtime` if a path of the rule can't be resolved or if a "StringMatches"
+    /// pattern is invalid.
+    pub fn matches(&self, input: &Value, context: &Value) -> Result<bool, ExecutionError> {
+        self.expression.evaluate(input, context)
+    }
+}
+
+/// "Choices" MUST be a non-empty array of Choice Rules.
+pub(crate) fn deserialize_choices<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Vec<ChoiceRule>, D::Error> {
+    let choices = Vec::<ChoiceRule>::deserialize(deserializer)?;
+    if choices.is_empty() {
+        return Err(D::Error::custom("\"Choices\" must contain at least one Choice Rule"));
+    }
+    Ok(choices)
+}
+
+/// Returns the first rule of `rules` matching the input, in order.
+pub fn first_match<'r>(rules: &'r [ChoiceRule], input: &Value, context: &Value) -> Result<Option<&'r ChoiceRule>, ExecutionError> {
+    for rule in rules {
+        if rule.matches(input, context)? {
+            return Ok(Some(rule));
+        }
+    }
+    Ok(None)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rstest::*;
+    use serde_json::json;
+    use anyhow::Result;
+    use crate::asl::error_handling::ErrorName;
+
+    fn input() -> Value {
+        json!({
+            "string": "foo23.log",
+            "number": 10,
+            "float": 1.5,
+            "bool": true,
+            "null": null,
+            "timestamp": "2024-01-01T00:00:00Z",
+            "otherTimestamp": "2024-01-01T01:00:00+01:00",
+            "otherString": "foo",
+            "otherNumber": 20,
+            "big": 9007199254740992u64,
+            "negative": -1,
+            "escaped": "a*b\\c"
+        })
+    }
+
+    #[rstest]
+    #[case::string_equals(json!({ "Variable": "$.string", "StringEquals": "foo23.log" }), true)]
+    #[case::string_equals_other_type(json!({ "Variable": "$.number", "StringEquals": "10" }), false)]
+    #[case::string_less_than(json!({ "Variable": "$.string", "StringLessThan": "goo" }), true)]
+    #[case::string_greater_than_path(json!({ "Variable": "$.string", "StringGreaterThanPath": "$.otherString" }), true)]
+    #[case::string_less_than_equals(json!({ "Variable": "$.string", "StringLessThanEquals": "foo23.log" }), true)]
+    #[case::string_greater_than_equals(json!({ "Variable": "$.string", "StringGreaterThanEquals": "foo3" }), false)]
+    #[case::string_matches(json!({ "Variable": "$.string", "StringMatches": "foo*.log" }), true)]
+    #[case::string_matches_any(json!({ "Variable": "$.string", "StringMatches": "*.log" }), true)]
+    #[case::string_matches_many(json!({ "Variable": "$.string", "StringMatches": "f*2*.*" }), true)]
+    #[case::string_matches_no_match(json!({ "Variable": "$.string", "StringMatches": "foo*.txt" }), false)]
+    #[case::string_matches_escapes(json!({ "Variable": "$.escaped", "StringMatches": "a\\*b\\\\c" }), true)]
+    #[case::string_matches_escaped_wildcard(json!({ "Variable": "$.string", "StringMatches": "foo\\*.log" }), false)]
+    #[case::numeric_equals(json!({ "Variable": "$.number", "NumericEquals": 10 }), true)]
+    #[case::numeric_equals_float(json!({ "Variable": "$.float", "NumericEquals": 1.5 }), true)]
+    #[case::numeric_less_than(json!({ "Variable": "$.float", "NumericLessThan": 2 }), true)]
+    #[case::numeric_greater_than(json!({ "Variable": "$.number", "NumericGreaterThan": 10 }), false)]
+    #[case::numeric_less_than_path(json!({ "Variable": "$.number", "NumericLessThanPath": "$.otherNumber" }), true)]
+    #[case::numeric_less_than_equals(json!({ "Variable": "$.number", "NumericLessThanEquals": 10 }), true)]
+    #[case::numeric_greater_than_equals_path(json!({ "Variable": "$.number", "NumericGreaterThanEqualsPath": "$.otherNumber" }), false)]
+    #[case::numeric_equals_big_integer(json!({ "Variable": "$.big", "NumericEquals": 9007199254740993u64 }), false)]
+    #[case::numeric_greater_than_big_integer(json!({ "Variable": "$.big", "NumericGreaterThan": 9007199254740991i64 }), true)]
+    #[case::numeric_less_than_negative(json!({ "Variable": "$.negative", "NumericLessThan": 18446744073709551615u64 }), true)]
+    #[case::numeric_other_type(json!({ "Variable": "$.string", "NumericEquals": 10 }), false)]
+    #[case::boolean_equals(json!({ "Variable": "$.bool", "BooleanEquals": true }), true)]
+    #[case::boolean_equals_path(json!({ "Variable": "$.bool", "BooleanEqualsPath": "$.null" }), false)]
+    #[case::timestamp_equals(json!({ "Variable": "$.timestamp", "TimestampEquals": "2024-01-01T00:00:00+00:00" }), true)]
+    #[case::timestamp_equals_path(json!({ "Variable": "$.timestamp", "TimestampEqualsPath": "$.otherTimestamp" }), true)]
+    #[case::timestamp_less_than(json!({ "Variable": "$.timestamp", "TimestampLessThan": "2024-01-02T00:00:00Z" }), true)]
+    #[case::timestamp_greater_than(json!({ "Variable": "$.timestamp", "TimestampGreaterThan": "2024-01-02T00:00:00Z" }), false)]
+    #[case::timestamp_not_a_timestamp(json!({ "Variable": "$.string", "TimestampLessThanEquals": "2024-01-02T00:00:00Z" }), false)]
+    #[case::is_null(json!({ "Variable": "$.null", "IsNull": true }), true)]
+    #[case::is_present(json!({ "Variable": "$.missing", "IsPresent": false }), true)]
+    #[case::is_numeric(json!({ "Variable": "$.float", "IsNumeric": true }), true)]
+    #[case::is_string(json!({ "Variable": "$.number", "IsString": true }), false)]
+    #[case::is_boolean(json!({ "Variable": "$.bool", "IsBoolean": false }), false)]
+    #[case::is_timestamp(json!({ "Variable": "$.timestamp", "IsTimestamp": true }), true)]
+    #[case::not(json!({ "Not": { "Variable": "$.bool", "BooleanEquals": true } }), false)]
+    #[case::and(json!({ "And": [{ "Variable": "$.bool", "BooleanEquals": true }, { "Variable": "$.number", "NumericEquals": 11 }] }), false)]
+    #[case::or(json!({ "Or": [{ "Variable": "$.bool", "BooleanEquals": false }, { "Variable": "$.number", "NumericEquals": 10 }] }), true)]
+    #[case::short_circuit(json!({ "And": [{ "Variable": "$.missing", "IsPresent": true }, { "Variable": "$.missing", "IsNull": true }] }), false)]
+    fn evaluate(#[case] mut rule: Value, #[case] expected: bool) -> Result<()> {
+        rule["Next"] = json!("Next");
+        let rule: ChoiceRule = serde_json::from_value(rule)?;
+
+        assert_eq!(rule.matches(&input(), &Value::Null)?, expected);
+        Ok(())
+    }
+
+    #[rstest]
+    #[case::missing_variable(json!({ "Variable": "$.missing", "IsNull": true }))]
+    #[case::missing_path(json!({ "Variable": "$.number", "NumericEqualsPath": "$.missing" }))]
+    #[case::open_escape(json!({ "Variable": "$.string", "StringMatches": "foo\\.log" }))]
+    #[case::trailing_open_escape(json!({ "Variable": "$.string", "StringMatches": "foo\\" }))]
+    fn evaluate_fails(#[case] mut rule: Value) -> Result<()> {
+        rule["Next"] = json!("Next");
+        let rule: ChoiceRule = serde_json::from_value(rule)?;
+
+        let error = rule.matches(&input(), &Value::Null).unwrap_err();
+
+        assert_eq!(error.error, Some(ErrorName::StatesRuntime));
+        Ok(())
+    }
+
+    #[rstest]
+    #[case::timestamp_equals(json!({ "Variable": "$.timestamp", "TimestampEquals": "nope" }))]
+    #[case::timestamp_less_than_date(json!({ "Variable": "$.timestamp", "TimestampLessThan": "2024-01-01" }))]
+    fn parse_invalid_timestamp(#[case] mut rule: Value) {
+        rule["Next"] = json!("Next");
+        assert!(serde_json::from_value::<ChoiceRule>(rule).is_err());
+    }
+
+    #[rstest]
+    fn first_match_in_order() -> Result<()> {
+        let rules: Vec<ChoiceRule> = serde_json::from_value(json!([
+            { "Variable": "$.number", "NumericGreaterThan": 20, "Next": "First" },
+            { "Variable": "$.number", "NumericGreaterThan": 5, "Next": "Second" },
+            { "Variable": "$.number", "NumericGreaterThan": 0, "Next": "Third" }
+        ]))?;
+
+        let rule = first_match(&rules, &input(), &Value::Null)?;
+
+        assert_eq!(rule.map(ChoiceRule::next), Some("Second"));
+        Ok(())
+    }
+}
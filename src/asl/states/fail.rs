@@ -1,14 +1,44 @@
-use serde::Deserialize;
-use crate::asl::types::MyJsonPath;
+use serde::{Deserialize, Deserializer};
+use crate::asl::types::alternative_fields;
 
-#[derive(Deserialize, Debug, PartialEq, Eq)]
+/// "ErrorPath" and "CausePath" are Reference Paths or Intrinsic Functions, so they're not modeled as
+/// a [JsonPath](crate::asl::json_path::JsonPath).
+#[derive(Debug, PartialEq, Eq)]
 pub enum FailStateErrorField {
     Error(String),
-    ErrorPath(MyJsonPath)
+    ErrorPath(String)
 }
 
-#[derive(Deserialize, Debug, PartialEq, Eq)]
+#[derive(Debug, PartialEq, Eq)]
 pub enum FailStateCauseField {
     Cause(String),
-    CausePath(MyJsonPath)
+    CausePath(String)
+}
+
+// Each pair of alternative fields is deserialized apart, as deserializing a flattened struct
+// takes the fields it reads from the state.
+#[derive(Deserialize)]
+#[serde(rename_all = "PascalCase")]
+struct ErrorFields {
+    error: Option<String>,
+    error_path: Option<String>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "PascalCase")]
+struct CauseFields {
+    cause: Option<String>,
+    cause_path: Option<String>,
+}
+
+/// Deserializes the flattened "Error" or "ErrorPath" field.
+pub(crate) fn deserialize_error<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Option<FailStateErrorField>, D::Error> {
+    let fields = ErrorFields::deserialize(deserializer)?;
+    alternative_fields(fields.error.map(FailStateErrorField::Error), fields.error_path.map(FailStateErrorField::ErrorPath), ["Error", "ErrorPath"])
+}
+
+/// Deserializes the flattened "Cause" or "CausePath" field.
+pub(crate) fn deserialize_cause<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Option<FailStateCauseField>, D::Error> {
+    let fields = CauseFields::deserialize(deserializer)?;
+    alternative_fields(fields.cause.map(FailStateCauseField::Cause), fields.cause_path.map(FailStateCauseField::CausePath), ["Cause", "CausePath"])
 }
@@ -1,7 +1,11 @@
 use std::collections::HashMap;
-use serde::Deserialize;
-use serde_json::{Value};
-use crate::asl::types::{MyJsonPath, Payload};
+use serde::{Deserialize, Deserializer};
+use serde::de::Error;
+use serde_json::{json, Map, Value};
+use crate::asl::execution::ExecutionError;
+use crate::asl::json_path::ReferencePath;
+use crate::asl::payload_template::PayloadTemplate;
+use crate::asl::types::deserialize_positive_integer;
 
 
 #[derive(Deserialize, Debug, PartialEq, Eq)]
@@ -9,44 +13,514 @@ use crate::asl::types::{MyJsonPath, Payload};
 pub struct MapStateIterator {
     start_at: String,
     states: HashMap<String, crate::asl::state_machine::State>,
-    processor_config: Option<Value>,
+    #[serde(default)]
+    processor_config: ProcessorConfig,
+}
+
+impl MapStateIterator {
+    pub fn start_at(&self) -> &str {
+        &self.start_at
+    }
+
+    pub fn states(&self) -> &HashMap<String, crate::asl::state_machine::State> {
+        &self.states
+    }
 
+    pub fn processor_config(&self) -> &ProcessorConfig {
+        &self.processor_config
+    }
 }
 
-#[derive(Deserialize, Debug, PartialEq, Eq)]
+/// How the iterations of a Map state are processed.
+#[derive(Deserialize, Debug, Default, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "UPPERCASE")]
+pub enum ProcessorMode {
+    /// Each iteration runs within the execution of the Map state, and shares its Context Object.
+    #[default]
+    Inline,
+    /// Each iteration runs as a child execution, with its own id, history and Context Object.
+    Distributed,
+}
+
+/// The type of the child executions of a distributed Map state.
+///
+/// Both types run the same way locally, the type is only recorded on each child execution.
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "UPPERCASE")]
+pub enum ExecutionType {
+    Express,
+    Standard,
+}
+
+/// The "ProcessorConfig" of the "ItemProcessor" of a Map state.
+#[derive(Deserialize, Debug, Default, Clone, Copy, PartialEq, Eq)]
+#[serde(try_from = "RawProcessorConfig")]
+pub struct ProcessorConfig {
+    mode: ProcessorMode,
+    execution_type: Option<ExecutionType>,
+}
+
+#[derive(Deserialize)]
 #[serde(rename_all = "PascalCase")]
-pub struct ResultWriterConfiguration {}
+struct RawProcessorConfig {
+    #[serde(default)]
+    mode: ProcessorMode,
+    execution_type: Option<ExecutionType>,
+}
 
-#[derive(Deserialize, Debug, PartialEq, Eq)]
-pub enum ToleratedFailurePercentage {
-    ToleratedFailurePercentage(u32),
-    ToleratedFailurePercentagePath(MyJsonPath),
+impl TryFrom<RawProcessorConfig> for ProcessorConfig {
+    type Error = String;
+
+    fn try_from(raw: RawProcessorConfig) -> Result<Self, Self::Error> {
+        if raw.mode == ProcessorMode::Distributed && raw.execution_type.is_none() {
+            return Err("\"ProcessorConfig\" must have \"ExecutionType\" when \"Mode\" is \"DISTRIBUTED\"".to_string());
+        }
+        Ok(ProcessorConfig {
+            mode: raw.mode,
+            execution_type: raw.execution_type,
+        })
+    }
+}
+
+impl ProcessorConfig {
+    pub fn mode(&self) -> ProcessorMode {
+        self.mode
+    }
+
+    pub fn execution_type(&self) -> Option<ExecutionType> {
+        self.execution_type
+    }
 }
 
+/// The "ResultWriter" of a Map state, which writes the results of its iterations to an external
+/// destination, the state output being a reference to what was written.
 #[derive(Deserialize, Debug, PartialEq, Eq)]
-pub enum ToleratedFailureCount {
-    ToleratedFailureCount(u32),
-    ToleratedFailureCountPath(MyJsonPath),
+#[serde(rename_all = "PascalCase")]
+pub struct ResultWriterConfiguration {
+    resource: String,
+    parameters: Option<PayloadTemplate>,
 }
 
+impl ResultWriterConfiguration {
+    /// The URI identifying the destination of the results, e.g. `arn:aws:states:::s3:putObject`.
+    pub fn resource(&self) -> &str {
+        &self.resource
+    }
+
+    pub fn parameters(&self) -> Option<&PayloadTemplate> {
+        self.parameters.as_ref()
+    }
+}
+
+/// The "ItemReader" of a Map state, which reads the items from an external source instead of
+/// the input of the state.
 #[derive(Deserialize, Debug, PartialEq, Eq)]
+#[serde(rename_all = "PascalCase")]
+pub struct ItemReaderConfiguration {
+    resource: String,
+    parameters: Option<PayloadTemplate>,
+    #[serde(default)]
+    reader_config: ReaderConfig,
+}
+
+impl ItemReaderConfiguration {
+    /// The URI identifying the source to read the items from, e.g. `arn:aws:states:::s3:getObject`.
+    pub fn resource(&self) -> &str {
+        &self.resource
+    }
+
+    pub fn parameters(&self) -> Option<&PayloadTemplate> {
+        self.parameters.as_ref()
+    }
+
+    pub fn reader_config(&self) -> &ReaderConfig {
+        &self.reader_config
+    }
+}
+
+/// The format of the items read by an "ItemReader".
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "UPPERCASE")]
+pub enum InputType {
+    Csv,
+    Json,
+    /// JSON Lines, one item per line.
+    Jsonl,
+    Manifest,
+    Parquet,
+}
+
+/// Where the headers of a CSV file come from.
+#[derive(Deserialize, Debug, Default, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
+pub enum CsvHeaderLocation {
+    /// The first row of the file holds the headers.
+    #[default]
+    FirstRow,
+    /// The headers are given by the "CSVHeaders" field.
+    Given,
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum MaxItems {
+    MaxItems(u64),
+    MaxItemsPath(ReferencePath),
+}
+
+#[derive(Deserialize, Debug, Default, PartialEq, Eq)]
+#[serde(try_from = "RawReaderConfig")]
+pub struct ReaderConfig {
+    input_type: Option<InputType>,
+    csv_header_location: CsvHeaderLocation,
+    csv_headers: Option<Vec<String>>,
+    max_items: Option<MaxItems>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "PascalCase")]
+struct RawReaderConfig {
+    input_type: Option<InputType>,
+    #[serde(rename = "CSVHeaderLocation", default)]
+    csv_header_location: CsvHeaderLocation,
+    #[serde(rename = "CSVHeaders")]
+    csv_headers: Option<Vec<String>>,
+    #[serde(default, deserialize_with = "deserialize_positive_integer")]
+    max_items: Option<u64>,
+    max_items_path: Option<ReferencePath>,
+}
+
+impl TryFrom<RawReaderConfig> for ReaderConfig {
+    type Error = String;
+
+    fn try_from(raw: RawReaderConfig) -> Result<Self, Self::Error> {
+        let max_items = match (raw.max_items, raw.max_items_path) {
+            (Some(_), Some(_)) => return Err("\"ReaderConfig\" can't have both \"MaxItems\" and \"MaxItemsPath\"".to_string()),
+            (Some(max), None) => Some(MaxItems::MaxItems(max)),
+            (None, Some(path)) => Some(MaxItems::MaxItemsPath(path)),
+            (None, None) => None,
+        };
+        if raw.csv_header_location == CsvHeaderLocation::Given && raw.csv_headers.is_none() {
+            return Err("\"ReaderConfig\" must have \"CSVHeaders\" when \"CSVHeaderLocation\" is \"GIVEN\"".to_string());
+        }
+        Ok(ReaderConfig {
+            input_type: raw.input_type,
+            csv_header_location: raw.csv_header_location,
+            csv_headers: raw.csv_headers,
+            max_items,
+        })
+    }
+}
+
+impl ReaderConfig {
+    pub fn input_type(&self) -> Option<InputType> {
+        self.input_type
+    }
+
+    pub fn csv_header_location(&self) -> CsvHeaderLocation {
+        self.csv_header_location
+    }
+
+    pub fn csv_headers(&self) -> Option<&[String]> {
+        self.csv_headers.as_deref()
+    }
+
+    /// Resolves the maximum number of items to read against the effective input of the Map state.
+    pub(crate) fn max_items(&self, input: &Value, context: &Value) -> Result<Option<u64>, ExecutionError> {
+        match &self.max_items {
+            None => Ok(None),
+            Some(MaxItems::MaxItems(max)) => Ok(Some(*max)),
+            Some(MaxItems::MaxItemsPath(path)) => positive_integer(path, "MaxItemsPath", input, context).map(Some),
+        }
+    }
+}
+
+/// The failures a Map state tolerates, given by "ToleratedFailureCount" or
+/// "ToleratedFailureCountPath", and by "ToleratedFailurePercentage" or
+/// "ToleratedFailurePercentagePath".
+#[derive(Deserialize, Debug, Default, PartialEq)]
+#[serde(rename_all = "PascalCase")]
+pub struct ToleratedFailures {
+    #[serde(default, deserialize_with = "deserialize_tolerated_failure_count")]
+    tolerated_failure_count: Option<u64>,
+    tolerated_failure_count_path: Option<ReferencePath>,
+    #[serde(default, deserialize_with = "deserialize_tolerated_failure_percentage")]
+    tolerated_failure_percentage: Option<f64>,
+    tolerated_failure_percentage_path: Option<ReferencePath>,
+}
+
+// JSON has no NaN, so the percentage is always equal to itself.
+impl Eq for ToleratedFailures {}
+
+impl ToleratedFailures {
+    /// Resolves the count, which MUST be a non-negative integer, against the effective input.
+    pub(crate) fn count(&self, input: &Value, context: &Value) -> Result<Option<u64>, ExecutionError> {
+        match (self.tolerated_failure_count, &self.tolerated_failure_count_path) {
+            (Some(count), _) => Ok(Some(count)),
+            (None, Some(path)) => path.get(input, context)
+                .and_then(Value::as_u64)
+                .map(Some)
+                .ok_or_else(|| ExecutionError::runtime(format!(
+                    "The JSONPath '{path}' specified for the field 'ToleratedFailureCountPath' must select a non-negative integer"
+                ))),
+            (None, None) => Ok(None),
+        }
+    }
+
+    /// Resolves the percentage, which MUST be between 0 and 100, against the effective input.
+    pub(crate) fn percentage(&self, input: &Value, context: &Value) -> Result<Option<f64>, ExecutionError> {
+        match (self.tolerated_failure_percentage, &self.tolerated_failure_percentage_path) {
+            (Some(percentage), _) => Ok(Some(percentage)),
+            (None, Some(path)) => path.get(input, context)
+                .and_then(Value::as_f64)
+                .filter(|percentage| (0.0..=100.0).contains(percentage))
+                .map(Some)
+                .ok_or_else(|| ExecutionError::runtime(format!(
+                    "The JSONPath '{path}' specified for the field 'ToleratedFailurePercentagePath' must select a number between 0 and 100"
+                ))),
+            (None, None) => Ok(None),
+        }
+    }
+}
+
+fn deserialize_tolerated_failure_count<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Option<u64>, D::Error> {
+    match Value::deserialize(deserializer)? {
+        Value::Number(count) if count.is_u64() => Ok(count.as_u64()),
+        value => Err(D::Error::custom(format!("\"ToleratedFailureCount\" must be a non-negative integer, but it is {value}"))),
+    }
+}
+
+fn deserialize_tolerated_failure_percentage<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Option<f64>, D::Error> {
+    match Value::deserialize(deserializer)? {
+        Value::Number(percentage) if percentage.as_f64().is_some_and(|percentage| (0.0..=100.0).contains(&percentage)) => Ok(percentage.as_f64()),
+        value => Err(D::Error::custom(format!("\"ToleratedFailurePercentage\" must be a number between 0 and 100, but it is {value}"))),
+    }
+}
+
+#[derive(Debug, PartialEq, Eq)]
 pub enum MaxItemsPerBatch {
-    MaxItemsPerBatch(u32),
-    MaxItemsPerBatchPath(MyJsonPath),
+    MaxItemsPerBatch(u64),
+    MaxItemsPerBatchPath(ReferencePath),
 }
 
-#[derive(Deserialize, Debug, PartialEq, Eq)]
+#[derive(Debug, PartialEq, Eq)]
 pub enum MaxInputBytesPerBatch {
-    MaxInputBytesPerBatch(u32),
-    MaxInputBytesPerBatchPath(MyJsonPath),
+    MaxInputBytesPerBatch(u64),
+    MaxInputBytesPerBatchPath(ReferencePath),
 }
 
+/// Groups the items of a Map state into batches, each processed by a single child execution
+/// whose input is `{ "BatchInput": ..., "Items": [...] }`.
+///
+/// A batch is closed once adding an item would exceed either "MaxItemsPerBatch" or
+/// "MaxInputBytesPerBatch", the latter being measured on the serialized input of the child.
 #[derive(Deserialize, Debug, PartialEq, Eq)]
-#[serde(rename_all = "PascalCase")]
+#[serde(try_from = "RawItemBatcherConfiguration")]
 pub struct ItemBatcherConfiguration {
-    batch_input: Option<Payload>,
-    #[serde(flatten)]
+    batch_input: Option<PayloadTemplate>,
     max_items_per_batch: Option<MaxItemsPerBatch>,
-    #[serde(flatten)]
     max_input_bytes_per_batch: Option<MaxInputBytesPerBatch>,
 }
+
+/// The fields of an "ItemBatcher" as they appear in the definition, where both forms of a limit
+/// may be present.
+#[derive(Deserialize)]
+#[serde(rename_all = "PascalCase")]
+struct RawItemBatcherConfiguration {
+    batch_input: Option<PayloadTemplate>,
+    #[serde(default, deserialize_with = "deserialize_positive_integer")]
+    max_items_per_batch: Option<u64>,
+    max_items_per_batch_path: Option<ReferencePath>,
+    #[serde(default, deserialize_with = "deserialize_positive_integer")]
+    max_input_bytes_per_batch: Option<u64>,
+    max_input_bytes_per_batch_path: Option<ReferencePath>,
+}
+
+impl TryFrom<RawItemBatcherConfiguration> for ItemBatcherConfiguration {
+    type Error = String;
+
+    fn try_from(raw: RawItemBatcherConfiguration) -> Result<Self, Self::Error> {
+        let max_items_per_batch = match (raw.max_items_per_batch, raw.max_items_per_batch_path) {
+            (Some(_), Some(_)) => return Err("\"ItemBatcher\" can't have both \"MaxItemsPerBatch\" and \"MaxItemsPerBatchPath\"".to_string()),
+            (Some(max), None) => Some(MaxItemsPerBatch::MaxItemsPerBatch(max)),
+            (None, Some(path)) => Some(MaxItemsPerBatch::MaxItemsPerBatchPath(path)),
+            (None, None) => None,
+        };
+        let max_input_bytes_per_batch = match (raw.max_input_bytes_per_batch, raw.max_input_bytes_per_batch_path) {
+            (Some(_), Some(_)) => return Err("\"ItemBatcher\" can't have both \"MaxInputBytesPerBatch\" and \"MaxInputBytesPerBatchPath\"".to_string()),
+            (Some(max), None) => Some(MaxInputBytesPerBatch::MaxInputBytesPerBatch(max)),
+            (None, Some(path)) => Some(MaxInputBytesPerBatch::MaxInputBytesPerBatchPath(path)),
+            (None, None) => None,
+        };
+        Ok(ItemBatcherConfiguration {
+            batch_input: raw.batch_input,
+            max_items_per_batch,
+            max_input_bytes_per_batch,
+        })
+    }
+}
+
+impl ItemBatcherConfiguration {
+    /// Groups the items into batches, resolving the limits and the "BatchInput" against the
+    /// effective input of the Map state.
+    pub(crate) fn batch(&self, items: Vec<Value>, input: &Value, context: &Value) -> Result<Vec<Value>, ExecutionError> {
+        let max_items = match &self.max_items_per_batch {
+            None => None,
+            Some(MaxItemsPerBatch::MaxItemsPerBatch(max)) => Some(*max),
+            Some(MaxItemsPerBatch::MaxItemsPerBatchPath(path)) => Some(positive_integer(path, "MaxItemsPerBatchPath", input, context)?),
+        };
+        let max_bytes = match &self.max_input_bytes_per_batch {
+            None => None,
+            Some(MaxInputBytesPerBatch::MaxInputBytesPerBatch(max)) => Some(*max),
+            Some(MaxInputBytesPerBatch::MaxInputBytesPerBatchPath(path)) => Some(positive_integer(path, "MaxInputBytesPerBatchPath", input, context)?),
+        };
+        let batch_input = match &self.batch_input {
+            Some(batch_input) => batch_input.render(input, context)?,
+            None => Value::Object(Map::new()),
+        };
+        // The size of `{"BatchInput":...,"Items":[]}`, to which each item adds its own size and a comma.
+        let empty_size = serialized_size(&json!({ "BatchInput": batch_input, "Items": [] }));
+
+        let mut batches: Vec<Vec<Value>> = vec![];
+        let mut size = empty_size;
+        for (index, item) in items.into_iter().enumerate() {
+            let item_size = serialized_size(&item);
+            if max_bytes.is_some_and(|max| empty_size + item_size > max) {
+                return Err(ExecutionError::runtime(format!(
+                    "The item at index {index} doesn't fit in a batch of at most {} bytes", max_bytes.unwrap_or_default()
+                )));
+            }
+            let fits = batches.last().is_some_and(|batch| {
+                // Adding an item to a non-empty batch also adds a comma.
+                max_items.is_none_or(|max| (batch.len() as u64) < max) && max_bytes.is_none_or(|max| size + item_size < max)
+            });
+            match batches.last_mut() {
+                Some(batch) if fits => {
+                    size += item_size + 1;
+                    batch.push(item);
+                }
+                _ => {
+                    size = empty_size + item_size;
+                    batches.push(vec![item]);
+                }
+            }
+        }
+        Ok(batches.into_iter()
+            .map(|items| json!({ "BatchInput": batch_input, "Items": items }))
+            .collect())
+    }
+}
+
+fn positive_integer(path: &ReferencePath, field: &str, input: &Value, context: &Value) -> Result<u64, ExecutionError> {
+    path.get(input, context)
+        .and_then(Value::as_u64)
+        .filter(|value| *value > 0)
+        .ok_or_else(|| ExecutionError::runtime(format!("The JSONPath '{path}' specified for the field '{field}' must select a positive integer")))
+}
+
+fn serialized_size(value: &Value) -> u64 {
+    value.to_string().len() as u64
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rstest::*;
+    use anyhow::Result;
+
+    #[rstest]
+    #[case::max_items(json!({ "MaxItemsPerBatch": 2 }), json!([[1, 2], [3, 4], [5]]))]
+    #[case::max_items_path(json!({ "MaxItemsPerBatchPath": "$.max" }), json!([[1, 2, 3], [4, 5]]))]
+    // `{"BatchInput":{},"Items":[1,2,3]}` is 33 bytes long.
+    #[case::max_bytes(json!({ "MaxInputBytesPerBatch": 33 }), json!([[1, 2, 3], [4, 5]]))]
+    #[case::max_bytes_path(json!({ "MaxInputBytesPerBatchPath": "$.bytes" }), json!([[1, 2], [3, 4], [5]]))]
+    #[case::both_limits(json!({ "MaxItemsPerBatch": 2, "MaxInputBytesPerBatch": 29 }), json!([[1], [2], [3], [4], [5]]))]
+    #[case::no_limit(json!({}), json!([[1, 2, 3, 4, 5]]))]
+    fn batch(#[case] configuration: Value, #[case] expected: Value) -> Result<()> {
+        let item_batcher: ItemBatcherConfiguration = serde_json::from_value(configuration)?;
+
+        let batches = item_batcher.batch(vec![json!(1), json!(2), json!(3), json!(4), json!(5)], &json!({ "max": 3, "bytes": 31 }), &Value::Null)?;
+
+        let items: Vec<Value> = batches.iter().map(|batch| batch["Items"].clone()).collect();
+        assert_eq!(Value::Array(items), expected);
+        assert!(batches.iter().all(|batch| batch["BatchInput"] == json!({})));
+        Ok(())
+    }
+
+    #[rstest]
+    fn batch_input() -> Result<()> {
+        let item_batcher: ItemBatcherConfiguration = serde_json::from_value(json!({
+            "MaxItemsPerBatch": 2,
+            "BatchInput": { "key.$": "$.key", "constant": 1 }
+        }))?;
+
+        let batches = item_batcher.batch(vec![json!("a"), json!("b"), json!("c")], &json!({ "key": "value" }), &Value::Null)?;
+
+        assert_eq!(batches, vec![
+            json!({ "BatchInput": { "key": "value", "constant": 1 }, "Items": ["a", "b"] }),
+            json!({ "BatchInput": { "key": "value", "constant": 1 }, "Items": ["c"] }),
+        ]);
+        Ok(())
+    }
+
+    #[rstest]
+    #[case::item_too_big(json!({ "MaxInputBytesPerBatch": 30 }), json!({}))]
+    #[case::invalid_path(json!({ "MaxItemsPerBatchPath": "$.max" }), json!({ "max": 0 }))]
+    fn batch_fails(#[case] configuration: Value, #[case] input: Value) -> Result<()> {
+        let item_batcher: ItemBatcherConfiguration = serde_json::from_value(configuration)?;
+
+        let batches = item_batcher.batch(vec![json!("a long item")], &input, &Value::Null);
+
+        assert!(batches.is_err());
+        Ok(())
+    }
+
+    #[rstest]
+    #[case::max_items(json!({ "MaxItemsPerBatch": 2, "MaxItemsPerBatchPath": "$.max" }))]
+    #[case::max_bytes(json!({ "MaxInputBytesPerBatch": 2, "MaxInputBytesPerBatchPath": "$.max" }))]
+    fn parse_duplicate_limits(#[case] configuration: Value) {
+        assert!(serde_json::from_value::<ItemBatcherConfiguration>(configuration).is_err());
+    }
+
+    #[rstest]
+    #[case::zero_items(json!({ "MaxItemsPerBatch": 0 }))]
+    #[case::negative_items(json!({ "MaxItemsPerBatch": -1 }))]
+    #[case::decimal_items(json!({ "MaxItemsPerBatch": 1.5 }))]
+    #[case::string_items(json!({ "MaxItemsPerBatch": "x" }))]
+    #[case::zero_bytes(json!({ "MaxInputBytesPerBatch": 0 }))]
+    #[case::string_bytes(json!({ "MaxInputBytesPerBatch": "x" }))]
+    fn parse_invalid_limits(#[case] configuration: Value) {
+        assert!(serde_json::from_value::<ItemBatcherConfiguration>(configuration).is_err());
+    }
+
+    #[rstest]
+    #[case::count(json!({ "ToleratedFailureCount": 5 }), Some((Some(5), None)))]
+    #[case::decimal_percentage(json!({ "ToleratedFailurePercentage": 50.5 }), Some((None, Some(50.5))))]
+    #[case::integer_percentage(json!({ "ToleratedFailurePercentage": 100 }), Some((None, Some(100.0))))]
+    #[case::negative_count(json!({ "ToleratedFailureCount": -1 }), None)]
+    #[case::decimal_count(json!({ "ToleratedFailureCount": 50.5 }), None)]
+    #[case::string_count(json!({ "ToleratedFailureCount": "5" }), None)]
+    #[case::negative_percentage(json!({ "ToleratedFailurePercentage": -1 }), None)]
+    #[case::percentage_above_100(json!({ "ToleratedFailurePercentage": 150.5 }), None)]
+    #[case::string_percentage(json!({ "ToleratedFailurePercentage": "5" }), None)]
+    fn parse_tolerated_failures(#[case] fields: Value, #[case] expected: Option<(Option<u64>, Option<f64>)>) -> Result<()> {
+        let tolerated_failures = serde_json::from_value::<ToleratedFailures>(fields).ok();
+
+        let resolved = tolerated_failures
+            .map(|tolerated_failures| Ok::<_, ExecutionError>((tolerated_failures.count(&Value::Null, &Value::Null)?, tolerated_failures.percentage(&Value::Null, &Value::Null)?)))
+            .transpose()?;
+        assert_eq!(resolved, expected);
+        Ok(())
+    }
+
+    #[rstest]
+    #[case::default(json!({}), Some((ProcessorMode::Inline, None)))]
+    #[case::inline(json!({ "Mode": "INLINE" }), Some((ProcessorMode::Inline, None)))]
+    #[case::distributed(json!({ "Mode": "DISTRIBUTED", "ExecutionType": "STANDARD" }), Some((ProcessorMode::Distributed, Some(ExecutionType::Standard))))]
+    #[case::distributed_without_execution_type(json!({ "Mode": "DISTRIBUTED" }), None)]
+    #[case::unknown_mode(json!({ "Mode": "REMOTE" }), None)]
+    fn parse_processor_config(#[case] configuration: Value, #[case] expected: Option<(ProcessorMode, Option<ExecutionType>)>) {
+        let processor_config = serde_json::from_value::<ProcessorConfig>(configuration).ok();
+
+        assert_eq!(processor_config.map(|config| (config.mode(), config.execution_type())), expected);
+    }
+}
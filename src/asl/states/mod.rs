@@ -3,3 +3,4 @@ pub mod fail;
 pub mod wait;
 pub mod task;
 pub mod map;
+pub mod parallel;
@@ -0,0 +1,22 @@
+use std::collections::HashMap;
+use serde::Deserialize;
+use crate::asl::state_machine::State;
+
+/// A branch of a Parallel state: a nested state machine, whose states can only transition to
+/// each other.
+#[derive(Deserialize, Debug, PartialEq, Eq)]
+#[serde(rename_all = "PascalCase")]
+pub struct ParallelBranch {
+    start_at: String,
+    states: HashMap<String, State>,
+}
+
+impl ParallelBranch {
+    pub fn start_at(&self) -> &str {
+        &self.start_at
+    }
+
+    pub fn states(&self) -> &HashMap<String, State> {
+        &self.states
+    }
+}
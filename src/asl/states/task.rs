@@ -1,11 +1,14 @@
-use serde_json::Number;
-use serde::Deserialize;
-use crate::asl::types::MyJsonPath;
+use serde_json::{Number, Value};
+use serde::{Deserialize, Deserializer};
+use serde::de::Error;
+use crate::asl::json_path::ReferencePath;
+use crate::asl::payload_template::PayloadTemplate;
+use crate::asl::types::{alternative_fields, deserialize_positive_integer};
 
-#[derive(Deserialize, Debug, PartialEq, Eq)]
+#[derive(Debug, PartialEq, Eq)]
 pub enum TimeoutSecondsOrPath {
     TimeoutSeconds(Number),
-    TimeoutSecondsPath(MyJsonPath)
+    TimeoutSecondsPath(ReferencePath)
 }
 
 impl Default for TimeoutSecondsOrPath {
@@ -14,8 +17,54 @@ impl Default for TimeoutSecondsOrPath {
     }
 }
 
-#[derive(Deserialize, Debug, PartialEq, Eq)]
+#[derive(Debug, PartialEq, Eq)]
 pub enum HeartbeatSecondsOrPath {
-    HeartbeatSeconds(u32),
-    HeartbeatSecondsPath(MyJsonPath)
+    HeartbeatSeconds(u64),
+    HeartbeatSecondsPath(ReferencePath)
+}
+
+// Each pair of alternative fields is deserialized apart, as deserializing a flattened struct
+// takes the fields it reads from the state.
+#[derive(Deserialize)]
+#[serde(rename_all = "PascalCase")]
+struct TimeoutFields {
+    #[serde(default, deserialize_with = "deserialize_positive_integer")]
+    timeout_seconds: Option<u64>,
+    timeout_seconds_path: Option<ReferencePath>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "PascalCase")]
+struct HeartbeatFields {
+    #[serde(default, deserialize_with = "deserialize_positive_integer")]
+    heartbeat_seconds: Option<u64>,
+    heartbeat_seconds_path: Option<ReferencePath>,
+}
+
+/// Deserializes the flattened "TimeoutSeconds" or "TimeoutSecondsPath" field.
+pub(crate) fn deserialize_timeout<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Option<TimeoutSecondsOrPath>, D::Error> {
+    let fields = TimeoutFields::deserialize(deserializer)?;
+    alternative_fields(
+        fields.timeout_seconds.map(|seconds| TimeoutSecondsOrPath::TimeoutSeconds(Number::from(seconds))),
+        fields.timeout_seconds_path.map(TimeoutSecondsOrPath::TimeoutSecondsPath),
+        ["TimeoutSeconds", "TimeoutSecondsPath"],
+    )
+}
+
+/// Deserializes the flattened "HeartbeatSeconds" or "HeartbeatSecondsPath" field.
+pub(crate) fn deserialize_heartbeat<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Option<HeartbeatSecondsOrPath>, D::Error> {
+    let fields = HeartbeatFields::deserialize(deserializer)?;
+    alternative_fields(
+        fields.heartbeat_seconds.map(HeartbeatSecondsOrPath::HeartbeatSeconds),
+        fields.heartbeat_seconds_path.map(HeartbeatSecondsOrPath::HeartbeatSecondsPath),
+        ["HeartbeatSeconds", "HeartbeatSecondsPath"],
+    )
+}
+
+/// "Credentials" MUST be a JSON object, which is treated as a Payload Template.
+pub(crate) fn deserialize_credentials<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Option<PayloadTemplate>, D::Error> {
+    match Value::deserialize(deserializer)? {
+        value @ Value::Object(_) => PayloadTemplate::try_from(value).map(Some).map_err(D::Error::custom),
+        value => Err(D::Error::custom(format!("\"Credentials\" must be an object, but it is {value}"))),
+    }
 }
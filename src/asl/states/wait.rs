@@ -1,11 +1,23 @@
-use serde::Deserialize;
-use serde_json::Number;
-use crate::asl::types::{MyJsonPath, Timestamp};
+use serde::{Deserialize, Deserializer};
+use serde::de::Error;
+use serde_json::{Number, Value};
+use crate::asl::json_path::ReferencePath;
+use crate::asl::types::{deserialize_timestamp, Timestamp};
 
 #[derive(Deserialize, Debug, PartialEq, Eq)]
 pub enum WaitDuration {
+    #[serde(deserialize_with = "deserialize_seconds")]
     Seconds(Number),
-    SecondsPath(MyJsonPath),
+    SecondsPath(ReferencePath),
+    #[serde(deserialize_with = "deserialize_timestamp")]
     Timestamp(Timestamp),
-    TimestampPath(MyJsonPath),
+    TimestampPath(ReferencePath),
+}
+
+/// "Seconds" MUST be a non-negative integer.
+fn deserialize_seconds<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Number, D::Error> {
+    match Value::deserialize(deserializer)? {
+        Value::Number(seconds) if seconds.is_u64() => Ok(seconds),
+        value => Err(D::Error::custom(format!("\"Seconds\" must be a non-negative integer, but it is {value}"))),
+    }
 }
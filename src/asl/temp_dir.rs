@@ -0,0 +1,32 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+use serde_json::Value;
+use uuid::Uuid;
+
+/// A temporary directory for tests, removed with its content when dropped, so that it doesn't
+/// leak when an assertion fails.
+pub(crate) struct TempDir(PathBuf);
+
+impl TempDir {
+    /// Creates an empty directory, named after `prefix` and a random suffix.
+    pub(crate) fn new(prefix: &str) -> anyhow::Result<Self> {
+        let path = std::env::temp_dir().join(format!("{prefix}-{}", Uuid::new_v4()));
+        fs::create_dir_all(&path)?;
+        Ok(TempDir(path))
+    }
+
+    pub(crate) fn path(&self) -> &Path {
+        &self.0
+    }
+
+    /// Reads the JSON file at `path`, relative to the directory.
+    pub(crate) fn read(&self, path: impl AsRef<Path>) -> anyhow::Result<Value> {
+        Ok(serde_json::from_str(&fs::read_to_string(self.0.join(path))?)?)
+    }
+}
+
+impl Drop for TempDir {
+    fn drop(&mut self) {
+        let _ = fs::remove_dir_all(&self.0);
+    }
+}
@@ -1,14 +1,35 @@
+use chrono::DateTime;
+use serde::{Deserialize, Deserializer};
+use serde::de::Error;
 use serde_json::Value;
+use crate::asl::payload_template::PayloadTemplate;
 
 // TODO: Implement Timestamp
 pub type Timestamp = String;
 
-// TODO: Implement JSONPath
-pub type MyJsonPath = String;
-pub type InvertedJsonPath = String;
+pub type Parameters = PayloadTemplate;
+pub type ResultSelector = PayloadTemplate;
 
-// TODO: Model a Payload according to https://states-language.net/spec.html#payload-template
-pub type Payload = Value;
+/// Timestamps MUST conform to RFC3339, such as "2016-03-14T01:59:00Z".
+pub(crate) fn deserialize_timestamp<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Timestamp, D::Error> {
+    let timestamp = Timestamp::deserialize(deserializer)?;
+    DateTime::parse_from_rfc3339(&timestamp).map_err(|e| D::Error::custom(format!("Invalid timestamp '{timestamp}': {e}")))?;
+    Ok(timestamp)
+}
 
-pub type Parameters = Payload;
-pub type ResultSelector = Payload;
+/// Limits such as "MaxItemsPerBatch" MUST be positive integers.
+pub(crate) fn deserialize_positive_integer<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Option<u64>, D::Error> {
+    match Value::deserialize(deserializer)? {
+        Value::Number(number) if number.as_u64().is_some_and(|number| number > 0) => Ok(number.as_u64()),
+        value => Err(D::Error::custom(format!("Expected a positive integer, but it is {value}"))),
+    }
+}
+
+/// Picks whichever of two alternative fields is present. Alternative fields are deserialized
+/// apart rather than as a flattened `Option`, which would turn a wrong value into `None`.
+pub(crate) fn alternative_fields<T, E: Error>(first: Option<T>, second: Option<T>, fields: [&str; 2]) -> Result<Option<T>, E> {
+    match (first, second) {
+        (Some(_), Some(_)) => Err(E::custom(format!("\"{}\" and \"{}\" can't both be present", fields[0], fields[1]))),
+        (first, second) => Ok(first.or(second)),
+    }
+}
@@ -0,0 +1,1210 @@
+use std::collections::{HashSet, VecDeque};
+use std::fmt::{Display, Formatter};
+use chrono::DateTime;
+use serde::de::{DeserializeSeed, Deserializer, MapAccess, SeqAccess, Visitor};
+use serde_json::{Error as SerdeError, Map, Number, Value};
+use crate::asl::json_path::{JsonPath, ReferencePath};
+use crate::asl::payload_template::PayloadTemplate;
+use crate::asl::state_machine::{Diagnostic, DiagnosticCode, ParseMode, UnknownField};
+
+/// State names MUST NOT be longer than 80 Unicode characters.
+const MAX_STATE_NAME_LENGTH: usize = 80;
+
+/// How many fields of a group an object can have. The fields of a group are alternative ways of
+/// providing the same value.
+#[derive(Clone, Copy)]
+enum Occurrence {
+    AtMostOne,
+    ExactlyOne,
+}
+
+type FieldGroup = (Occurrence, &'static [&'static str]);
+
+const NEXT_OR_END: FieldGroup = (Occurrence::ExactlyOne, &["Next", "End"]);
+
+/// The groups of fields of a state, by state type.
+fn field_groups(state_type: &str) -> &'static [FieldGroup] {
+    use Occurrence::*;
+    match state_type {
+        "Task" => &[NEXT_OR_END, (AtMostOne, &["TimeoutSeconds", "TimeoutSecondsPath"]), (AtMostOne, &["HeartbeatSeconds", "HeartbeatSecondsPath"])],
+        "Parallel" | "Pass" => &[NEXT_OR_END],
+        "Map" => &[
+            NEXT_OR_END,
+            (ExactlyOne, &["ItemProcessor", "Iterator"]),
+            (AtMostOne, &["ItemSelector", "Parameters"]),
+            (AtMostOne, &["ToleratedFailureCount", "ToleratedFailureCountPath"]),
+            (AtMostOne, &["ToleratedFailurePercentage", "ToleratedFailurePercentagePath"]),
+        ],
+        "Wait" => &[NEXT_OR_END, (ExactlyOne, &["Seconds", "SecondsPath", "Timestamp", "TimestampPath"])],
+        "Fail" => &[(AtMostOne, &["Error", "ErrorPath"]), (AtMostOne, &["Cause", "CausePath"])],
+        _ => &[],
+    }
+}
+
+const READER_CONFIG_GROUPS: &[FieldGroup] = &[(Occurrence::AtMostOne, &["MaxItems", "MaxItemsPath"])];
+const ITEM_BATCHER_GROUPS: &[FieldGroup] = &[
+    (Occurrence::AtMostOne, &["MaxItemsPerBatch", "MaxItemsPerBatchPath"]),
+    (Occurrence::AtMostOne, &["MaxInputBytesPerBatch", "MaxInputBytesPerBatchPath"]),
+];
+
+/// A Choice Rule MUST have exactly one of these operators, the composed ones being "And", "Or"
+/// and "Not".
+const CHOICE_OPERATORS: &[&str] = &[
+    "And", "Or", "Not",
+    "StringEquals", "StringEqualsPath", "StringLessThan", "StringLessThanPath", "StringGreaterThan", "StringGreaterThanPath",
+    "StringLessThanEquals", "StringLessThanEqualsPath", "StringGreaterThanEquals", "StringGreaterThanEqualsPath", "StringMatches",
+    "NumericEquals", "NumericEqualsPath", "NumericLessThan", "NumericLessThanPath", "NumericGreaterThan", "NumericGreaterThanPath",
+    "NumericLessThanEquals", "NumericLessThanEqualsPath", "NumericGreaterThanEquals", "NumericGreaterThanEqualsPath",
+    "BooleanEquals", "BooleanEqualsPath",
+    "TimestampEquals", "TimestampEqualsPath", "TimestampLessThan", "TimestampLessThanPath", "TimestampGreaterThan", "TimestampGreaterThanPath",
+    "TimestampLessThanEquals", "TimestampLessThanEqualsPath", "TimestampGreaterThanEquals", "TimestampGreaterThanEqualsPath",
+    "IsNull", "IsPresent", "IsNumeric", "IsString", "IsBoolean", "IsTimestamp",
+];
+
+/// Lists fields as "'A', 'B' or 'C'".
+fn alternatives(fields: &[&str]) -> String {
+    match fields {
+        [] => String::new(),
+        [field] => format!("'{field}'"),
+        [fields @ .., last] => format!("{} or '{last}'", fields.iter().map(|field| format!("'{field}'")).collect::<Vec<_>>().join(", ")),
+    }
+}
+
+/// The fields of the top level of a state machine.
+const STATE_MACHINE_FIELDS: &[&str] = &["Comment", "StartAt", "States", "TimeoutSeconds", "Version"];
+/// The fields of a Parallel branch.
+const BRANCH_FIELDS: &[&str] = &["Comment", "StartAt", "States"];
+/// The fields of the "ItemProcessor" of a Map state, or of its deprecated "Iterator".
+const ITEM_PROCESSOR_FIELDS: &[&str] = &["Comment", "ProcessorConfig", "StartAt", "States"];
+const PROCESSOR_CONFIG_FIELDS: &[&str] = &["ExecutionType", "Mode"];
+const RETRIER_FIELDS: &[&str] = &["BackoffRate", "Comment", "ErrorEquals", "IntervalSeconds", "JitterStrategy", "MaxAttempts", "MaxDelaySeconds"];
+const CATCHER_FIELDS: &[&str] = &["Comment", "ErrorEquals", "Next", "ResultPath"];
+const ITEM_READER_FIELDS: &[&str] = &["Parameters", "ReaderConfig", "Resource"];
+const READER_CONFIG_FIELDS: &[&str] = &["CSVHeaderLocation", "CSVHeaders", "InputType", "MaxItems", "MaxItemsPath"];
+const ITEM_BATCHER_FIELDS: &[&str] = &["BatchInput", "MaxInputBytesPerBatch", "MaxInputBytesPerBatchPath", "MaxItemsPerBatch", "MaxItemsPerBatchPath"];
+const RESULT_WRITER_FIELDS: &[&str] = &["Parameters", "Resource"];
+
+/// The fields a state MUST have besides "Type", by state type. The alternative fields a state
+/// needs one of are groups, see [field_groups].
+fn required_fields(state_type: &str) -> &'static [&'static str] {
+    match state_type {
+        "Task" => &["Resource"],
+        "Parallel" => &["Branches"],
+        "Choice" => &["Choices"],
+        _ => &[],
+    }
+}
+
+/// The kinds of values of the fields, as deserializing a definition expects them. "ErrorPath" and
+/// "CausePath" are only checked to be strings, as they're parsed when the Fail state runs.
+#[derive(Clone, Copy)]
+enum FieldValue {
+    String,
+    Object,
+    Array,
+    /// An array of strings, such as the headers of a CSV file.
+    Strings,
+    /// A non-empty array of Error Names.
+    ErrorNames,
+    /// A Path, or `null` to discard the input or the output.
+    Path,
+    /// A Reference Path, or `null` to discard the result.
+    ResultPath,
+    ReferencePath,
+    PayloadTemplate,
+    /// A Payload Template, which MUST be an object.
+    Credentials,
+    /// An RFC3339 timestamp, such as "2016-03-14T01:59:00Z".
+    Timestamp,
+    PositiveInteger,
+    NonNegativeInteger,
+    /// A number between 0 and 100, which can be decimal.
+    Percentage,
+    /// A number greater than or equal to 1.0.
+    BackoffRate,
+    /// One of the given strings.
+    OneOf(&'static [&'static str]),
+}
+
+/// The kind of value of a field, or `None` if its value isn't parsed.
+fn field_value(field: &str) -> Option<FieldValue> {
+    use FieldValue::*;
+    Some(match field {
+        "InputPath" | "OutputPath" => Path,
+        "ResultPath" => ResultPath,
+        "ItemsPath" | "TimeoutSecondsPath" | "HeartbeatSecondsPath" | "SecondsPath" | "TimestampPath" | "ToleratedFailureCountPath"
+        | "ToleratedFailurePercentagePath" | "MaxItemsPath" | "MaxItemsPerBatchPath" | "MaxInputBytesPerBatchPath" => ReferencePath,
+        "Parameters" | "ResultSelector" | "ItemSelector" | "BatchInput" => PayloadTemplate,
+        "Credentials" => Credentials,
+        "Timestamp" => Timestamp,
+        "Comment" | "Version" | "Resource" | "Next" | "Default" | "Label" | "Error" | "ErrorPath" | "Cause" | "CausePath" => String,
+        "ItemReader" | "ReaderConfig" | "ItemBatcher" | "ResultWriter" | "ProcessorConfig" => Object,
+        "Retry" | "Catch" => Array,
+        "CSVHeaders" => Strings,
+        "ErrorEquals" => ErrorNames,
+        "TimeoutSeconds" | "HeartbeatSeconds" | "IntervalSeconds" | "MaxDelaySeconds" | "MaxItems" | "MaxItemsPerBatch"
+        | "MaxInputBytesPerBatch" => PositiveInteger,
+        "Seconds" | "MaxAttempts" | "MaxConcurrency" | "ToleratedFailureCount" => NonNegativeInteger,
+        "ToleratedFailurePercentage" => Percentage,
+        "BackoffRate" => BackoffRate,
+        "Mode" => OneOf(&["INLINE", "DISTRIBUTED"]),
+        "ExecutionType" => OneOf(&["STANDARD", "EXPRESS"]),
+        "InputType" => OneOf(&["CSV", "JSON", "JSONL", "MANIFEST", "PARQUET"]),
+        "CSVHeaderLocation" => OneOf(&["FIRST_ROW", "GIVEN"]),
+        "JitterStrategy" => OneOf(&["FULL", "NONE"]),
+        _ => return None,
+    })
+}
+
+/// The fields a state can have, by state type, or `None` if the type is unknown.
+fn state_fields(state_type: &str) -> Option<&'static [&'static str]> {
+    Some(match state_type {
+        "Task" => &[
+            "Type", "Comment", "InputPath", "OutputPath", "Next", "End", "ResultPath", "Parameters", "ResultSelector", "Retry", "Catch",
+            "Resource", "TimeoutSeconds", "TimeoutSecondsPath", "HeartbeatSeconds", "HeartbeatSecondsPath", "Credentials",
+        ],
+        "Parallel" => &[
+            "Type", "Comment", "InputPath", "OutputPath", "Next", "End", "ResultPath", "Parameters", "ResultSelector", "Retry", "Catch",
+            "Branches",
+        ],
+        "Map" => &[
+            "Type", "Comment", "InputPath", "OutputPath", "Next", "End", "ResultPath", "Parameters", "ResultSelector", "Retry", "Catch",
+            "ItemProcessor", "Iterator", "ItemsPath", "ItemSelector", "ItemBatcher", "ItemReader", "ResultWriter", "MaxConcurrency", "Label",
+            "ToleratedFailureCount", "ToleratedFailureCountPath", "ToleratedFailurePercentage", "ToleratedFailurePercentagePath",
+        ],
+        "Pass" => &["Type", "Comment", "InputPath", "OutputPath", "Next", "End", "ResultPath", "Parameters", "Result"],
+        "Wait" => &["Type", "Comment", "InputPath", "OutputPath", "Next", "End", "Seconds", "SecondsPath", "Timestamp", "TimestampPath"],
+        "Choice" => &["Type", "Comment", "InputPath", "OutputPath", "Choices", "Default"],
+        "Succeed" => &["Type", "Comment", "InputPath", "OutputPath"],
+        "Fail" => &["Type", "Comment", "Error", "ErrorPath", "Cause", "CausePath"],
+        _ => return None,
+    })
+}
+
+/// A JSON Pointer to a node of the definition, see https://www.rfc-editor.org/rfc/rfc6901
+#[derive(Debug, Clone, Default)]
+struct Pointer(String);
+
+impl Pointer {
+    fn join(&self, token: impl Display) -> Pointer {
+        let token = token.to_string().replace('~', "~0").replace('/', "~1");
+        Pointer(format!("{}/{token}", self.0))
+    }
+}
+
+/// Collects the problems found in a definition.
+#[derive(Default)]
+struct Validator<'v> {
+    mode: ParseMode,
+    /// The names of the states of every scope, which MUST be unique in the whole state machine.
+    names: HashSet<&'v str>,
+    diagnostics: Vec<Diagnostic>,
+    /// The unknown fields kept in [ParseMode::Lenient].
+    unknown_fields: Vec<UnknownField>,
+}
+
+impl<'v> Validator<'v> {
+    fn report(&mut self, code: DiagnosticCode, pointer: &Pointer, state: Option<&str>, message: String) {
+        self.diagnostics.push(Diagnostic {
+            code,
+            pointer: pointer.0.clone(),
+            state: state.map(str::to_string),
+            message,
+        });
+    }
+
+    fn invalid_field(&mut self, state_pointer: &Pointer, state: &str, field: &str, message: String) {
+        let message = format!("Invalid value for the field '{field}' of the state '{state}': {message}");
+        self.report(DiagnosticCode::InvalidFieldValue, &state_pointer.join(field), Some(state), message);
+    }
+
+    /// Reports the fields of `object` missing from `fields`, as errors or as unknown fields
+    /// depending on the mode, and checks the values of the others. `state` is the state the object
+    /// belongs to, if any.
+    fn check_fields(&mut self, object: &Value, fields: &[&str], pointer: &Pointer, state: Option<&str>) {
+        for (name, value) in object.as_object().into_iter().flatten() {
+            if fields.contains(&name.as_str()) {
+                if let Some(kind) = field_value(name) {
+                    self.check_value(pointer, state, name, value, kind);
+                }
+                continue;
+            }
+            let unknown_field = UnknownField {
+                pointer: pointer.join(name).0,
+                state: state.map(str::to_string),
+                name: name.clone(),
+                value: value.clone(),
+            };
+            match self.mode {
+                ParseMode::Strict => self.diagnostics.push(unknown_field.diagnostic()),
+                ParseMode::Lenient => self.unknown_fields.push(unknown_field),
+            }
+        }
+    }
+
+    /// Reports that the object at `pointer` lacks `field`. `owner` describes the object, such as
+    /// "A Catcher of the state", and is followed by the name of the state.
+    fn missing_field(&mut self, pointer: &Pointer, state: &str, owner: &str, field: &str) {
+        let message = format!("{owner} '{state}' must have the field '{field}'");
+        self.report(DiagnosticCode::MissingField, pointer, Some(state), message);
+    }
+
+    /// Parses the value of a field like deserializing does, reporting why it's invalid. `state` is
+    /// the state the field belongs to, if any.
+    fn check_value(&mut self, pointer: &Pointer, state: Option<&str>, field: &str, value: &Value, kind: FieldValue) {
+        let problem = match (kind, value) {
+            (FieldValue::Path | FieldValue::ResultPath, Value::Null) => None,
+            (FieldValue::Path, Value::String(path)) => path.parse::<JsonPath>().err().map(|e| e.to_string()),
+            (FieldValue::ResultPath | FieldValue::ReferencePath, Value::String(path)) => path.parse::<ReferencePath>().err().map(|e| e.to_string()),
+            (FieldValue::PayloadTemplate, template) | (FieldValue::Credentials, template @ Value::Object(_)) => {
+                PayloadTemplate::try_from(template.clone()).err().map(|e| e.to_string())
+            }
+            (FieldValue::Credentials | FieldValue::Object, value) => (!value.is_object()).then(|| format!("must be an object, but it is {value}")),
+            (FieldValue::Array, value) => (!value.is_array()).then(|| format!("must be an array, but it is {value}")),
+            (FieldValue::Strings, value) => value.as_array().is_none_or(|values| !values.iter().all(Value::is_string))
+                .then(|| format!("must be an array of strings, but it is {value}")),
+            (FieldValue::ErrorNames, value) => value.as_array().is_none_or(|values| values.is_empty() || !values.iter().all(Value::is_string))
+                .then(|| format!("must be a non-empty array of Error Names, but it is {value}")),
+            (FieldValue::Timestamp, Value::String(timestamp)) => DateTime::parse_from_rfc3339(timestamp).err()
+                .map(|e| format!("'{timestamp}' is not an RFC3339 timestamp: {e}")),
+            (FieldValue::PositiveInteger, value) => value.as_u64().is_none_or(|integer| integer == 0)
+                .then(|| format!("must be a positive integer, but it is {value}")),
+            (FieldValue::NonNegativeInteger, value) => (!value.is_u64()).then(|| format!("must be a non-negative integer, but it is {value}")),
+            (FieldValue::Percentage, value) => value.as_f64().is_none_or(|percentage| !(0.0..=100.0).contains(&percentage))
+                .then(|| format!("must be a number between 0 and 100, but it is {value}")),
+            (FieldValue::BackoffRate, value) => value.as_f64().is_none_or(|rate| rate < 1.0)
+                .then(|| format!("must be a number greater than or equal to 1.0, but it is {value}")),
+            (FieldValue::OneOf(values), value) => value.as_str().is_none_or(|value| !values.contains(&value))
+                .then(|| format!("must be one of {}, but it is {value}", alternatives(values))),
+            (FieldValue::String, Value::String(_)) => None,
+            (_, value) => Some(format!("must be a string, but it is {value}")),
+        };
+        match (problem, state) {
+            (Some(message), Some(state)) => self.invalid_field(pointer, state, field, message),
+            (Some(message), None) => {
+                let message = format!("Invalid value for the field '{field}': {message}");
+                self.report(DiagnosticCode::InvalidFieldValue, &pointer.join(field), None, message);
+            }
+            (None, _) => {}
+        }
+    }
+
+    /// Reports the groups of alternative fields of `object` that are combined, or missing while
+    /// one of them is required. `state` is the state the object belongs to.
+    fn check_groups(&mut self, object: &Value, groups: &[FieldGroup], pointer: &Pointer, state: &str) {
+        for (occurrence, fields) in groups {
+            let mut present = fields.iter().filter(|field| object.get(**field).is_some());
+            match (present.next(), present.next(), occurrence) {
+                (Some(first), Some(second), _) => {
+                    let message = format!("The state '{state}' can't have both the fields '{first}' and '{second}'");
+                    self.report(DiagnosticCode::MutuallyExclusiveFields, &pointer.join(second), Some(state), message);
+                }
+                (None, _, Occurrence::ExactlyOne) => {
+                    let message = format!("The state '{state}' must have one of the fields {}", alternatives(fields));
+                    self.report(DiagnosticCode::MissingField, pointer, Some(state), message);
+                }
+                _ => {}
+            }
+        }
+    }
+
+    /// Checks that a Choice Rule, and each rule it's composed of, has exactly one operator.
+    fn validate_choice_rule(&mut self, state: &str, rule: &Value, pointer: &Pointer) {
+        let Some(fields) = rule.as_object() else {
+            let message = format!("A Choice Rule of the state '{state}' must be an object, but it is {rule}");
+            self.report(DiagnosticCode::InvalidFieldValue, pointer, Some(state), message);
+            return;
+        };
+        // "Variable" and the operators comparing to another value of the input are Reference Paths.
+        for (field, value) in fields {
+            if field == "Variable" || field.ends_with("Path") && CHOICE_OPERATORS.contains(&field.as_str()) {
+                self.check_value(pointer, Some(state), field, value, FieldValue::ReferencePath);
+            } else if field.starts_with("Timestamp") && CHOICE_OPERATORS.contains(&field.as_str()) {
+                self.check_value(pointer, Some(state), field, value, FieldValue::Timestamp);
+            }
+        }
+        let mut operators = CHOICE_OPERATORS.iter().filter(|operator| rule.get(**operator).is_some());
+        match (operators.next(), operators.next()) {
+            (Some(first), Some(second)) => {
+                let message = format!("A Choice Rule of the state '{state}' can't have both the operators '{first}' and '{second}'");
+                self.report(DiagnosticCode::MutuallyExclusiveFields, &pointer.join(second), Some(state), message);
+            }
+            (None, _) => {
+                let message = format!("A Choice Rule of the state '{state}' must have an operator, such as 'StringEquals' or 'And'");
+                self.report(DiagnosticCode::MissingField, pointer, Some(state), message);
+            }
+            _ => {}
+        }
+        for operator in ["And", "Or"] {
+            for (index, rule) in rule[operator].as_array().into_iter().flatten().enumerate() {
+                self.validate_choice_rule(state, rule, &pointer.join(operator).join(index));
+            }
+        }
+        if let Some(rule) = rule.get("Not") {
+            self.validate_choice_rule(state, rule, &pointer.join("Not"));
+        }
+    }
+
+    /// Validates the states of the top level of a state machine, of a Parallel branch or of a Map
+    /// iteration. Transitions can't cross the boundaries of a scope.
+    ///
+    /// `fields` are the fields the scope can have, and `owner` the state it belongs to, if any.
+    fn validate_scope(&mut self, scope: &'v Value, fields: &[&str], pointer: &Pointer, owner: Option<&str>) {
+        let scope_name = match owner {
+            Some(owner) => format!("The state machine nested in the state '{owner}'"),
+            None => "The state machine".to_string(),
+        };
+        if !scope.is_object() {
+            let message = format!("{scope_name} must be an object, but it is {scope}");
+            self.report(DiagnosticCode::InvalidFieldValue, pointer, owner, message);
+            return;
+        }
+        self.check_fields(scope, fields, pointer, owner);
+        let start_at = scope["StartAt"].as_str();
+        let states = scope["States"].as_object();
+        for (field, valid, expected) in [("StartAt", start_at.is_some(), "a string"), ("States", states.is_some(), "an object")] {
+            match scope.get(field) {
+                None => {
+                    let message = format!("{scope_name} must have the field '{field}'");
+                    self.report(DiagnosticCode::MissingField, pointer, owner, message);
+                }
+                Some(value) if !valid => {
+                    let message = format!("Invalid value for the field '{field}': must be {expected}, but it is {value}");
+                    self.report(DiagnosticCode::InvalidFieldValue, &pointer.join(field), owner, message);
+                }
+                Some(_) => {}
+            }
+        }
+        let Some(states) = states else {
+            return;
+        };
+        let states_pointer = pointer.join("States");
+        if let Some(start_at) = start_at.filter(|start_at| !states.contains_key(*start_at)) {
+            let message = format!("The start state '{start_at}' is not defined in \"States\"");
+            self.report(DiagnosticCode::StartStateNotDefined, &pointer.join("StartAt"), None, message);
+        }
+        for (name, state) in states {
+            let state_pointer = states_pointer.join(name);
+            if !self.names.insert(name) {
+                let message = format!("The state name '{name}' is used more than once");
+                self.report(DiagnosticCode::DuplicateStateName, &state_pointer, Some(name), message);
+            }
+            if name.chars().count() > MAX_STATE_NAME_LENGTH {
+                let message = format!("The state name '{name}' is longer than {MAX_STATE_NAME_LENGTH} characters");
+                self.report(DiagnosticCode::StateNameTooLong, &state_pointer, Some(name), message);
+            }
+            for (field, target) in transitions(state) {
+                if !states.contains_key(target) {
+                    let message = format!("The state '{name}' transitions to the state '{target}', which is not defined in its scope");
+                    self.report(DiagnosticCode::UnknownTransition, &field.resolve(&state_pointer), Some(name), message);
+                }
+            }
+            self.validate_state(name, state, &state_pointer);
+            match state["Type"].as_str() {
+                Some("Parallel") => {
+                    for (index, branch) in state["Branches"].as_array().into_iter().flatten().enumerate() {
+                        self.validate_scope(branch, BRANCH_FIELDS, &state_pointer.join("Branches").join(index), Some(name));
+                    }
+                }
+                Some("Map") => {
+                    let item_processor = ["ItemProcessor", "Iterator"].into_iter()
+                        .find_map(|field| state.get(field).map(|item_processor| (field, item_processor)));
+                    if let Some((field, item_processor)) = item_processor {
+                        let item_processor_pointer = state_pointer.join(field);
+                        self.validate_scope(item_processor, ITEM_PROCESSOR_FIELDS, &item_processor_pointer, Some(name));
+                        if let Some(processor_config) = item_processor.get("ProcessorConfig") {
+                            self.check_fields(processor_config, PROCESSOR_CONFIG_FIELDS, &item_processor_pointer.join("ProcessorConfig"), Some(name));
+                        }
+                    }
+                }
+                _ => {}
+            }
+        }
+
+        if !states.values().any(is_terminal) {
+            let message = "No state ends the execution: at least one state must be terminal in each scope".to_string();
+            self.report(DiagnosticCode::MissingTerminalState, &states_pointer, None, message);
+        }
+        // States that never run are only a problem in the strict mode, as definitions such as
+        // some of the asl-validator examples keep states that no transition targets.
+        let start_at = start_at.filter(|start_at| self.mode == ParseMode::Strict && states.contains_key(*start_at));
+        if let Some(start_at) = start_at {
+            let reachable = reachable_states(start_at, states);
+            for name in states.keys().filter(|name| !reachable.contains(name.as_str())) {
+                let message = format!("The state '{name}' can't be reached from the start state of its scope");
+                self.report(DiagnosticCode::UnreachableState, &states_pointer.join(name), Some(name), message);
+            }
+        }
+    }
+
+    /// Checks the fields of a single state.
+    fn validate_state(&mut self, name: &str, state: &Value, pointer: &Pointer) {
+        if !state.is_object() {
+            let message = format!("The state '{name}' must be an object, but it is {state}");
+            self.report(DiagnosticCode::InvalidFieldValue, pointer, Some(name), message);
+            return;
+        }
+        let state_type = state["Type"].as_str().unwrap_or_default();
+        match (state.get("Type"), state_fields(state_type)) {
+            (None, _) => self.missing_field(pointer, name, "The state", "Type"),
+            (Some(_), Some(fields)) => self.check_fields(state, fields, pointer, Some(name)),
+            (Some(Value::String(state_type)), None) => self.invalid_field(pointer, name, "Type", format!("'{state_type}' is not a state type")),
+            (Some(value), None) => self.invalid_field(pointer, name, "Type", format!("must be a string, but it is {value}")),
+        }
+        for field in required_fields(state_type) {
+            if state.get(*field).is_none() {
+                self.missing_field(pointer, name, "The state", field);
+            }
+        }
+        let error_handlers = [
+            ("Retry", RETRIER_FIELDS, &["ErrorEquals"][..], "A Retrier of the state"),
+            ("Catch", CATCHER_FIELDS, &["ErrorEquals", "Next"], "A Catcher of the state"),
+        ];
+        for (field, fields, required, owner) in error_handlers {
+            for (index, object) in state[field].as_array().into_iter().flatten().enumerate() {
+                let object_pointer = pointer.join(field).join(index);
+                if !object.is_object() {
+                    let message = format!("{owner} '{name}' must be an object, but it is {object}");
+                    self.report(DiagnosticCode::InvalidFieldValue, &object_pointer, Some(name), message);
+                    continue;
+                }
+                self.check_fields(object, fields, &object_pointer, Some(name));
+                for field in required.iter().filter(|field| object.get(**field).is_none()) {
+                    self.missing_field(&object_pointer, name, owner, field);
+                }
+            }
+        }
+        self.check_groups(state, field_groups(state_type), pointer, name);
+        // "End" only replaces "Next" when it makes the state terminal.
+        if matches!(state_type, "Task" | "Parallel" | "Pass" | "Map" | "Wait") && state.get("Next").is_none() {
+            if let Some(end) = state.get("End").filter(|end| **end != Value::Bool(true)) {
+                self.invalid_field(pointer, name, "End", format!("must be true, but it is {end}"));
+            }
+        }
+
+        match state_type {
+            "Task" => {
+                if let Some(resource) = state["Resource"].as_str() {
+                    if !is_valid_resource(resource) {
+                        self.invalid_field(pointer, name, "Resource", format!("'{resource}' is not a valid URI"));
+                    }
+                }
+                // Values that aren't positive integers are reported with the other fields.
+                let positive_integer = |field: &str| state.get(field).and_then(Value::as_u64).filter(|value| *value > 0);
+                let timeout = positive_integer("TimeoutSeconds");
+                let heartbeat = positive_integer("HeartbeatSeconds");
+                // "TimeoutSeconds" defaults to 60, but it can't be known when "TimeoutSecondsPath" is used.
+                let timeout = timeout.or_else(|| state.get("TimeoutSecondsPath").is_none().then_some(60));
+                if let (Some(heartbeat), Some(timeout)) = (heartbeat, timeout) {
+                    if heartbeat >= timeout {
+                        let message = format!("must be smaller than \"TimeoutSeconds\" ({timeout}), but it is {heartbeat}");
+                        self.invalid_field(pointer, name, "HeartbeatSeconds", message);
+                    }
+                }
+            }
+            "Parallel" => {
+                if let Some(branches) = state.get("Branches").filter(|branches| !branches.is_array()) {
+                    self.invalid_field(pointer, name, "Branches", format!("must be an array, but it is {branches}"));
+                }
+            }
+            "Choice" => match state.get("Choices") {
+                None => {}
+                Some(Value::Array(rules)) if rules.is_empty() => {
+                    self.invalid_field(pointer, name, "Choices", "must contain at least one Choice Rule".to_string());
+                }
+                Some(Value::Array(rules)) => {
+                    for (index, rule) in rules.iter().enumerate() {
+                        let rule_pointer = pointer.join("Choices").join(index);
+                        self.validate_choice_rule(name, rule, &rule_pointer);
+                        // Only the top-level rules transition, the rules they're composed of don't.
+                        if rule.is_object() && rule.get("Next").is_none() {
+                            self.missing_field(&rule_pointer, name, "A Choice Rule of the state", "Next");
+                        }
+                    }
+                }
+                Some(choices) => self.invalid_field(pointer, name, "Choices", format!("must be an array, but it is {choices}")),
+            },
+            "Map" => {
+                for (field, fields) in [("ItemReader", ITEM_READER_FIELDS), ("ItemBatcher", ITEM_BATCHER_FIELDS), ("ResultWriter", RESULT_WRITER_FIELDS)] {
+                    if let Some(object) = state.get(field) {
+                        self.check_fields(object, fields, &pointer.join(field), Some(name));
+                    }
+                }
+                if let Some(reader_config) = state["ItemReader"].get("ReaderConfig") {
+                    let reader_config_pointer = pointer.join("ItemReader").join("ReaderConfig");
+                    self.check_fields(reader_config, READER_CONFIG_FIELDS, &reader_config_pointer, Some(name));
+                    self.check_groups(reader_config, READER_CONFIG_GROUPS, &reader_config_pointer, name);
+                }
+                if let Some(item_batcher) = state.get("ItemBatcher") {
+                    self.check_groups(item_batcher, ITEM_BATCHER_GROUPS, &pointer.join("ItemBatcher"), name);
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
+/// Checks the rules of the spec on a definition before it's deserialized, such as transitions to
+/// states that don't exist, missing fields or invalid paths, returning every problem found.
+///
+/// The values of the fields are checked like deserializing parses them, so that a wrongly typed
+/// field is reported with its location rather than as the first error of deserializing.
+/// The fields the spec doesn't define are problems in [ParseMode::Strict], and are returned apart
+/// in [ParseMode::Lenient], where the states that can't be reached aren't checked.
+pub(crate) fn validate(definition: &Value, mode: ParseMode) -> (Vec<Diagnostic>, Vec<UnknownField>) {
+    let mut validator = Validator {
+        mode,
+        ..Validator::default()
+    };
+    validator.validate_scope(definition, STATE_MACHINE_FIELDS, &Pointer::default(), None);
+    (validator.diagnostics, validator.unknown_fields)
+}
+
+/// Parses a definition into JSON, reporting the keys defined more than once in an object, which
+/// deserializing would silently collapse into the last one.
+pub(crate) fn parse(definition: &str) -> Result<(Value, Vec<Diagnostic>), SerdeError> {
+    let mut duplicates = vec![];
+    let mut deserializer = serde_json::Deserializer::from_str(definition);
+    let value = RawValue { path: vec![], duplicates: &mut duplicates }.deserialize(&mut deserializer)?;
+    deserializer.end()?;
+    Ok((value, duplicates.iter().map(|path| duplicate_key(path)).collect()))
+}
+
+fn duplicate_key(path: &[String]) -> Diagnostic {
+    let pointer = path.iter().fold(Pointer::default(), |pointer, token| pointer.join(token));
+    let key = path.last().map(String::as_str).unwrap_or_default();
+    if is_state(path) {
+        return Diagnostic {
+            code: DiagnosticCode::DuplicateStateName,
+            pointer: pointer.0,
+            state: Some(key.to_string()),
+            message: format!("The state name '{key}' is used more than once"),
+        };
+    }
+    let state = (1..path.len()).rev().map(|length| &path[..length]).find(|path| is_state(path)).and_then(|path| path.last());
+    let message = match state {
+        Some(state) => format!("The state '{state}' defines the field '{key}' more than once"),
+        None => format!("The field '{key}' is defined more than once"),
+    };
+    Diagnostic { code: DiagnosticCode::DuplicateField, pointer: pointer.0, state: state.cloned(), message }
+}
+
+/// Whether the path leads to a scope: the top level, a Parallel branch or a Map iteration.
+fn is_scope(path: &[String]) -> bool {
+    match path {
+        [] => true,
+        [state @ .., field] if field == "ItemProcessor" || field == "Iterator" => is_state(state),
+        [state @ .., branches, _] if branches == "Branches" => is_state(state),
+        _ => false,
+    }
+}
+
+/// Whether the path leads to a state of a scope.
+fn is_state(path: &[String]) -> bool {
+    matches!(path, [scope @ .., states, _] if states == "States" && is_scope(scope))
+}
+
+/// Deserializes a JSON value like [Value] does, recording the path of every duplicate key.
+struct RawValue<'d> {
+    path: Vec<String>,
+    duplicates: &'d mut Vec<Vec<String>>,
+}
+
+impl RawValue<'_> {
+    fn child(&mut self, token: String) -> RawValue<'_> {
+        let mut path = self.path.clone();
+        path.push(token);
+        RawValue { path, duplicates: self.duplicates }
+    }
+}
+
+impl<'de> DeserializeSeed<'de> for RawValue<'_> {
+    type Value = Value;
+
+    fn deserialize<D: Deserializer<'de>>(self, deserializer: D) -> Result<Value, D::Error> {
+        deserializer.deserialize_any(self)
+    }
+}
+
+impl<'de> Visitor<'de> for RawValue<'_> {
+    type Value = Value;
+
+    fn expecting(&self, formatter: &mut Formatter) -> std::fmt::Result {
+        formatter.write_str("any valid JSON value")
+    }
+
+    fn visit_bool<E>(self, value: bool) -> Result<Value, E> {
+        Ok(Value::Bool(value))
+    }
+
+    fn visit_i64<E>(self, value: i64) -> Result<Value, E> {
+        Ok(Value::Number(value.into()))
+    }
+
+    fn visit_u64<E>(self, value: u64) -> Result<Value, E> {
+        Ok(Value::Number(value.into()))
+    }
+
+    fn visit_f64<E>(self, value: f64) -> Result<Value, E> {
+        Ok(Number::from_f64(value).map_or(Value::Null, Value::Number))
+    }
+
+    fn visit_str<E>(self, value: &str) -> Result<Value, E> {
+        Ok(Value::String(value.to_string()))
+    }
+
+    fn visit_string<E>(self, value: String) -> Result<Value, E> {
+        Ok(Value::String(value))
+    }
+
+    fn visit_unit<E>(self) -> Result<Value, E> {
+        Ok(Value::Null)
+    }
+
+    fn visit_seq<A: SeqAccess<'de>>(mut self, mut seq: A) -> Result<Value, A::Error> {
+        let mut values = vec![];
+        while let Some(value) = seq.next_element_seed(self.child(values.len().to_string()))? {
+            values.push(value);
+        }
+        Ok(Value::Array(values))
+    }
+
+    fn visit_map<A: MapAccess<'de>>(mut self, mut map: A) -> Result<Value, A::Error> {
+        let mut values = Map::new();
+        while let Some(key) = map.next_key::<String>()? {
+            let value = map.next_value_seed(self.child(key.clone()))?;
+            if values.insert(key.clone(), value).is_some() {
+                let mut path = self.path.clone();
+                path.push(key);
+                self.duplicates.push(path);
+            }
+        }
+        Ok(values.into())
+    }
+}
+
+/// Where a transition is defined within its state.
+enum TransitionField {
+    Next,
+    Default,
+    Choice(usize),
+    Catcher(usize),
+}
+
+impl TransitionField {
+    fn resolve(&self, state: &Pointer) -> Pointer {
+        match self {
+            TransitionField::Next => state.join("Next"),
+            TransitionField::Default => state.join("Default"),
+            TransitionField::Choice(index) => state.join("Choices").join(index).join("Next"),
+            TransitionField::Catcher(index) => state.join("Catch").join(index).join("Next"),
+        }
+    }
+}
+
+/// The names of the states a state can transition to, with where each transition is defined.
+fn transitions(state: &Value) -> Vec<(TransitionField, &str)> {
+    let rules = state["Choices"].as_array().into_iter().flatten().enumerate()
+        .map(|(index, rule)| (TransitionField::Choice(index), &rule["Next"]));
+    let catchers = state["Catch"].as_array().into_iter().flatten().enumerate()
+        .map(|(index, catcher)| (TransitionField::Catcher(index), &catcher["Next"]));
+    [(TransitionField::Next, &state["Next"]), (TransitionField::Default, &state["Default"])].into_iter()
+        .chain(rules)
+        .chain(catchers)
+        .filter_map(|(field, target)| Some((field, target.as_str()?)))
+        .collect()
+}
+
+/// Whether a state can end the execution of its scope.
+fn is_terminal(state: &Value) -> bool {
+    matches!(state["Type"].as_str(), Some("Succeed" | "Fail")) || state["End"] == Value::Bool(true)
+}
+
+fn reachable_states<'v>(start_at: &'v str, states: &'v Map<String, Value>) -> HashSet<&'v str> {
+    let mut reachable = HashSet::from([start_at]);
+    let mut pending = VecDeque::from([start_at]);
+    while let Some(name) = pending.pop_front() {
+        let targets = states.get(name).map(transitions).unwrap_or_default();
+        for (_, target) in targets {
+            if reachable.insert(target) {
+                pending.push_back(target);
+            }
+        }
+    }
+    reachable
+}
+
+/// A "Resource" MUST be a URI. Since handlers are registered by name in
+/// [Resources](crate::asl::resources::Resources), relative references such as `return` are accepted
+/// too, as long as they only use URI characters.
+///
+/// Definitions deployed with CloudFormation may also use definition substitutions such as
+/// `${LambdaArn}`, which are replaced before the URI is checked.
+fn is_valid_resource(resource: &str) -> bool {
+    let mut uri = String::new();
+    let mut rest = resource;
+    while let Some(start) = rest.find("${") {
+        let Some(length) = rest[start + 2..].find('}') else {
+            return false;
+        };
+        let name = &rest[start + 2..start + 2 + length];
+        if name.is_empty() || !name.chars().all(|c| c.is_ascii_alphanumeric() || "_-.:".contains(c)) {
+            return false;
+        }
+        uri.push_str(&rest[..start]);
+        uri.push_str("substitution");
+        rest = &rest[start + 2 + length + 1..];
+    }
+    uri.push_str(rest);
+    is_uri_reference(&uri) && is_valid_lambda_arn(&uri)
+}
+
+/// Checks that a URI reference only uses the characters allowed by
+/// https://www.rfc-editor.org/rfc/rfc3986#section-2
+fn is_uri_reference(uri: &str) -> bool {
+    !uri.is_empty() && uri.chars().all(|c| c.is_ascii_alphanumeric() || "-._~:/?#[]@!$&'()*+,;=%".contains(c))
+}
+
+/// Lambda function ARNs have the form `arn:<partition>:lambda:<region>:<account>:function:<name>`,
+/// optionally followed by `:<version or alias>`. Other ARNs are not checked.
+fn is_valid_lambda_arn(arn: &str) -> bool {
+    let parts: Vec<&str> = arn.split(':').collect();
+    if parts.first() != Some(&"arn") || parts.get(2) != Some(&"lambda") {
+        return true;
+    }
+    let is_name = |name: &str| !name.is_empty() && name.chars().all(|c| c.is_ascii_alphanumeric() || "-_".contains(c));
+    match parts.as_slice() {
+        [_, _, _, _, _, "function", name] => is_name(name),
+        [_, _, _, _, _, "function", name, qualifier] => is_name(name) && (*qualifier == "$LATEST" || is_name(qualifier)),
+        _ => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rstest::*;
+    use serde_json::json;
+    use anyhow::Result;
+
+    #[rstest]
+    #[case::arn("arn:aws:states:::lambda:invoke", true)]
+    #[case::lambda_function("arn:aws:lambda:us-east-1:123456789012:function:foo", true)]
+    #[case::lambda_alias("arn:aws:lambda:us-east-1:123456789012:function:foo:Prod-1", true)]
+    #[case::lambda_latest("arn:aws:lambda:us-east-1:123456789012:function:foo:$LATEST", true)]
+    #[case::other_scheme("https://example.com/task?id=1", true)]
+    #[case::name("return", true)]
+    #[case::substitution("${LambdaArn}", true)]
+    #[case::partial_substitution("arn:aws:lambda:${AWS::Region}:123456789012:function:foo", true)]
+    #[case::unclosed_substitution("${LambdaArn", false)]
+    #[case::empty_substitution("${}", false)]
+    #[case::empty("", false)]
+    #[case::space("arn:aws:states:::lambda: invoke", false)]
+    #[case::empty_lambda_alias("arn:aws:lambda:us-east-1:123456789012:function:foo:", false)]
+    #[case::invalid_lambda_alias("arn:aws:lambda:us-east-1:123456789012:function:foo:$*#", false)]
+    #[case::lambda_layer("arn:aws:lambda:us-east-1:123456789012:layer:foo", false)]
+    fn validate_resource(#[case] resource: &str, #[case] expected: bool) {
+        assert_eq!(is_valid_resource(resource), expected);
+    }
+
+    fn definition(states: Value) -> Value {
+        json!({ "StartAt": "Start", "States": states })
+    }
+
+    #[rstest]
+    #[case::loop_with_exit(definition(json!({
+        "Start": { "Type": "Pass", "Next": "Choice" },
+        "Choice": { "Type": "Choice", "Choices": [{ "Variable": "$.done", "BooleanEquals": false, "Next": "Start" }], "Default": "End" },
+        "End": { "Type": "Succeed" },
+    })))]
+    #[case::caught_error(definition(json!({
+        "Start": { "Type": "Task", "Resource": "arn:aws:states:::lambda:invoke", "Catch": [{ "ErrorEquals": ["States.ALL"], "Next": "Failed" }], "End": true },
+        "Failed": { "Type": "Fail" },
+    })))]
+    #[case::heartbeat_with_timeout_path(definition(json!({
+        "Start": { "Type": "Task", "Resource": "arn:aws:states:::lambda:invoke", "HeartbeatSeconds": 120, "TimeoutSecondsPath": "$.timeout", "End": true },
+    })))]
+    #[case::null_paths(definition(json!({
+        "Start": { "Type": "Pass", "InputPath": null, "OutputPath": null, "ResultPath": null, "End": true },
+    })))]
+    fn validate_valid(#[case] definition: Value) {
+        assert_eq!(validate(&definition, ParseMode::Strict).0, vec![]);
+    }
+
+    #[rstest]
+    #[case::unknown_start(
+        json!({ "StartAt": "Missing", "States": { "Start": { "Type": "Succeed" } } }),
+        DiagnosticCode::StartStateNotDefined, "/StartAt",
+        "The start state 'Missing' is not defined in \"States\"",
+    )]
+    #[case::unknown_default(
+        definition(json!({ "Start": { "Type": "Choice", "Choices": [{ "Variable": "$", "IsNull": true, "Next": "End" }], "Default": "Missing" }, "End": { "Type": "Succeed" } })),
+        DiagnosticCode::UnknownTransition, "/States/Start/Default",
+        "The state 'Start' transitions to the state 'Missing', which is not defined in its scope",
+    )]
+    #[case::unknown_catcher(
+        definition(json!({ "Start": { "Type": "Task", "Resource": "arn:aws:states:::lambda:invoke", "Catch": [{ "ErrorEquals": ["States.ALL"], "Next": "Missing" }], "End": true } })),
+        DiagnosticCode::UnknownTransition, "/States/Start/Catch/0/Next",
+        "The state 'Start' transitions to the state 'Missing', which is not defined in its scope",
+    )]
+    #[case::unknown_target_in_map(
+        definition(json!({ "Start": { "Type": "Map", "ItemProcessor": { "StartAt": "a/b", "States": {
+            "a/b": { "Type": "Choice", "Choices": [{ "Variable": "$", "IsNull": true, "Next": "Done" }], "Default": "Missing" },
+            "Done": { "Type": "Succeed" },
+        } }, "End": true } })),
+        DiagnosticCode::UnknownTransition, "/States/Start/ItemProcessor/States/a~1b/Default",
+        "The state 'a/b' transitions to the state 'Missing', which is not defined in its scope",
+    )]
+    #[case::unreachable_task(
+        definition(json!({ "Start": { "Type": "Succeed" }, "Draft": { "Type": "Task", "Resource": "arn:aws:states:::lambda:invoke", "End": true } })),
+        DiagnosticCode::UnreachableState, "/States/Draft",
+        "The state 'Draft' can't be reached from the start state of its scope",
+    )]
+    #[case::unreachable_choice(
+        definition(json!({ "Start": { "Type": "Succeed" }, "Orphan": { "Type": "Choice", "Choices": [{ "Variable": "$", "IsNull": true, "Next": "Start" }] } })),
+        DiagnosticCode::UnreachableState, "/States/Orphan",
+        "The state 'Orphan' can't be reached from the start state of its scope",
+    )]
+    #[case::duplicate_name_in_branch(
+        definition(json!({ "Start": { "Type": "Parallel", "Branches": [{ "StartAt": "Start", "States": { "Start": { "Type": "Succeed" } } }], "End": true } })),
+        DiagnosticCode::DuplicateStateName, "/States/Start/Branches/0/States/Start",
+        "The state name 'Start' is used more than once",
+    )]
+    #[case::heartbeat_not_smaller(
+        definition(json!({ "Start": { "Type": "Task", "Resource": "arn:aws:states:::lambda:invoke", "TimeoutSeconds": 10, "HeartbeatSeconds": 10, "End": true } })),
+        DiagnosticCode::InvalidFieldValue, "/States/Start/HeartbeatSeconds",
+        "Invalid value for the field 'HeartbeatSeconds' of the state 'Start': must be smaller than \"TimeoutSeconds\" (10), but it is 10",
+    )]
+    #[case::heartbeat_not_smaller_than_default(
+        definition(json!({ "Start": { "Type": "Task", "Resource": "arn:aws:states:::lambda:invoke", "HeartbeatSeconds": 60, "End": true } })),
+        DiagnosticCode::InvalidFieldValue, "/States/Start/HeartbeatSeconds",
+        "Invalid value for the field 'HeartbeatSeconds' of the state 'Start': must be smaller than \"TimeoutSeconds\" (60), but it is 60",
+    )]
+    #[case::zero_timeout(
+        definition(json!({ "Start": { "Type": "Task", "Resource": "arn:aws:states:::lambda:invoke", "TimeoutSeconds": 0, "End": true } })),
+        DiagnosticCode::InvalidFieldValue, "/States/Start/TimeoutSeconds",
+        "Invalid value for the field 'TimeoutSeconds' of the state 'Start': must be a positive integer, but it is 0",
+    )]
+    #[case::next_and_end(
+        definition(json!({ "Start": { "Type": "Pass", "Next": "End", "End": true }, "End": { "Type": "Succeed" } })),
+        DiagnosticCode::MutuallyExclusiveFields, "/States/Start/End",
+        "The state 'Start' can't have both the fields 'Next' and 'End'",
+    )]
+    #[case::missing_next_and_end(
+        definition(json!({
+            "Start": { "Type": "Choice", "Choices": [{ "Variable": "$", "IsNull": true, "Next": "Pass" }], "Default": "End" },
+            "Pass": { "Type": "Pass" },
+            "End": { "Type": "Succeed" },
+        })),
+        DiagnosticCode::MissingField, "/States/Pass",
+        "The state 'Pass' must have one of the fields 'Next' or 'End'",
+    )]
+    #[case::end_false(
+        definition(json!({
+            "Start": { "Type": "Choice", "Choices": [{ "Variable": "$", "IsNull": true, "Next": "Pass" }], "Default": "End" },
+            "Pass": { "Type": "Pass", "End": false },
+            "End": { "Type": "Succeed" },
+        })),
+        DiagnosticCode::InvalidFieldValue, "/States/Pass/End",
+        "Invalid value for the field 'End' of the state 'Pass': must be true, but it is false",
+    )]
+    #[case::missing_start_at(
+        json!({ "States": { "Start": { "Type": "Succeed" } } }),
+        DiagnosticCode::MissingField, "",
+        "The state machine must have the field 'StartAt'",
+    )]
+    #[case::missing_branch_states(
+        definition(json!({ "Start": { "Type": "Parallel", "Branches": [{ "StartAt": "A" }], "End": true } })),
+        DiagnosticCode::MissingField, "/States/Start/Branches/0",
+        "The state machine nested in the state 'Start' must have the field 'States'",
+    )]
+    #[case::missing_type(
+        definition(json!({ "Start": { "End": true } })),
+        DiagnosticCode::MissingField, "/States/Start",
+        "The state 'Start' must have the field 'Type'",
+    )]
+    #[case::unknown_type(
+        definition(json!({ "Start": { "Type": "Sleep", "End": true } })),
+        DiagnosticCode::InvalidFieldValue, "/States/Start/Type",
+        "Invalid value for the field 'Type' of the state 'Start': 'Sleep' is not a state type",
+    )]
+    #[case::missing_resource(
+        definition(json!({ "Start": { "Type": "Task", "End": true } })),
+        DiagnosticCode::MissingField, "/States/Start",
+        "The state 'Start' must have the field 'Resource'",
+    )]
+    #[case::catcher_without_next(
+        definition(json!({ "Start": { "Type": "Task", "Resource": "arn:aws:states:::lambda:invoke", "Catch": [{ "ErrorEquals": ["States.ALL"] }], "End": true } })),
+        DiagnosticCode::MissingField, "/States/Start/Catch/0",
+        "A Catcher of the state 'Start' must have the field 'Next'",
+    )]
+    #[case::choice_rule_without_next(
+        definition(json!({ "Start": { "Type": "Choice", "Choices": [{ "Variable": "$", "IsNull": true }], "Default": "End" }, "End": { "Type": "Succeed" } })),
+        DiagnosticCode::MissingField, "/States/Start/Choices/0",
+        "A Choice Rule of the state 'Start' must have the field 'Next'",
+    )]
+    #[case::choice_rule_path(
+        definition(json!({
+            "Start": { "Type": "Choice", "Choices": [{ "Not": { "Variable": "$.a", "StringEqualsPath": "$.b[*]" }, "Next": "End" }] },
+            "End": { "Type": "Succeed" },
+        })),
+        DiagnosticCode::InvalidFieldValue, "/States/Start/Choices/0/Not/StringEqualsPath",
+        "Invalid value for the field 'StringEqualsPath' of the state 'Start': '$.b[*]' is not a Reference Path: wildcards are not allowed",
+    )]
+    #[case::choice_rule_timestamp(
+        definition(json!({
+            "Start": { "Type": "Choice", "Choices": [{ "Variable": "$.a", "TimestampEquals": "nope", "Next": "End" }] },
+            "End": { "Type": "Succeed" },
+        })),
+        DiagnosticCode::InvalidFieldValue, "/States/Start/Choices/0/TimestampEquals",
+        "Invalid value for the field 'TimestampEquals' of the state 'Start': 'nope' is not an RFC3339 timestamp: premature end of input",
+    )]
+    #[case::items_path(
+        definition(json!({ "Start": { "Type": "Map", "ItemsPath": "$..items", "ItemProcessor": { "StartAt": "A", "States": { "A": { "Type": "Succeed" } } }, "End": true } })),
+        DiagnosticCode::InvalidFieldValue, "/States/Start/ItemsPath",
+        "Invalid value for the field 'ItemsPath' of the state 'Start': '$..items' is not a Reference Path: deep scans are not allowed",
+    )]
+    #[case::item_reader_parameters(
+        definition(json!({ "Start": {
+            "Type": "Map",
+            "ItemReader": { "Resource": "arn:aws:states:::s3:getObject", "Parameters": { "Key.$": 1 } },
+            "ItemProcessor": { "StartAt": "A", "States": { "A": { "Type": "Succeed" } } },
+            "End": true,
+        } })),
+        DiagnosticCode::InvalidFieldValue, "/States/Start/ItemReader/Parameters",
+        "Invalid value for the field 'Parameters' of the state 'Start': The value of the field 'Key.$' must be a string",
+    )]
+    #[case::item_batcher_zero_limit(
+        definition(json!({ "Start": { "Type": "Map", "ItemBatcher": { "MaxItemsPerBatch": 0 }, "ItemProcessor": { "StartAt": "A", "States": { "A": { "Type": "Succeed" } } }, "End": true } })),
+        DiagnosticCode::InvalidFieldValue, "/States/Start/ItemBatcher/MaxItemsPerBatch",
+        "Invalid value for the field 'MaxItemsPerBatch' of the state 'Start': must be a positive integer, but it is 0",
+    )]
+    #[case::item_batcher_string_limit(
+        definition(json!({ "Start": { "Type": "Map", "ItemBatcher": { "MaxInputBytesPerBatch": "x" }, "ItemProcessor": { "StartAt": "A", "States": { "A": { "Type": "Succeed" } } }, "End": true } })),
+        DiagnosticCode::InvalidFieldValue, "/States/Start/ItemBatcher/MaxInputBytesPerBatch",
+        r#"Invalid value for the field 'MaxInputBytesPerBatch' of the state 'Start': must be a positive integer, but it is "x""#,
+    )]
+    #[case::reader_config_max_items(
+        definition(json!({ "Start": {
+            "Type": "Map",
+            "ItemReader": { "Resource": "arn:aws:states:::s3:getObject", "ReaderConfig": { "MaxItems": -1 } },
+            "ItemProcessor": { "StartAt": "A", "States": { "A": { "Type": "Succeed" } } },
+            "End": true,
+        } })),
+        DiagnosticCode::InvalidFieldValue, "/States/Start/ItemReader/ReaderConfig/MaxItems",
+        "Invalid value for the field 'MaxItems' of the state 'Start': must be a positive integer, but it is -1",
+    )]
+    #[case::reader_config_string_max_items(
+        definition(json!({ "Start": {
+            "Type": "Map",
+            "ItemReader": { "Resource": "arn:aws:states:::s3:getObject", "ReaderConfig": { "MaxItems": "x" } },
+            "ItemProcessor": { "StartAt": "A", "States": { "A": { "Type": "Succeed" } } },
+            "End": true,
+        } })),
+        DiagnosticCode::InvalidFieldValue, "/States/Start/ItemReader/ReaderConfig/MaxItems",
+        r#"Invalid value for the field 'MaxItems' of the state 'Start': must be a positive integer, but it is "x""#,
+    )]
+    #[case::path_not_a_string(
+        definition(json!({ "Start": { "Type": "Pass", "OutputPath": 1, "End": true } })),
+        DiagnosticCode::InvalidFieldValue, "/States/Start/OutputPath",
+        "Invalid value for the field 'OutputPath' of the state 'Start': must be a string, but it is 1",
+    )]
+    #[case::max_concurrency_string(
+        definition(json!({ "Start": { "Type": "Map", "MaxConcurrency": "x", "ItemProcessor": { "StartAt": "A", "States": { "A": { "Type": "Succeed" } } }, "End": true } })),
+        DiagnosticCode::InvalidFieldValue, "/States/Start/MaxConcurrency",
+        r#"Invalid value for the field 'MaxConcurrency' of the state 'Start': must be a non-negative integer, but it is "x""#,
+    )]
+    #[case::max_concurrency_negative(
+        definition(json!({ "Start": { "Type": "Map", "MaxConcurrency": -1, "ItemProcessor": { "StartAt": "A", "States": { "A": { "Type": "Succeed" } } }, "End": true } })),
+        DiagnosticCode::InvalidFieldValue, "/States/Start/MaxConcurrency",
+        "Invalid value for the field 'MaxConcurrency' of the state 'Start': must be a non-negative integer, but it is -1",
+    )]
+    #[case::retrier_max_attempts(
+        definition(json!({ "Start": { "Type": "Task", "Resource": "arn:aws:states:::lambda:invoke", "Retry": [{ "ErrorEquals": ["States.ALL"], "MaxAttempts": -1 }], "End": true } })),
+        DiagnosticCode::InvalidFieldValue, "/States/Start/Retry/0/MaxAttempts",
+        "Invalid value for the field 'MaxAttempts' of the state 'Start': must be a non-negative integer, but it is -1",
+    )]
+    #[case::retrier_backoff_rate(
+        definition(json!({ "Start": { "Type": "Task", "Resource": "arn:aws:states:::lambda:invoke", "Retry": [{ "ErrorEquals": ["States.ALL"], "BackoffRate": 0.5 }], "End": true } })),
+        DiagnosticCode::InvalidFieldValue, "/States/Start/Retry/0/BackoffRate",
+        "Invalid value for the field 'BackoffRate' of the state 'Start': must be a number greater than or equal to 1.0, but it is 0.5",
+    )]
+    #[case::retrier_jitter_strategy(
+        definition(json!({ "Start": { "Type": "Task", "Resource": "arn:aws:states:::lambda:invoke", "Retry": [{ "ErrorEquals": ["States.ALL"], "JitterStrategy": "HALF" }], "End": true } })),
+        DiagnosticCode::InvalidFieldValue, "/States/Start/Retry/0/JitterStrategy",
+        r#"Invalid value for the field 'JitterStrategy' of the state 'Start': must be one of 'FULL' or 'NONE', but it is "HALF""#,
+    )]
+    #[case::retrier_not_an_object(
+        definition(json!({ "Start": { "Type": "Task", "Resource": "arn:aws:states:::lambda:invoke", "Retry": ["States.ALL"], "End": true } })),
+        DiagnosticCode::InvalidFieldValue, "/States/Start/Retry/0",
+        r#"A Retrier of the state 'Start' must be an object, but it is "States.ALL""#,
+    )]
+    #[case::error_equals_string(
+        definition(json!({ "Start": { "Type": "Task", "Resource": "arn:aws:states:::lambda:invoke", "Catch": [{ "ErrorEquals": "A", "Next": "Start" }], "End": true } })),
+        DiagnosticCode::InvalidFieldValue, "/States/Start/Catch/0/ErrorEquals",
+        r#"Invalid value for the field 'ErrorEquals' of the state 'Start': must be a non-empty array of Error Names, but it is "A""#,
+    )]
+    #[case::error_equals_empty(
+        definition(json!({ "Start": { "Type": "Task", "Resource": "arn:aws:states:::lambda:invoke", "Retry": [{ "ErrorEquals": [] }], "End": true } })),
+        DiagnosticCode::InvalidFieldValue, "/States/Start/Retry/0/ErrorEquals",
+        "Invalid value for the field 'ErrorEquals' of the state 'Start': must be a non-empty array of Error Names, but it is []",
+    )]
+    #[case::processor_mode(
+        definition(json!({ "Start": {
+            "Type": "Map",
+            "ItemProcessor": { "ProcessorConfig": { "Mode": "REMOTE" }, "StartAt": "A", "States": { "A": { "Type": "Succeed" } } },
+            "End": true,
+        } })),
+        DiagnosticCode::InvalidFieldValue, "/States/Start/ItemProcessor/ProcessorConfig/Mode",
+        r#"Invalid value for the field 'Mode' of the state 'Start': must be one of 'INLINE' or 'DISTRIBUTED', but it is "REMOTE""#,
+    )]
+    #[case::reader_input_type(
+        definition(json!({ "Start": {
+            "Type": "Map",
+            "ItemReader": { "Resource": "arn:aws:states:::s3:getObject", "ReaderConfig": { "InputType": "XML" } },
+            "ItemProcessor": { "StartAt": "A", "States": { "A": { "Type": "Succeed" } } },
+            "End": true,
+        } })),
+        DiagnosticCode::InvalidFieldValue, "/States/Start/ItemReader/ReaderConfig/InputType",
+        r#"Invalid value for the field 'InputType' of the state 'Start': must be one of 'CSV', 'JSON', 'JSONL', 'MANIFEST' or 'PARQUET', but it is "XML""#,
+    )]
+    #[case::resource_not_a_string(
+        definition(json!({ "Start": { "Type": "Task", "Resource": 1, "End": true } })),
+        DiagnosticCode::InvalidFieldValue, "/States/Start/Resource",
+        "Invalid value for the field 'Resource' of the state 'Start': must be a string, but it is 1",
+    )]
+    #[case::version_not_a_string(
+        json!({ "StartAt": "Start", "Version": 1, "States": { "Start": { "Type": "Succeed" } } }),
+        DiagnosticCode::InvalidFieldValue, "/Version",
+        "Invalid value for the field 'Version': must be a string, but it is 1",
+    )]
+    #[case::state_machine_timeout(
+        json!({ "StartAt": "Start", "TimeoutSeconds": "x", "States": { "Start": { "Type": "Succeed" } } }),
+        DiagnosticCode::InvalidFieldValue, "/TimeoutSeconds",
+        r#"Invalid value for the field 'TimeoutSeconds': must be a positive integer, but it is "x""#,
+    )]
+    #[case::missing_wait_duration(
+        definition(json!({ "Start": { "Type": "Wait", "End": true } })),
+        DiagnosticCode::MissingField, "/States/Start",
+        "The state 'Start' must have one of the fields 'Seconds', 'SecondsPath', 'Timestamp' or 'TimestampPath'",
+    )]
+    #[case::item_selector_and_parameters(
+        definition(json!({ "Start": { "Type": "Map", "ItemSelector": {}, "Parameters": {}, "ItemProcessor": { "StartAt": "Item", "States": { "Item": { "Type": "Succeed" } } }, "End": true } })),
+        DiagnosticCode::MutuallyExclusiveFields, "/States/Start/Parameters",
+        "The state 'Start' can't have both the fields 'ItemSelector' and 'Parameters'",
+    )]
+    #[case::item_processor_and_iterator(
+        definition(json!({
+            "Start": {
+                "Type": "Map",
+                "ItemProcessor": { "StartAt": "Item", "States": { "Item": { "Type": "Succeed" } } },
+                "Iterator": { "StartAt": "Other", "States": { "Other": { "Type": "Succeed" } } },
+                "End": true,
+            },
+        })),
+        DiagnosticCode::MutuallyExclusiveFields, "/States/Start/Iterator",
+        "The state 'Start' can't have both the fields 'ItemProcessor' and 'Iterator'",
+    )]
+    #[case::choice_operators(
+        definition(json!({ "Start": { "Type": "Choice", "Choices": [{ "Variable": "$", "StringEquals": "a", "NumericEquals": 1, "Next": "End" }] }, "End": { "Type": "Succeed" } })),
+        DiagnosticCode::MutuallyExclusiveFields, "/States/Start/Choices/0/NumericEquals",
+        "A Choice Rule of the state 'Start' can't have both the operators 'StringEquals' and 'NumericEquals'",
+    )]
+    #[case::nested_choice_operators(
+        definition(json!({ "Start": { "Type": "Choice", "Choices": [{ "Not": { "Variable": "$", "IsNull": true, "IsString": true }, "Next": "End" }] }, "End": { "Type": "Succeed" } })),
+        DiagnosticCode::MutuallyExclusiveFields, "/States/Start/Choices/0/Not/IsString",
+        "A Choice Rule of the state 'Start' can't have both the operators 'IsNull' and 'IsString'",
+    )]
+    #[case::missing_choice_operator(
+        definition(json!({ "Start": { "Type": "Choice", "Choices": [{ "Or": [{ "Variable": "$", "Next": "End" }], "Next": "End" }] }, "End": { "Type": "Succeed" } })),
+        DiagnosticCode::MissingField, "/States/Start/Choices/0/Or/0",
+        "A Choice Rule of the state 'Start' must have an operator, such as 'StringEquals' or 'And'",
+    )]
+    fn validate_invalid(#[case] definition: Value, #[case] code: DiagnosticCode, #[case] pointer: &str, #[case] message: &str) {
+        let diagnostics = validate(&definition, ParseMode::Strict).0;
+        let found = diagnostics.iter().map(|diagnostic| (diagnostic.code, diagnostic.pointer.as_str(), diagnostic.message.as_str())).collect::<Vec<_>>();
+        assert_eq!(found, vec![(code, pointer, message)]);
+    }
+
+    #[rstest]
+    fn validate_lenient_allows_unreachable_states() {
+        let definition = definition(json!({ "Start": { "Type": "Succeed" }, "Draft": { "Type": "Pass", "End": true } }));
+
+        assert!(validate(&definition, ParseMode::Lenient).0.is_empty());
+    }
+
+    #[rstest]
+    fn validate_collects_every_problem() {
+        let definition = definition(json!({
+            "Start": { "Type": "Pass", "Next": "Missing" },
+            "Map": {
+                "Type": "Map",
+                "ItemProcessor": {
+                    "StartAt": "LambdaTask",
+                    "States": { "LambdaTask": { "Type": "Task", "Resource": "${lambdaArn", "TimeoutSeconds": 0, "Next": "Final" } },
+                },
+                "End": true,
+            },
+        }));
+
+        let diagnostics = validate(&definition, ParseMode::Strict).0;
+
+        let found = diagnostics.iter().map(|diagnostic| (diagnostic.code, diagnostic.pointer.as_str(), diagnostic.state.as_deref())).collect::<Vec<_>>();
+        assert_eq!(found, vec![
+            (DiagnosticCode::UnknownTransition, "/States/Map/ItemProcessor/States/LambdaTask/Next", Some("LambdaTask")),
+            (DiagnosticCode::InvalidFieldValue, "/States/Map/ItemProcessor/States/LambdaTask/TimeoutSeconds", Some("LambdaTask")),
+            (DiagnosticCode::InvalidFieldValue, "/States/Map/ItemProcessor/States/LambdaTask/Resource", Some("LambdaTask")),
+            (DiagnosticCode::MissingTerminalState, "/States/Map/ItemProcessor/States", None),
+            (DiagnosticCode::UnknownTransition, "/States/Start/Next", Some("Start")),
+            (DiagnosticCode::UnreachableState, "/States/Map", Some("Map")),
+        ]);
+    }
+
+    #[rstest]
+    #[case::state_name(
+        r#"{ "StartAt": "A", "States": { "A": { "Type": "Succeed" }, "A": { "Type": "Fail" } } }"#,
+        DiagnosticCode::DuplicateStateName, "/States/A", Some("A"),
+        "The state name 'A' is used more than once",
+    )]
+    #[case::state_name_in_map(
+        r#"{ "StartAt": "M", "States": { "M": { "Type": "Map", "ItemProcessor": { "StartAt": "A", "States": { "A": { "Type": "Succeed" }, "A": { "Type": "Succeed" } } }, "End": true } } }"#,
+        DiagnosticCode::DuplicateStateName, "/States/M/ItemProcessor/States/A", Some("A"),
+        "The state name 'A' is used more than once",
+    )]
+    #[case::state_field(
+        r#"{ "StartAt": "A", "States": { "A": { "Type": "Pass", "Type": "Succeed" } } }"#,
+        DiagnosticCode::DuplicateField, "/States/A/Type", Some("A"),
+        "The state 'A' defines the field 'Type' more than once",
+    )]
+    #[case::nested_field(
+        r#"{ "StartAt": "A", "States": { "A": { "Type": "Pass", "Parameters": { "States": { "B": 1, "B": 2 } }, "End": true } } }"#,
+        DiagnosticCode::DuplicateField, "/States/A/Parameters/States/B", Some("A"),
+        "The state 'A' defines the field 'B' more than once",
+    )]
+    #[case::top_level_field(
+        r#"{ "StartAt": "A", "StartAt": "A", "States": { "A": { "Type": "Succeed" } } }"#,
+        DiagnosticCode::DuplicateField, "/StartAt", None,
+        "The field 'StartAt' is defined more than once",
+    )]
+    fn parse_duplicate_keys(
+        #[case] definition: &str,
+        #[case] code: DiagnosticCode,
+        #[case] pointer: &str,
+        #[case] state: Option<&str>,
+        #[case] message: &str,
+    ) -> Result<()> {
+        let (_, diagnostics) = parse(definition)?;
+        let found = diagnostics.iter().map(|diagnostic| (diagnostic.code, diagnostic.pointer.as_str(), diagnostic.state.as_deref(), diagnostic.message.as_str())).collect::<Vec<_>>();
+        assert_eq!(found, vec![(code, pointer, state, message)]);
+        Ok(())
+    }
+
+    #[rstest]
+    fn parse_keeps_values() -> Result<()> {
+        let definition = r#"{ "a": [1, -2, 3.5, "b", true, null, { "c": {} }] }"#;
+        let (value, diagnostics) = parse(definition)?;
+        assert_eq!(value, serde_json::from_str::<Value>(definition)?);
+        assert_eq!(diagnostics, vec![]);
+        Ok(())
+    }
+}